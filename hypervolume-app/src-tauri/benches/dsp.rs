@@ -0,0 +1,100 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hypervolume_app_lib::simd::{self, scalar};
+use hypervolume_app_lib::{
+    AudioFormat, AudioProcessor, AutoGainSettings, BoostControl, ClipCurve, ClipperSettings, DynamicsMode, EqBand,
+    EqPreset, FilterType, MultibandSettings,
+};
+
+// One 10 ms stereo block at 48 kHz, what a WASAPI shared-mode callback usually delivers
+const FRAMES: usize = 480;
+const CHANNELS: u16 = 2;
+
+fn test_signal(len: usize) -> Vec<f32> {
+    (0..len).map(|index| 1.5 * (index as f32 * 0.01).sin()).collect()
+}
+
+fn kernels(c: &mut Criterion) {
+    let samples = test_signal(FRAMES * CHANNELS as usize);
+    let mut group = c.benchmark_group("kernels");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+    // Named after the instruction set the dispatch picked, so results from different CPUs
+    // are not compared by accident
+    let dispatched = format!("dispatched-{:?}", simd::simd_level());
+
+    type Kernel = fn(&mut [f32], f32);
+    let kernels: [(&str, Kernel, Kernel); 3] = [
+        ("apply_gain", simd::apply_gain, scalar::apply_gain),
+        ("hard_clip", simd::hard_clip, scalar::hard_clip),
+        ("cubic_clip", simd::cubic_clip, scalar::cubic_clip),
+    ];
+    for (name, vectorized, fallback) in kernels {
+        for (variant, kernel) in [(dispatched.as_str(), vectorized), ("scalar", fallback)] {
+            group.bench_function(BenchmarkId::new(name, variant), |b| {
+                let mut buffer = samples.clone();
+                b.iter(|| {
+                    buffer.copy_from_slice(&samples);
+                    kernel(black_box(&mut buffer), black_box(0.8));
+                })
+            });
+        }
+    }
+
+    // Filter lengths of the true-peak detector and the clipper's 4x decimation
+    for taps in [12, 65] {
+        let a = test_signal(taps);
+        let b = test_signal(taps + 1);
+        group.bench_function(BenchmarkId::new("dot", format!("{}/{}", dispatched, taps)), |bench| {
+            bench.iter(|| simd::dot(black_box(&a), black_box(&b[1..])))
+        });
+        group.bench_function(BenchmarkId::new("dot", format!("scalar/{}", taps)), |bench| {
+            bench.iter(|| scalar::dot(black_box(&a), black_box(&b[1..])))
+        });
+    }
+    group.finish();
+}
+
+fn processing_path(c: &mut Criterion) {
+    let format = AudioFormat::new(48000, CHANNELS);
+    let samples = test_signal(FRAMES * CHANNELS as usize);
+    let mut group = c.benchmark_group("processing_path");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+
+    // Boost and the true-peak limiter only, as every boosted session runs
+    let default_control = BoostControl::new(2.0);
+
+    // Every stage at its most expensive
+    let full_control = BoostControl::new(2.0);
+    full_control.set_auto_gain(Some(AutoGainSettings::default()));
+    full_control.set_dynamics(DynamicsMode::Multiband(MultibandSettings::default()));
+    full_control.set_equalizer(EqPreset {
+        preamp_db: -3.0,
+        bands: (0..8)
+            .map(|band| EqBand {
+                filter: FilterType::Peaking,
+                frequency_hz: 60.0 * 2.0f32.powi(band),
+                gain_db: 3.0,
+                q: 1.0,
+            })
+            .collect(),
+    });
+    full_control.set_clipper(Some(ClipperSettings {
+        curve: ClipCurve::Tanh,
+        ceiling_db: -1.0,
+        oversampling: 4,
+    }));
+
+    for (name, control) in [("default", &default_control), ("all_stages", &full_control)] {
+        group.bench_function(name, |b| {
+            let mut processor = AudioProcessor::for_control(format, control);
+            let mut buffer = samples.clone();
+            b.iter(|| {
+                buffer.copy_from_slice(&samples);
+                processor.process_with_control(black_box(&mut buffer), control);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, kernels, processing_path);
+criterion_main!(benches);
@@ -0,0 +1,194 @@
+use crate::audio_boost::AudioBoostManager;
+use crate::audio_manager::{stable_id, AudioBackend, AudioSession, SessionState};
+
+// All sessions of one application, e.g. every renderer process of a browser
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AppGroup {
+    pub id: String,
+    pub name: String,
+    pub executable_path: String,
+    pub icon_path: String,
+    // Loudest member, so the group slider never understates what is playing
+    pub volume: f32,
+    // True only when every member is muted
+    pub muted: bool,
+    pub state: SessionState,
+    pub session_ids: Vec<String>,
+    pub process_ids: Vec<u32>,
+}
+
+pub fn group_not_found(group_id: &str) -> String {
+    format!("Application group {} not found", group_id)
+}
+
+// Identifies the application a session belongs to, the same across its sessions and runs.
+// Sessions are grouped by executable path (case-insensitive, as on Windows). Sessions
+// without a path fall back to their display name, and nameless ones stay on their own.
+pub fn app_key(session: &AudioSession) -> String {
+    if !session.executable_path.is_empty() {
+        format!("exe|{}", session.executable_path.to_lowercase())
+    } else if !session.display_name.is_empty() {
+        format!("name|{}", session.display_name)
+    } else if session.process_id == 0 {
+        "system".to_string()
+    } else {
+        format!("session|{}", session.id)
+    }
+}
+
+// Groups in the order their first session appears in `sessions`
+pub fn group_sessions(backend: &dyn AudioBackend, sessions: &[AudioSession]) -> Vec<AppGroup> {
+    let mut keys: Vec<String> = Vec::new();
+    let mut members: Vec<Vec<&AudioSession>> = Vec::new();
+
+    for session in sessions {
+        let key = app_key(session);
+        match keys.iter().position(|existing| *existing == key) {
+            Some(index) => members[index].push(session),
+            None => {
+                keys.push(key);
+                members.push(vec![session]);
+            }
+        }
+    }
+
+    keys.iter()
+        .zip(members)
+        .map(|(key, members)| build_group(backend, key, &members))
+        .collect()
+}
+
+fn build_group(backend: &dyn AudioBackend, key: &str, members: &[&AudioSession]) -> AppGroup {
+    let first = members[0];
+
+    let state = if members.iter().any(|session| session.state == SessionState::Active) {
+        SessionState::Active
+    } else if members.iter().all(|session| session.state == SessionState::Expired) {
+        SessionState::Expired
+    } else {
+        SessionState::Inactive
+    };
+
+    let mut process_ids: Vec<u32> = members.iter().map(|session| session.process_id).collect();
+    process_ids.sort_unstable();
+    process_ids.dedup();
+
+    // The shell can extract an icon from the executable when no session set one
+    let icon_path = members.iter()
+        .map(|session| session.icon_path.as_str())
+        .find(|icon_path| !icon_path.is_empty())
+        .unwrap_or(&first.executable_path)
+        .to_string();
+
+    AppGroup {
+        id: stable_id(&["app", key]),
+        name: resolve_app_name(backend, members),
+        executable_path: first.executable_path.clone(),
+        icon_path,
+        volume: members.iter().map(|session| session.volume).fold(0.0, f32::max),
+        muted: members.iter().all(|session| session.muted),
+        state,
+        session_ids: members.iter().map(|session| session.id.clone()).collect(),
+        process_ids,
+    }
+}
+
+// Picks the first usable of: a member's display name, the executable's product name,
+// the executable file name. Names starting with '@' are unresolved resource references
+// such as "@%SystemRoot%\System32\AudioSrv.Dll,-202".
+fn resolve_app_name(backend: &dyn AudioBackend, members: &[&AudioSession]) -> String {
+    let display_name = members.iter()
+        .map(|session| session.display_name.trim())
+        .find(|name| !name.is_empty() && !name.starts_with('@'));
+    if let Some(display_name) = display_name {
+        return display_name.to_string();
+    }
+
+    let first = members[0];
+    if first.executable_path.is_empty() {
+        return if first.process_id == 0 {
+            "System Sounds".to_string()
+        } else {
+            format!("Process {}", first.process_id)
+        };
+    }
+
+    backend.describe_executable(&first.executable_path)
+        .unwrap_or_else(|| executable_name(&first.executable_path))
+}
+
+// "C:\Program Files\Discord\Discord.exe" -> "Discord", "/usr/bin/firefox" -> "Firefox"
+pub fn executable_name(executable_path: &str) -> String {
+    let file_name = executable_path.rsplit(['\\', '/']).next().unwrap_or(executable_path);
+    let stem = match file_name.rfind('.') {
+        Some(dot) if file_name[dot..].eq_ignore_ascii_case(".exe") => &file_name[..dot],
+        _ => file_name,
+    };
+
+    let mut chars = stem.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+pub fn enumerate_groups(backend: &dyn AudioBackend) -> Result<Vec<AppGroup>, String> {
+    Ok(group_sessions(backend, &backend.enumerate_sessions()?))
+}
+
+pub fn find_group(backend: &dyn AudioBackend, group_id: &str) -> Result<AppGroup, String> {
+    enumerate_groups(backend)?
+        .into_iter()
+        .find(|group| group.id == group_id)
+        .ok_or_else(|| group_not_found(group_id))
+}
+
+// Applies `f` to every member; a member that fails does not stop the others
+fn fan_out(group: &AppGroup, mut f: impl FnMut(&str) -> Result<(), String>) -> Result<(), String> {
+    let errors: Vec<String> = group.session_ids.iter()
+        .filter_map(|session_id| f(session_id).err())
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+// Sets every member's volume, with the boost of a higher setting taken back
+pub fn set_group_volume(
+    manager: &AudioBoostManager,
+    backend: &dyn AudioBackend,
+    group_id: &str,
+    volume: f32,
+) -> Result<(), String> {
+    let group = find_group(backend, group_id)?;
+    fan_out(&group, |session_id| {
+        if manager.get_boost(session_id)? != 1.0 {
+            manager.set_boost(session_id, 1.0)?;
+        }
+        backend.set_session_volume(session_id, volume)
+    })
+}
+
+pub fn set_group_mute(backend: &dyn AudioBackend, group_id: &str, muted: bool) -> Result<(), String> {
+    let group = find_group(backend, group_id)?;
+    fan_out(&group, |session_id| backend.set_session_mute(session_id, muted))
+}
+
+// Mutes the whole group unless every member is already muted
+pub fn toggle_group_mute(backend: &dyn AudioBackend, group_id: &str) -> Result<(), String> {
+    let muted = !find_group(backend, group_id)?.muted;
+    set_group_mute(backend, group_id, muted)
+}
+
+pub fn set_group_boost(
+    manager: &AudioBoostManager,
+    backend: &dyn AudioBackend,
+    group_id: &str,
+    boost_factor: f32,
+) -> Result<(), String> {
+    let group = find_group(backend, group_id)?;
+    fan_out(&group, |session_id| manager.set_boost(session_id, boost_factor))
+}
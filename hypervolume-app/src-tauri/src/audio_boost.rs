@@ -1,201 +1,881 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
-use windows::core::HSTRING;
-use windows::Win32::Foundation::PWSTR;
-use windows::Win32::Media::Audio::CoreAudio::{
-    eRender,
-    IMMDeviceEnumerator,
-    MMDeviceEnumerator,
-    DEVICE_STATE_ACTIVE,
-    IAudioSessionManager2,
-    ISimpleAudioVolume,
-    IAudioSessionControl2,
-    IAudioClient,
-    AUDCLNT_SHAREMODE_SHARED,
-    WAVEFORMATEX,
-};
-use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_APARTMENTTHREADED};
-
-// Structure to hold boost settings for each process
+use std::time::Duration;
+
+use crate::audio_endpoints::AudioFormat;
+use crate::app_groups::app_key;
+use crate::audio_manager::{session_not_found, AudioBackend};
+use crate::audio_pipeline::{BoostPipeline, PipelineRunner};
+use crate::virtual_device::StreamStats;
+use crate::clipper::{ClipCurve, Clipper, ClipperSettings};
+use crate::compressor::{Dynamics, DynamicsMode};
+use crate::equalizer::{EqControl, EqPreset, EqPresetStore, Equalizer};
+use crate::gain_ramp::{GainRamp, RampSettings, RampShape};
+use crate::limiter::{db_to_linear, LimiterSettings, LookaheadLimiter};
+use crate::loudness::{AutoGain, AutoGainSettings, LoudnessMeter, LoudnessReading};
+use crate::metering::{LevelMeter, MeterReading, MeterSettings};
+use crate::processor::{ChainAttachment, ChainControl, ChainDescription, LatencyReport, ProcessorChain};
+use crate::resampler::ResamplerQuality;
+
+// Highest level boosted audio may reach after D/A conversion, in dBTP
+pub const DEFAULT_TRUE_PEAK_CEILING_DBTP: f32 = -1.0;
+pub const MIN_TRUE_PEAK_CEILING_DBTP: f32 = -20.0;
+pub const MAX_TRUE_PEAK_CEILING_DBTP: f32 = 0.0;
+
+// Highest boost the volume slider reaches, 777%
+pub const MAX_BOOST_FACTOR: f32 = 7.77;
+
+// Structure to hold boost settings for each session
 #[derive(Debug, Clone)]
 pub struct BoostSettings {
     pub boost_factor: f32,  // 1.0 = 100%, 7.77 = 777%
     pub enabled: bool,
+    pub true_peak_ceiling_dbtp: f32,
+    // When set, the gain follows the session's loudness instead of `boost_factor`
+    pub auto_gain: Option<AutoGainSettings>,
+    // Compression between the boost and the limiter
+    pub dynamics: DynamicsMode,
+    // How changes of the boost factor and auto-gain are ramped in
+    pub gain_ramp: RampSettings,
+    // Saturation ahead of the limiter; None leaves the peaks to the limiter alone
+    pub clipper: Option<ClipperSettings>,
+    // Effects of the session's own, after the dynamics
+    pub chain: ChainDescription,
+    // Sample-rate conversion of pipelines whose endpoints run at different rates
+    pub resampler_quality: ResamplerQuality,
+}
+
+impl Default for BoostSettings {
+    fn default() -> Self {
+        Self {
+            boost_factor: 1.0,
+            enabled: false,
+            true_peak_ceiling_dbtp: DEFAULT_TRUE_PEAK_CEILING_DBTP,
+            auto_gain: None,
+            dynamics: DynamicsMode::Off,
+            gain_ramp: RampSettings::default(),
+            clipper: None,
+            chain: ChainDescription::default(),
+            resampler_quality: ResamplerQuality::default(),
+        }
+    }
+}
+
+// f32 stored as its bits, so the audio thread reads and writes it without taking a lock.
+// NaN stands for "not set".
+#[derive(Debug)]
+struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    fn new(value: f32) -> Self {
+        Self(AtomicU32::new(value.to_bits()))
+    }
+
+    fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get_option(&self) -> Option<f32> {
+        Some(self.get()).filter(|value| !value.is_nan())
+    }
+
+    fn set_option(&self, value: Option<f32>) {
+        self.set(value.unwrap_or(f32::NAN));
+    }
+}
+
+#[derive(Debug)]
+struct SharedBoost {
+    boost_factor: AtomicF32,
+    true_peak_ceiling_dbtp: AtomicF32,
+    // Auto-gain settings; a NaN target means auto-gain is off
+    target_lufs: AtomicF32,
+    max_gain_db: AtomicF32,
+    max_slew_db_per_second: AtomicF32,
+    // Published by the audio thread
+    auto_gain_db: AtomicF32,
+    momentary_lufs: AtomicF32,
+    short_term_lufs: AtomicF32,
+    integrated_lufs: AtomicF32,
+    // Too large for atomics; the audio thread only try-locks it after `dynamics_version`
+    // moved, so the manager never blocks it
+    dynamics: Mutex<DynamicsMode>,
+    dynamics_version: AtomicU32,
+    // Same as `dynamics`
+    clipper: Mutex<Option<ClipperSettings>>,
+    clipper_version: AtomicU32,
+    equalizer: EqControl,
+    ramp_shape: AtomicU8,
+    ramp_duration_ms: AtomicF32,
+    chain: ChainControl,
+    // Published by the audio thread; a zero sample rate means nothing was processed yet
+    latency_frames: AtomicU32,
+    latency_sample_rate: AtomicU32,
+    // Levels of the processed audio; None before the first block
+    levels: Mutex<Option<MeterReading>>,
+}
+
+// Boost settings shared between the manager and the pipelines processing a session, and
+// the loudness those pipelines measure in return
+#[derive(Debug, Clone)]
+pub struct BoostControl(Arc<SharedBoost>);
+
+impl BoostControl {
+    pub fn new(boost_factor: f32) -> Self {
+        Self(Arc::new(SharedBoost {
+            boost_factor: AtomicF32::new(boost_factor),
+            true_peak_ceiling_dbtp: AtomicF32::new(DEFAULT_TRUE_PEAK_CEILING_DBTP),
+            target_lufs: AtomicF32::new(f32::NAN),
+            max_gain_db: AtomicF32::new(0.0),
+            max_slew_db_per_second: AtomicF32::new(0.0),
+            auto_gain_db: AtomicF32::new(0.0),
+            momentary_lufs: AtomicF32::new(f32::NAN),
+            short_term_lufs: AtomicF32::new(f32::NAN),
+            integrated_lufs: AtomicF32::new(f32::NAN),
+            dynamics: Mutex::new(DynamicsMode::Off),
+            dynamics_version: AtomicU32::new(0),
+            clipper: Mutex::new(None),
+            clipper_version: AtomicU32::new(0),
+            equalizer: EqControl::default(),
+            ramp_shape: AtomicU8::new(RampSettings::default().shape as u8),
+            ramp_duration_ms: AtomicF32::new(RampSettings::default().duration_ms),
+            chain: ChainControl::default(),
+            latency_frames: AtomicU32::new(0),
+            latency_sample_rate: AtomicU32::new(0),
+            levels: Mutex::new(None),
+        }))
+    }
+
+    pub fn get(&self) -> f32 {
+        self.0.boost_factor.get()
+    }
+
+    pub fn set(&self, boost_factor: f32) {
+        self.0.boost_factor.set(boost_factor);
+    }
+
+    pub fn true_peak_ceiling_dbtp(&self) -> f32 {
+        self.0.true_peak_ceiling_dbtp.get()
+    }
+
+    pub fn set_true_peak_ceiling_dbtp(&self, ceiling_dbtp: f32) {
+        self.0.true_peak_ceiling_dbtp.set(ceiling_dbtp);
+    }
+
+    pub fn auto_gain(&self) -> Option<AutoGainSettings> {
+        self.0.target_lufs.get_option().map(|target_lufs| AutoGainSettings {
+            target_lufs,
+            max_gain_db: self.0.max_gain_db.get(),
+            max_slew_db_per_second: self.0.max_slew_db_per_second.get(),
+        })
+    }
+
+    pub fn set_auto_gain(&self, settings: Option<AutoGainSettings>) {
+        if let Some(settings) = settings {
+            self.0.max_gain_db.set(settings.max_gain_db);
+            self.0.max_slew_db_per_second.set(settings.max_slew_db_per_second);
+        }
+        // Written last: it switches auto-gain on and off
+        self.0.target_lufs.set_option(settings.map(|settings| settings.target_lufs));
+    }
+
+    pub fn dynamics(&self) -> DynamicsMode {
+        *self.0.dynamics.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_dynamics(&self, mode: DynamicsMode) {
+        *self.0.dynamics.lock().unwrap_or_else(|e| e.into_inner()) = mode;
+        self.0.dynamics_version.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn clipper(&self) -> Option<ClipperSettings> {
+        *self.0.clipper.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_clipper(&self, settings: Option<ClipperSettings>) {
+        *self.0.clipper.lock().unwrap_or_else(|e| e.into_inner()) = settings;
+        self.0.clipper_version.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn equalizer(&self) -> EqPreset {
+        self.0.equalizer.preset()
+    }
+
+    pub fn set_equalizer(&self, preset: EqPreset) {
+        self.0.equalizer.set_preset(preset);
+    }
+
+    pub fn gain_ramp(&self) -> RampSettings {
+        RampSettings {
+            shape: RampShape::from_u8(self.0.ramp_shape.load(Ordering::Relaxed)),
+            duration_ms: self.0.ramp_duration_ms.get(),
+        }
+    }
+
+    pub fn set_gain_ramp(&self, settings: RampSettings) {
+        self.0.ramp_shape.store(settings.shape as u8, Ordering::Relaxed);
+        self.0.ramp_duration_ms.set(settings.duration_ms);
+    }
+
+    pub fn chain(&self) -> ChainDescription {
+        self.0.chain.description()
+    }
+
+    // Builds the chain on the calling thread; running processors swap it in with their
+    // next block
+    pub fn set_chain(&self, description: ChainDescription) {
+        self.0.chain.set_description(description);
+    }
+
+    // Latency of the last block processed, chain included; None before the first one
+    pub fn latency(&self) -> Option<LatencyReport> {
+        let sample_rate = self.0.latency_sample_rate.load(Ordering::Relaxed);
+        (sample_rate > 0).then(|| LatencyReport::new(self.0.latency_frames.load(Ordering::Relaxed) as usize, sample_rate))
+    }
+
+    // The dynamics mode, if it changed since version `seen`; never blocks
+    fn dynamics_update(&self, seen: &mut u32) -> Option<DynamicsMode> {
+        let version = self.0.dynamics_version.load(Ordering::Acquire);
+        if version == *seen {
+            return None;
+        }
+        let mode = *self.0.dynamics.try_lock().ok()?;
+        *seen = version;
+        Some(mode)
+    }
+
+    // The clipper settings, if they changed since version `seen`; never blocks
+    fn clipper_update(&self, seen: &mut u32) -> Option<Option<ClipperSettings>> {
+        let version = self.0.clipper_version.load(Ordering::Acquire);
+        if version == *seen {
+            return None;
+        }
+        let settings = *self.0.clipper.try_lock().ok()?;
+        *seen = version;
+        Some(settings)
+    }
+
+    // Gain auto-gain currently applies instead of the boost factor
+    pub fn auto_gain_db(&self) -> f32 {
+        self.0.auto_gain_db.get()
+    }
+
+    // Loudness of the session before the boost, as last measured by its pipeline
+    pub fn loudness(&self) -> LoudnessReading {
+        LoudnessReading {
+            momentary_lufs: self.0.momentary_lufs.get_option(),
+            short_term_lufs: self.0.short_term_lufs.get_option(),
+            integrated_lufs: self.0.integrated_lufs.get_option(),
+        }
+    }
+
+    // Peak, RMS and peak-hold of the session after the boost, as last metered by its pipeline
+    pub fn levels(&self) -> Option<MeterReading> {
+        *self.0.levels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn publish_auto_gain_db(&self, gain_db: f32) {
+        self.0.auto_gain_db.set(gain_db);
+    }
+
+    fn publish_latency(&self, frames: usize, sample_rate: u32) {
+        self.0.latency_frames.store(frames as u32, Ordering::Relaxed);
+        self.0.latency_sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    fn publish_loudness(&self, reading: LoudnessReading) {
+        self.0.momentary_lufs.set_option(reading.momentary_lufs);
+        self.0.short_term_lufs.set_option(reading.short_term_lufs);
+        self.0.integrated_lufs.set_option(reading.integrated_lufs);
+    }
+
+    // Never blocks; the reading is dropped while someone reads the levels
+    fn publish_levels(&self, reading: MeterReading) {
+        if let Ok(mut levels) = self.0.levels.try_lock() {
+            *levels = Some(reading);
+        }
+    }
 }
 
 // Global boost manager
 pub struct AudioBoostManager {
-    boost_settings: Arc<Mutex<HashMap<u32, BoostSettings>>>,
+    backend: Arc<dyn AudioBackend>,
+    // Keyed by session ID, which stays the same when the application restarts
+    boost_settings: Arc<Mutex<HashMap<String, BoostSettings>>>,
+    controls: Mutex<HashMap<String, BoostControl>>,
+    // EQ presets of applications and output devices
+    eq_presets: Mutex<EqPresetStore>,
+    // Controls of the device presets, keyed by device ID
+    device_equalizers: Mutex<HashMap<String, EqControl>>,
+    pipelines: Mutex<HashMap<String, PipelineRunner>>,
 }
 
 impl AudioBoostManager {
-    pub fn new() -> Self {
+    pub fn new(backend: Arc<dyn AudioBackend>) -> Self {
         Self {
+            backend,
             boost_settings: Arc::new(Mutex::new(HashMap::new())),
+            controls: Mutex::new(HashMap::new()),
+            eq_presets: Mutex::new(EqPresetStore::default()),
+            device_equalizers: Mutex::new(HashMap::new()),
+            pipelines: Mutex::new(HashMap::new()),
         }
     }
 
-    pub fn set_boost(&self, process_id: u32, boost_factor: f32) -> Result<(), String> {
-        let mut settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
-        
-        settings.insert(process_id, BoostSettings {
-            boost_factor,
-            enabled: boost_factor > 1.0,
-        });
+    // Takes the EQ presets from `store`, e.g. the ones saved by an earlier run
+    pub fn with_eq_presets(mut self, store: EqPresetStore) -> Self {
+        self.eq_presets = Mutex::new(store);
+        self
+    }
+
+    pub fn set_boost(&self, session_id: &str, boost_factor: f32) -> Result<(), String> {
+        if !(0.0..=MAX_BOOST_FACTOR).contains(&boost_factor) {
+            return Err(format!("Boost factor {} is outside 0..={}", boost_factor, MAX_BOOST_FACTOR));
+        }
+        // Only sessions the backend knows can be boosted
+        self.backend.get_session_volume(session_id)?;
+
+        {
+            let mut settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+            let settings = settings.entry(session_id.to_string()).or_default();
+            settings.boost_factor = boost_factor;
+            settings.enabled = boost_factor > 1.0;
+        }
+
+        // Running pipelines pick the new factor up with their next block
+        self.boost_control(session_id)?.set(boost_factor);
 
-        // For now, we'll implement a simple volume multiplication approach
-        // In a full implementation, this would involve audio stream interception
-        self.apply_boost_via_volume(process_id, boost_factor)
+        // The boost applies on top of the session at full volume. Without a pipeline that
+        // is as loud as the session gets, so the slider never ends up below where it was.
+        if boost_factor > 1.0 {
+            self.backend.set_session_volume(session_id, 1.0)?;
+        }
+        Ok(())
     }
 
-    pub fn get_boost(&self, process_id: u32) -> Result<f32, String> {
+    pub fn get_boost(&self, session_id: &str) -> Result<f32, String> {
         let settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
         
-        Ok(settings.get(&process_id)
+        Ok(settings.get(session_id)
             .map(|s| s.boost_factor)
             .unwrap_or(1.0))
     }
 
-    pub fn remove_boost(&self, process_id: u32) -> Result<(), String> {
-        let mut settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
-        settings.remove(&process_id);
-        
-        // Reset to normal volume
-        self.apply_boost_via_volume(process_id, 1.0)
+    // Caps the session's boosted audio at `ceiling_dbtp` true peak
+    pub fn set_true_peak_ceiling(&self, session_id: &str, ceiling_dbtp: f32) -> Result<(), String> {
+        if !(MIN_TRUE_PEAK_CEILING_DBTP..=MAX_TRUE_PEAK_CEILING_DBTP).contains(&ceiling_dbtp) {
+            return Err(format!(
+                "True-peak ceiling {} dBTP is outside {}..={} dBTP",
+                ceiling_dbtp, MIN_TRUE_PEAK_CEILING_DBTP, MAX_TRUE_PEAK_CEILING_DBTP
+            ));
+        }
+        self.backend.get_session_volume(session_id)?;
+
+        self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?
+            .entry(session_id.to_string())
+            .or_default()
+            .true_peak_ceiling_dbtp = ceiling_dbtp;
+
+        self.boost_control(session_id)?.set_true_peak_ceiling_dbtp(ceiling_dbtp);
+        Ok(())
     }
 
-    // Simplified boost implementation using volume multiplication
-    // Note: This is a basic approach. A full implementation would require
-    // audio stream interception and DSP processing
-    fn apply_boost_via_volume(&self, process_id: u32, boost_factor: f32) -> Result<(), String> {
-        unsafe {
-            CoInitializeEx(None, COINIT_APARTMENTTHREADED).map_err(|e| format!("Failed to initialize COM: {:?}", e))?;
+    pub fn get_true_peak_ceiling(&self, session_id: &str) -> Result<f32, String> {
+        let settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
 
-            let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
-                .map_err(|e| format!("Failed to create device enumerator: {:?}", e))?;
+        Ok(settings.get(session_id)
+            .map(|s| s.true_peak_ceiling_dbtp)
+            .unwrap_or(DEFAULT_TRUE_PEAK_CEILING_DBTP))
+    }
 
-            let device = enumerator.GetDefaultAudioEndpoint(eRender, DEVICE_STATE_ACTIVE)
-                .map_err(|e| format!("Failed to get default audio endpoint: {:?}", e))?;
+    // Steers the session's gain towards `settings.target_lufs`; None goes back to the
+    // manual boost factor
+    pub fn set_auto_gain(&self, session_id: &str, settings: Option<AutoGainSettings>) -> Result<(), String> {
+        if let Some(settings) = &settings {
+            settings.validate()?;
+        }
+        self.backend.get_session_volume(session_id)?;
+
+        self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?
+            .entry(session_id.to_string())
+            .or_default()
+            .auto_gain = settings;
 
-            let session_manager2: IAudioSessionManager2 = device.Activate(CLSCTX_ALL, None)
-                .map_err(|e| format!("Failed to activate audio session manager: {:?}", e))?;
+        self.boost_control(session_id)?.set_auto_gain(settings);
+        Ok(())
+    }
 
-            let session_enumerator = session_manager2.GetSessionEnumerator()
-                .map_err(|e| format!("Failed to get session enumerator: {:?}", e))?;
+    pub fn get_auto_gain(&self, session_id: &str) -> Result<Option<AutoGainSettings>, String> {
+        let settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(settings.get(session_id).and_then(|s| s.auto_gain))
+    }
+
+    // Compressor or multiband compressor the session's boosted audio goes through
+    pub fn set_dynamics(&self, session_id: &str, mode: DynamicsMode) -> Result<(), String> {
+        mode.validate()?;
+        self.backend.get_session_volume(session_id)?;
+
+        self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?
+            .entry(session_id.to_string())
+            .or_default()
+            .dynamics = mode;
+
+        self.boost_control(session_id)?.set_dynamics(mode);
+        Ok(())
+    }
 
-            let count = session_enumerator.GetCount()
-                .map_err(|e| format!("Failed to get session count: {:?}", e))?;
+    pub fn get_dynamics(&self, session_id: &str) -> Result<DynamicsMode, String> {
+        let settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(settings.get(session_id).map(|s| s.dynamics).unwrap_or_default())
+    }
 
-            for i in 0..count {
-                let session_control = session_enumerator.GetSession(i)
-                    .map_err(|e| format!("Failed to get session control: {:?}", e))?;
+    // Clipper the session's audio goes through right before the limiter; None turns it off
+    pub fn set_clipper(&self, session_id: &str, settings: Option<ClipperSettings>) -> Result<(), String> {
+        if let Some(settings) = &settings {
+            settings.validate()?;
+        }
+        self.backend.get_session_volume(session_id)?;
 
-                let session_control2: IAudioSessionControl2 = session_control.cast()
-                    .map_err(|e| format!("Failed to cast to IAudioSessionControl2: {:?}", e))?;
+        self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?
+            .entry(session_id.to_string())
+            .or_default()
+            .clipper = settings;
 
-                let current_process_id = session_control2.GetProcessId()
-                    .map_err(|e| format!("Failed to get process ID: {:?}", e))?;
+        self.boost_control(session_id)?.set_clipper(settings);
+        Ok(())
+    }
 
-                if current_process_id == process_id {
-                    let simple_audio_volume: ISimpleAudioVolume = session_control.cast()
-                        .map_err(|e| format!("Failed to cast to ISimpleAudioVolume: {:?}", e))?;
+    pub fn get_clipper(&self, session_id: &str) -> Result<Option<ClipperSettings>, String> {
+        let settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(settings.get(session_id).and_then(|s| s.clipper))
+    }
 
-                    // Get current volume
-                    let mut current_volume = 0.0;
-                    simple_audio_volume.GetMasterVolume(&mut current_volume)
-                        .map_err(|e| format!("Failed to get master volume: {:?}", e))?;
+    // Parametric EQ the session's audio goes through before the boost. The preset belongs
+    // to the session's application, so every session of it uses the preset, in this run and
+    // the next ones.
+    pub fn set_equalizer(&self, session_id: &str, preset: EqPreset) -> Result<(), String> {
+        preset.validate()?;
+        let sessions = self.backend.enumerate_sessions()?;
+        let key = sessions.iter()
+            .find(|session| session.id == session_id)
+            .map(app_key)
+            .ok_or_else(|| session_not_found(session_id))?;
 
-                    // Apply boost (clamped to prevent damage)
-                    let boosted_volume = (current_volume * boost_factor).min(1.0);
-                    
-                    simple_audio_volume.SetMasterVolume(boosted_volume, None)
-                        .map_err(|e| format!("Failed to set master volume: {:?}", e))?;
-                    
-                    return Ok(());
-                }
+        // Running pipelines get the preset even if it could not be saved
+        let saved = self.eq_presets.lock().map_err(|e| format!("Lock error: {:?}", e))?.set_app(&key, preset.clone());
+        let controls = self.controls.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        for session in sessions.iter().filter(|session| app_key(session) == key) {
+            if let Some(control) = controls.get(&session.id) {
+                control.set_equalizer(preset.clone());
             }
-            Err(format!("Session with process ID {} not found", process_id))
         }
+        saved
+    }
+
+    pub fn get_equalizer(&self, session_id: &str) -> Result<EqPreset, String> {
+        let sessions = self.backend.enumerate_sessions()?;
+        let Some(session) = sessions.iter().find(|session| session.id == session_id) else {
+            return Ok(EqPreset::default());
+        };
+        let presets = self.eq_presets.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(presets.app(&app_key(session)))
+    }
+
+    // Parametric EQ of everything a pipeline renders to the device, e.g. to correct
+    // headphones; applied after the session's own processing
+    pub fn set_device_equalizer(&self, device_id: &str, preset: EqPreset) -> Result<(), String> {
+        preset.validate()?;
+        self.backend.get_device_volume(device_id)?;
+        let saved = self.eq_presets.lock().map_err(|e| format!("Lock error: {:?}", e))?.set_device(device_id, preset.clone());
+        self.device_equalizer_control(device_id)?.set_preset(preset);
+        saved
+    }
+
+    pub fn get_device_equalizer(&self, device_id: &str) -> Result<EqPreset, String> {
+        let presets = self.eq_presets.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(presets.device(device_id))
+    }
+
+    // Handle that follows every later EQ change of the device
+    pub fn device_equalizer_control(&self, device_id: &str) -> Result<EqControl, String> {
+        let preset = self.get_device_equalizer(device_id)?;
+        let mut equalizers = self.device_equalizers.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(equalizers.entry(device_id.to_string()).or_insert_with(|| EqControl::new(preset)).clone())
+    }
+
+    // How the session's boost and auto-gain changes are ramped in
+    pub fn set_gain_ramp(&self, session_id: &str, ramp: RampSettings) -> Result<(), String> {
+        ramp.validate()?;
+        self.backend.get_session_volume(session_id)?;
+
+        self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?
+            .entry(session_id.to_string())
+            .or_default()
+            .gain_ramp = ramp;
+
+        self.boost_control(session_id)?.set_gain_ramp(ramp);
+        Ok(())
+    }
+
+    pub fn get_gain_ramp(&self, session_id: &str) -> Result<RampSettings, String> {
+        let settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(settings.get(session_id).map(|s| s.gain_ramp).unwrap_or_default())
+    }
+
+    // Effects the session's audio goes through after its dynamics, in order; a running
+    // pipeline swaps the new chain in without stopping
+    pub fn set_chain(&self, session_id: &str, description: ChainDescription) -> Result<(), String> {
+        description.validate()?;
+        self.backend.get_session_volume(session_id)?;
+
+        self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?
+            .entry(session_id.to_string())
+            .or_default()
+            .chain = description.clone();
+
+        self.boost_control(session_id)?.set_chain(description);
+        Ok(())
+    }
+
+    pub fn get_chain(&self, session_id: &str) -> Result<ChainDescription, String> {
+        let settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(settings.get(session_id).map(|s| s.chain.clone()).unwrap_or_default())
+    }
+
+    // Takes effect when the session's pipeline starts next
+    pub fn set_resampler_quality(&self, session_id: &str, quality: ResamplerQuality) -> Result<(), String> {
+        self.backend.get_session_volume(session_id)?;
+
+        self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?
+            .entry(session_id.to_string())
+            .or_default()
+            .resampler_quality = quality;
+        Ok(())
+    }
+
+    pub fn get_resampler_quality(&self, session_id: &str) -> Result<ResamplerQuality, String> {
+        let settings = self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Ok(settings.get(session_id).map(|s| s.resampler_quality).unwrap_or_default())
+    }
+
+    // Latency of the session's processing; None while no pipeline runs
+    pub fn get_latency(&self, session_id: &str) -> Result<Option<LatencyReport>, String> {
+        Ok(self.boost_control(session_id)?.latency())
+    }
+
+    // Loudness measured by the session's pipeline; empty while no pipeline runs
+    pub fn get_loudness(&self, session_id: &str) -> Result<LoudnessReading, String> {
+        Ok(self.boost_control(session_id)?.loudness())
+    }
+
+    pub fn remove_boost(&self, session_id: &str) -> Result<(), String> {
+        self.boost_settings.lock().map_err(|e| format!("Lock error: {:?}", e))?.remove(session_id);
+
+        // Back to unity gain, the default ceiling and ramp, no auto-gain, no compression,
+        // no clipper and no effects. The EQ preset stays with the application.
+        let control = self.boost_control(session_id)?;
+        control.set(1.0);
+        control.set_true_peak_ceiling_dbtp(DEFAULT_TRUE_PEAK_CEILING_DBTP);
+        control.set_auto_gain(None);
+        control.set_dynamics(DynamicsMode::Off);
+        control.set_clipper(None);
+        control.set_gain_ramp(RampSettings::default());
+        control.set_chain(ChainDescription::default());
+        Ok(())
+    }
+
+    // Handle that follows every later boost change of the session
+    pub fn boost_control(&self, session_id: &str) -> Result<BoostControl, String> {
+        let boost_factor = self.get_boost(session_id)?;
+        let ceiling_dbtp = self.get_true_peak_ceiling(session_id)?;
+        let auto_gain = self.get_auto_gain(session_id)?;
+        let dynamics = self.get_dynamics(session_id)?;
+        let clipper = self.get_clipper(session_id)?;
+        let equalizer = self.get_equalizer(session_id)?;
+        let gain_ramp = self.get_gain_ramp(session_id)?;
+        let chain = self.get_chain(session_id)?;
+        let mut controls = self.controls.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+
+        Ok(controls.entry(session_id.to_string())
+            .or_insert_with(|| {
+                let control = BoostControl::new(boost_factor);
+                control.set_true_peak_ceiling_dbtp(ceiling_dbtp);
+                control.set_auto_gain(auto_gain);
+                control.set_dynamics(dynamics);
+                control.set_clipper(clipper);
+                control.set_equalizer(equalizer);
+                control.set_gain_ramp(gain_ramp);
+                control.set_chain(chain);
+                control
+            })
+            .clone())
+    }
+
+    // Boosts the session's audio by routing it from `capture_device_id` (e.g. a virtual
+    // cable the application plays into) through the boost and the EQ of
+    // `render_device_id` to that device, converted to that device's format where the two
+    // differ. Replaces a pipeline already running for the session.
+    pub fn start_pipeline(&self, session_id: &str, capture_device_id: &str, render_device_id: &str) -> Result<(), String> {
+        // Capturing a render device records its loopback, which would feed the boosted
+        // audio back into itself
+        if capture_device_id == render_device_id {
+            return Err(format!("Cannot boost device {} into itself", render_device_id));
+        }
+        self.backend.get_session_volume(session_id)?;
+        let boost = self.boost_control(session_id)?;
+        let device_equalizer = self.device_equalizer_control(render_device_id)?;
+        let resampler_quality = self.get_resampler_quality(session_id)?;
+
+        let mut pipelines = self.pipelines.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        // The previous pipeline may hold the same devices, so it stops first
+        if let Some(mut previous) = pipelines.remove(session_id) {
+            let _ = previous.stop();
+        }
+
+        let backend = Arc::clone(&self.backend);
+        let capture_device_id = capture_device_id.to_string();
+        let render_device_id = render_device_id.to_string();
+        let render_backend = Arc::clone(&backend);
+        let runner = PipelineRunner::start(move || render_backend.open_render(&render_device_id), move |render| {
+            let capture = backend.open_capture(&capture_device_id)?;
+            BoostPipeline::new(capture, render, boost)?
+                .with_resampler_quality(resampler_quality)
+                .map(|pipeline| pipeline.with_device_equalizer(device_equalizer))
+        })?;
+
+        pipelines.insert(session_id.to_string(), runner);
+        Ok(())
+    }
+
+    // Stops the session's pipeline, returning the error it failed with, if any
+    pub fn stop_pipeline(&self, session_id: &str) -> Result<(), String> {
+        let runner = self.pipelines.lock().map_err(|e| format!("Lock error: {:?}", e))?.remove(session_id);
+        match runner {
+            Some(mut runner) => runner.stop(),
+            None => Ok(()),
+        }
+    }
+
+    // Levels of the sessions whose pipeline is running, metered on the processed audio
+    pub fn pipeline_levels(&self) -> HashMap<String, MeterReading> {
+        let (Ok(pipelines), Ok(controls)) = (self.pipelines.lock(), self.controls.lock()) else {
+            return HashMap::new();
+        };
+        pipelines.iter()
+            .filter(|(_, runner)| runner.is_running())
+            .filter_map(|(session_id, _)| Some((session_id.clone(), controls.get(session_id)?.levels()?)))
+            .collect()
+    }
+
+    pub fn pipeline_running(&self, session_id: &str) -> bool {
+        self.pipelines.lock()
+            .map(|pipelines| pipelines.get(session_id).is_some_and(PipelineRunner::is_running))
+            .unwrap_or(false)
+    }
+
+    // Underruns and overruns between the capture and render side of the session's pipeline
+    pub fn pipeline_stats(&self, session_id: &str) -> Result<StreamStats, String> {
+        self.pipelines.lock().map_err(|e| format!("Lock error: {:?}", e))?
+            .get(session_id)
+            .map(PipelineRunner::stats)
+            .ok_or_else(|| format!("No boost pipeline for session {}", session_id))
     }
 }
 
-// Audio DSP functions for future implementation
+// Sample-domain boost with limiting, run by BoostPipeline
 pub struct AudioProcessor {
-    limiter_threshold: f32,
-    limiter_ratio: f32,
+    format: AudioFormat,
+    // The application's EQ before the boost, the output device's after the dynamics
+    equalizer: Equalizer,
+    device_equalizer: Equalizer,
+    device_eq: Option<EqControl>,
+    gain: GainRamp,
+    dynamics: Dynamics,
+    // Version of the control's dynamics mode `dynamics` runs
+    dynamics_version: u32,
+    // The session's effects, after the dynamics
+    chain: ProcessorChain,
+    // Only set for processors running a control's chain
+    chain_attachment: Option<ChainAttachment>,
+    clipper: Clipper,
+    clipper_version: u32,
+    limiter: LookaheadLimiter,
+    loudness: LoudnessMeter,
+    auto_gain: AutoGain,
+    level_meter: LevelMeter,
+}
+
+impl Default for AudioProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AudioProcessor {
+    // Treats the samples as one channel; use `with_format` to limit frames as a whole
     pub fn new() -> Self {
+        Self::with_format(AudioFormat::new(48000, 1))
+    }
+
+    pub fn with_format(format: AudioFormat) -> Self {
+        Self::with_limiter(format, LimiterSettings::default())
+    }
+
+    pub fn with_limiter(format: AudioFormat, settings: LimiterSettings) -> Self {
         Self {
-            limiter_threshold: 0.95, // Prevent clipping at 95%
-            limiter_ratio: 10.0,     // 10:1 compression ratio
-        }
-    }
-
-    // Apply boost with limiting to prevent clipping
-    pub fn process_samples(&self, samples: &mut [f32], boost_factor: f32) {
-        for sample in samples.iter_mut() {
-            // Apply boost
-            *sample *= boost_factor;
-            
-            // Apply limiter to prevent clipping
-            if sample.abs() > self.limiter_threshold {
-                let sign = if *sample > 0.0 { 1.0 } else { -1.0 };
-                let excess = sample.abs() - self.limiter_threshold;
-                let compressed_excess = excess / self.limiter_ratio;
-                *sample = sign * (self.limiter_threshold + compressed_excess);
-            }
+            format,
+            equalizer: Equalizer::new(format),
+            device_equalizer: Equalizer::new(format),
+            device_eq: None,
+            gain: GainRamp::new(format, RampSettings::default()),
+            dynamics: Dynamics::new(format),
+            dynamics_version: 0,
+            chain: ProcessorChain::empty(format),
+            chain_attachment: None,
+            clipper: Clipper::new(format),
+            clipper_version: 0,
+            limiter: LookaheadLimiter::new(format, settings),
+            loudness: LoudnessMeter::new(format),
+            auto_gain: AutoGain::new(),
+            level_meter: LevelMeter::new(MeterSettings::default()),
         }
     }
 
-    // Soft clipping function for additional protection
-    pub fn soft_clip(&self, sample: f32) -> f32 {
-        if sample.abs() <= 1.0 {
-            sample
-        } else {
-            let sign = if sample > 0.0 { 1.0 } else { -1.0 };
-            sign * (1.0 - (-sample.abs()).exp())
-        }
+    // Limits the true peak to the control's ceiling, see `process_with_control`,
+    // and runs the control's chain
+    pub fn for_control(format: AudioFormat, boost: &BoostControl) -> Self {
+        let mut processor = Self::with_limiter(format, LimiterSettings::true_peak(boost.true_peak_ceiling_dbtp()));
+        let (chain, attachment) = boost.0.chain.attach(format);
+        processor.chain = chain;
+        processor.chain_attachment = Some(attachment);
+        processor
     }
-}
 
-// Future: Virtual Audio Device implementation
-// This would require a more complex implementation involving:
-// 1. Windows Audio Session API (WASAPI) loopback capture
-// 2. Audio Processing Objects (APO) or custom audio driver
-// 3. Real-time audio stream processing
-// 4. Integration with Windows audio routing
-
-pub struct VirtualAudioDevice {
-    // Placeholder for future implementation
-    device_name: String,
-    sample_rate: u32,
-    channels: u16,
-}
+    // Apply EQ and boost, then compression, the effect chain, the device EQ and the clipper,
+    // with limiting to prevent clipping. A new boost factor is ramped in (see
+    // `set_gain_ramp`). The output lags the input by `latency_frames`, the chain's, the
+    // clipper's and the limiter's look-ahead.
+    pub fn process_samples(&mut self, samples: &mut [f32], boost_factor: f32) {
+        self.equalizer.process(samples);
+        self.gain.set_target(boost_factor);
+        self.gain.process(samples);
+        self.dynamics.process(samples);
+        self.chain.process(samples);
+        self.device_equalizer.process(samples);
+        self.clipper.process(samples);
+        self.limiter.process(samples);
+    }
 
-impl VirtualAudioDevice {
-    pub fn new(name: &str) -> Self {
-        Self {
-            device_name: name.to_string(),
-            sample_rate: 44100,
-            channels: 2,
+    pub fn clipper(&self) -> Option<ClipperSettings> {
+        self.clipper.settings()
+    }
+
+    pub fn set_clipper(&mut self, settings: Option<ClipperSettings>) {
+        self.clipper.set_settings(settings);
+    }
+
+    pub fn chain(&self) -> &ProcessorChain {
+        &self.chain
+    }
+
+    // Replaces the effect chain; build it for this processor's format
+    pub fn set_chain(&mut self, chain: ProcessorChain) {
+        self.chain = chain;
+    }
+
+    pub fn gain_ramp(&self) -> &GainRamp {
+        &self.gain
+    }
+
+    pub fn set_gain_ramp(&mut self, settings: RampSettings) {
+        self.gain.set_settings(settings);
+    }
+
+    // Follows the output device's EQ in `process_with_control`
+    pub fn set_device_equalizer(&mut self, control: EqControl) {
+        self.device_eq = Some(control);
+    }
+
+    pub fn dynamics(&self) -> DynamicsMode {
+        self.dynamics.mode()
+    }
+
+    pub fn set_dynamics(&mut self, mode: DynamicsMode) {
+        self.dynamics.set_mode(mode);
+    }
+
+    // Applies the control's current EQ, boost (or auto-gain), ramp, dynamics, chain, clipper
+    // and ceiling, and publishes the loudness of the samples as they came in, their levels
+    // as they go out and the latency
+    pub fn process_with_control(&mut self, samples: &mut [f32], boost: &BoostControl) {
+        let ceiling = db_to_linear(boost.true_peak_ceiling_dbtp());
+        if ceiling != self.limiter.settings().ceiling {
+            self.limiter.set_ceiling(ceiling);
+        }
+        if let Some(mode) = boost.dynamics_update(&mut self.dynamics_version) {
+            self.dynamics.set_mode(mode);
+        }
+        if let Some(settings) = boost.clipper_update(&mut self.clipper_version) {
+            self.clipper.set_settings(settings);
         }
+        self.gain.set_settings(boost.gain_ramp());
+        self.equalizer.sync(&boost.0.equalizer);
+        if let Some(control) = &self.device_eq {
+            self.device_equalizer.sync(control);
+        }
+        if let Some(attachment) = &mut self.chain_attachment {
+            boost.0.chain.sync(&mut self.chain, attachment);
+        }
+        boost.publish_latency(self.latency_frames(), self.format.sample_rate);
+
+        self.loudness.process(samples);
+        boost.publish_loudness(self.loudness.reading());
+
+        let frames = samples.len() / self.format.channels.max(1) as usize;
+        let elapsed = Duration::from_secs_f64(frames as f64 / self.format.sample_rate.max(1) as f64);
+        let boost_factor = match boost.auto_gain() {
+            Some(settings) => {
+                // Short-term loudness once 3 s are in, momentary until then
+                let loudness_lufs = self.loudness.short_term_lufs().or(self.loudness.momentary_lufs());
+                db_to_linear(self.auto_gain.update(&settings, loudness_lufs, elapsed))
+            }
+            None => {
+                self.auto_gain.reset();
+                boost.get()
+            }
+        };
+        boost.publish_auto_gain_db(self.auto_gain.gain_db());
+
+        self.process_samples(samples, boost_factor);
+
+        self.level_meter.process_samples(samples, elapsed);
+        boost.publish_levels(self.level_meter.reading());
     }
 
-    // Future: Initialize virtual audio device
-    pub fn initialize(&self) -> Result<(), String> {
-        // This would involve:
-        // 1. Registering a virtual audio endpoint
-        // 2. Setting up audio stream capture/render
-        // 3. Implementing real-time audio processing pipeline
-        Err("Virtual audio device not yet implemented".to_string())
+    pub fn loudness(&self) -> &LoudnessMeter {
+        &self.loudness
     }
 
-    // Future: Process audio stream with boost
-    pub fn process_audio_stream(&self, _input: &[f32], _output: &mut [f32], _boost_factor: f32) -> Result<(), String> {
-        // This would implement real-time audio processing
-        Err("Audio stream processing not yet implemented".to_string())
+    pub fn latency_frames(&self) -> usize {
+        self.chain.latency_frames() + self.clipper.latency_frames() + self.limiter.latency_frames()
     }
-}
 
+    pub fn limiter(&self) -> &LookaheadLimiter {
+        &self.limiter
+    }
+
+    pub fn limiter_mut(&mut self) -> &mut LookaheadLimiter {
+        &mut self.limiter
+    }
+
+    // Soft clipping function for additional protection; see `set_clipper` for the stage
+    // `process_samples` runs
+    pub fn soft_clip(&self, sample: f32) -> f32 {
+        ClipCurve::Tanh.apply(sample)
+    }
+}
@@ -0,0 +1,311 @@
+#[cfg(test)]
+use std::fs::File;
+#[cfg(test)]
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+#[cfg(test)]
+use std::path::Path;
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+use crate::sample_format::{decode, parse_wave_format, Dither, Quantizer, SampleFormat};
+
+// Sample rate and channel count of an interleaved f32 stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioFormat {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self { sample_rate, channels }
+    }
+}
+
+impl Default for AudioFormat {
+    fn default() -> Self {
+        Self::new(48000, 2)
+    }
+}
+
+// Where audio enters the pipeline: a loopback or capture device, a file, a buffer.
+// All samples are interleaved f32, whole frames only.
+pub trait CaptureEndpoint {
+    fn format(&self) -> AudioFormat;
+
+    // Fills the start of `buffer` and returns how many samples were written.
+    // Live endpoints return 0 when nothing is available yet.
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize, String>;
+
+    // True once a finite source (file, buffer) has nothing left to read
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+// Where the pipeline delivers processed audio
+pub trait RenderEndpoint {
+    fn format(&self) -> AudioFormat;
+
+    // Blocks until every sample has been accepted
+    fn write(&mut self, samples: &[f32]) -> Result<(), String>;
+
+    // Frames written and not played yet, for endpoints that play at their device's pace;
+    // None for those that take samples as fast as they come
+    fn buffered_frames(&self) -> Result<Option<usize>, String> {
+        Ok(None)
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// In-memory endpoints, behind the devices of the simulated backend
+#[cfg(test)]
+pub struct MemoryCapture {
+    format: AudioFormat,
+    samples: Vec<f32>,
+    position: usize,
+}
+
+#[cfg(test)]
+impl MemoryCapture {
+    pub fn new(format: AudioFormat, samples: Vec<f32>) -> Self {
+        Self { format, samples, position: 0 }
+    }
+}
+
+#[cfg(test)]
+impl CaptureEndpoint for MemoryCapture {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize, String> {
+        let count = whole_frames(buffer.len().min(self.samples.len() - self.position), self.format);
+        buffer[..count].copy_from_slice(&self.samples[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+
+    fn finished(&self) -> bool {
+        self.position >= self.samples.len()
+    }
+}
+
+// Collects everything written to it; `buffer()` stays readable after the endpoint moved
+// into a pipeline thread
+#[cfg(test)]
+pub struct MemoryRender {
+    format: AudioFormat,
+    samples: Arc<Mutex<Vec<f32>>>,
+}
+
+#[cfg(test)]
+impl MemoryRender {
+    pub fn new(format: AudioFormat) -> Self {
+        Self::with_buffer(format, Arc::new(Mutex::new(Vec::new())))
+    }
+
+    pub fn with_buffer(format: AudioFormat, samples: Arc<Mutex<Vec<f32>>>) -> Self {
+        Self { format, samples }
+    }
+
+    pub fn buffer(&self) -> Arc<Mutex<Vec<f32>>> {
+        Arc::clone(&self.samples)
+    }
+}
+
+#[cfg(test)]
+impl RenderEndpoint for MemoryRender {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        self.samples.lock().map_err(|e| format!("Lock error: {:?}", e))?.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn whole_frames(samples: usize, format: AudioFormat) -> usize {
+    let channels = format.channels.max(1) as usize;
+    samples - samples % channels
+}
+
+// Size of the WAV header written by WavFileRender, up to the first sample
+#[cfg(test)]
+const WAV_HEADER_SIZE: u32 = 44;
+
+// Streams 16-, 24- or 32-bit PCM or 32-bit float WAV files, for tests to feed pipelines
+#[cfg(test)]
+pub struct WavFileCapture {
+    reader: BufReader<File>,
+    format: AudioFormat,
+    sample_format: SampleFormat,
+    remaining_bytes: u64,
+    bytes: Vec<u8>,
+}
+
+#[cfg(test)]
+impl WavFileCapture {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {:?}", path.display(), e))?;
+        let mut reader = BufReader::new(file);
+
+        let mut riff = [0u8; 12];
+        reader.read_exact(&mut riff).map_err(|e| format!("Failed to read WAV header: {:?}", e))?;
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err(format!("{} is not a WAV file", path.display()));
+        }
+
+        let mut format = None;
+        loop {
+            let mut chunk = [0u8; 8];
+            reader.read_exact(&mut chunk).map_err(|e| format!("Failed to find WAV data chunk: {:?}", e))?;
+            let chunk_size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+
+            match &chunk[0..4] {
+                b"fmt " => {
+                    let mut fmt = vec![0u8; chunk_size as usize];
+                    reader.read_exact(&mut fmt).map_err(|e| format!("Failed to read WAV format: {:?}", e))?;
+                    format = Some(parse_wave_format(&fmt)?);
+                }
+                b"data" => {
+                    let (format, sample_format) = format.ok_or_else(|| "WAV data chunk comes before its format".to_string())?;
+                    return Ok(Self {
+                        reader,
+                        format,
+                        sample_format,
+                        remaining_bytes: chunk_size,
+                        bytes: Vec::new(),
+                    });
+                }
+                _ => {
+                    // Chunks are padded to an even size
+                    let skip = chunk_size + chunk_size % 2;
+                    reader.seek(SeekFrom::Current(skip as i64))
+                        .map_err(|e| format!("Failed to skip WAV chunk: {:?}", e))?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl CaptureEndpoint for WavFileCapture {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize, String> {
+        let bytes_per_sample = self.sample_format.bytes_per_sample();
+        let available = (self.remaining_bytes / bytes_per_sample as u64) as usize;
+        let count = whole_frames(buffer.len().min(available), self.format);
+
+        self.bytes.resize(count * bytes_per_sample, 0);
+        self.reader.read_exact(&mut self.bytes).map_err(|e| format!("Failed to read WAV samples: {:?}", e))?;
+        self.remaining_bytes -= self.bytes.len() as u64;
+
+        Ok(decode(self.sample_format, &self.bytes, buffer))
+    }
+
+    fn finished(&self) -> bool {
+        self.remaining_bytes < (self.sample_format.bytes_per_sample() * self.format.channels as usize) as u64
+    }
+}
+
+// Writes a WAV file, 32-bit float unless created with another sample format. The header
+// sizes are filled in by `flush` and on drop.
+#[cfg(test)]
+pub struct WavFileRender {
+    writer: BufWriter<File>,
+    format: AudioFormat,
+    quantizer: Quantizer,
+    bytes: Vec<u8>,
+    data_bytes: u32,
+}
+
+#[cfg(test)]
+impl WavFileRender {
+    pub fn create(path: impl AsRef<Path>, format: AudioFormat) -> Result<Self, String> {
+        Self::create_with_sample_format(path, format, SampleFormat::F32, Dither::default())
+    }
+
+    // `dither` applies on the way down to 16 bits
+    pub fn create_with_sample_format(
+        path: impl AsRef<Path>,
+        format: AudioFormat,
+        sample_format: SampleFormat,
+        dither: Dither,
+    ) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {:?}", path.display(), e))?;
+
+        let mut render = Self {
+            writer: BufWriter::new(file),
+            format,
+            quantizer: Quantizer::new(sample_format, format.channels as usize, dither),
+            bytes: Vec::new(),
+            data_bytes: 0,
+        };
+        render.write_header()?;
+        Ok(render)
+    }
+
+    fn write_header(&mut self) -> Result<(), String> {
+        let sample_format = self.quantizer.sample_format();
+        let block_align = self.format.channels as u32 * sample_format.bytes_per_sample() as u32;
+
+        let mut header = Vec::with_capacity(WAV_HEADER_SIZE as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(WAV_HEADER_SIZE - 8 + self.data_bytes).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&sample_format.format_tag().to_le_bytes());
+        header.extend_from_slice(&self.format.channels.to_le_bytes());
+        header.extend_from_slice(&self.format.sample_rate.to_le_bytes());
+        header.extend_from_slice(&(self.format.sample_rate * block_align).to_le_bytes());
+        header.extend_from_slice(&(block_align as u16).to_le_bytes());
+        header.extend_from_slice(&sample_format.bits_per_sample().to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&self.data_bytes.to_le_bytes());
+
+        self.writer.write_all(&header).map_err(|e| format!("Failed to write WAV header: {:?}", e))
+    }
+}
+
+#[cfg(test)]
+impl RenderEndpoint for WavFileRender {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        self.bytes.resize(samples.len() * self.quantizer.sample_format().bytes_per_sample(), 0);
+        let count = self.quantizer.encode(samples, &mut self.bytes);
+        let length = count * self.quantizer.sample_format().bytes_per_sample();
+        self.writer.write_all(&self.bytes[..length])
+            .map_err(|e| format!("Failed to write WAV samples: {:?}", e))?;
+        self.data_bytes += length as u32;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.writer.seek(SeekFrom::Start(0)).map_err(|e| format!("Failed to seek WAV file: {:?}", e))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0)).map_err(|e| format!("Failed to seek WAV file: {:?}", e))?;
+        self.writer.flush().map_err(|e| format!("Failed to flush WAV file: {:?}", e))
+    }
+}
+
+#[cfg(test)]
+impl Drop for WavFileRender {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -1,177 +1,542 @@
-use windows::core::HSTRING;
-use windows::Win32::Foundation::PWSTR;
-use windows::Win32::Media::Audio::CoreAudio::{
-    eRender,
-    IMMDeviceEnumerator,
-    MMDeviceEnumerator,
-    DEVICE_STATE_ACTIVE,
-    IAudioSessionManager2,
-    ISimpleAudioVolume,
-    IAudioSessionControl2,
-};
-use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_APARTMENTTHREADED};
-use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
-
-#[derive(Debug, Clone, serde::Serialize)]
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use crate::audio_endpoints::{CaptureEndpoint, RenderEndpoint};
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct AudioSession {
     pub id: String,
     pub display_name: String,
     pub volume: f32,
     pub muted: bool,
     pub process_id: u32,
+    pub executable_path: String,
+    // Icon the application set for the session, often empty
+    pub icon_path: String,
+    pub state: SessionState,
+    pub device_id: String,
+    pub channel_count: u32,
+    // One entry per channel, in the order used by the channel volume calls
+    pub channel_layout: Vec<ChannelPosition>,
 }
 
-pub fn enumerate_audio_sessions() -> Result<Vec<AudioSession>, String> {
-    unsafe {
-        CoInitializeEx(None, COINIT_APARTMENTTHREADED).map_err(|e| format!("Failed to initialize COM: {:?}", e))?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionState {
+    Inactive,
+    Active,
+    Expired,
+}
 
-        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
-            .map_err(|e| format!("Failed to create device enumerator: {:?}", e))?;
+// Speaker a channel feeds; mono streams report a single FrontCenter channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelPosition {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    FrontLeftOfCenter,
+    FrontRightOfCenter,
+    BackCenter,
+    SideLeft,
+    SideRight,
+    Other,
+}
 
-        let device = enumerator.GetDefaultAudioEndpoint(eRender, DEVICE_STATE_ACTIVE)
-            .map_err(|e| format!("Failed to get default audio endpoint: {:?}", e))?;
+impl ChannelPosition {
+    fn is_left(self) -> bool {
+        matches!(
+            self,
+            ChannelPosition::FrontLeft
+                | ChannelPosition::BackLeft
+                | ChannelPosition::FrontLeftOfCenter
+                | ChannelPosition::SideLeft
+        )
+    }
 
-        let session_manager2: IAudioSessionManager2 = device.Activate(CLSCTX_ALL, None)
-            .map_err(|e| format!("Failed to activate audio session manager: {:?}", e))?;
+    fn is_right(self) -> bool {
+        matches!(
+            self,
+            ChannelPosition::FrontRight
+                | ChannelPosition::BackRight
+                | ChannelPosition::FrontRightOfCenter
+                | ChannelPosition::SideRight
+        )
+    }
+}
 
-        let session_enumerator = session_manager2.GetSessionEnumerator()
-            .map_err(|e| format!("Failed to get session enumerator: {:?}", e))?;
+// Positions in WAVEFORMATEXTENSIBLE dwChannelMask bit order (SPEAKER_FRONT_LEFT = bit 0, ...)
+const CHANNEL_MASK_POSITIONS: [ChannelPosition; 11] = [
+    ChannelPosition::FrontLeft,
+    ChannelPosition::FrontRight,
+    ChannelPosition::FrontCenter,
+    ChannelPosition::LowFrequency,
+    ChannelPosition::BackLeft,
+    ChannelPosition::BackRight,
+    ChannelPosition::FrontLeftOfCenter,
+    ChannelPosition::FrontRightOfCenter,
+    ChannelPosition::BackCenter,
+    ChannelPosition::SideLeft,
+    ChannelPosition::SideRight,
+];
+
+// Channels are interleaved in mask bit order; channels past the named bits (top speakers,
+// or more channels than the mask describes) are reported as Other
+pub fn channel_layout_from_mask(channel_mask: u32, channel_count: usize) -> Vec<ChannelPosition> {
+    let mut layout: Vec<ChannelPosition> = CHANNEL_MASK_POSITIONS.iter()
+        .enumerate()
+        .filter(|(bit, _)| channel_mask & (1 << bit) != 0)
+        .map(|(_, position)| *position)
+        .collect();
+    layout.resize(channel_count, ChannelPosition::Other);
+    layout
+}
 
-        let count = session_enumerator.GetCount()
-            .map_err(|e| format!("Failed to get session count: {:?}", e))?;
+// Layout Windows assumes for a plain WAVEFORMATEX with no channel mask (KSAUDIO_SPEAKER_*)
+pub fn default_channel_layout(channel_count: usize) -> Vec<ChannelPosition> {
+    let channel_mask = match channel_count {
+        1 => 0x4,
+        2 => 0x3,
+        4 => 0x33,
+        6 => 0x3f,
+        8 => 0x63f,
+        _ => 0x3,
+    };
+    channel_layout_from_mask(channel_mask, channel_count)
+}
 
-        let mut sessions = Vec::new();
+// Constant-power pan law for `pan` in -1.0 (left) ..= 1.0 (right).
+// left² + right² is always 1, so the total power and with it the perceived loudness
+// stays the same across the whole range; the centre is -3 dB on each side.
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    (angle.cos(), angle.sin())
+}
 
-        for i in 0..count {
-            let session_control = session_enumerator.GetSession(i)
-                .map_err(|e| format!("Failed to get session control: {:?}", e))?;
+// Per-channel volumes that pan a session with `layout`; centre and LFE channels are not panned
+pub fn balance_channel_volumes(layout: &[ChannelPosition], pan: f32) -> Vec<f32> {
+    let (left, right) = pan_gains(pan);
+    layout.iter()
+        .map(|position| {
+            if position.is_left() {
+                left
+            } else if position.is_right() {
+                right
+            } else {
+                1.0
+            }
+        })
+        .collect()
+}
 
-            let session_control2: IAudioSessionControl2 = session_control.cast()
-                .map_err(|e| format!("Failed to cast to IAudioSessionControl2: {:?}", e))?;
+// Direction of an audio endpoint: playback (render) or recording (capture)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceFlow {
+    Render,
+    Capture,
+}
 
-            let process_id = session_control2.GetProcessId()
-                .map_err(|e| format!("Failed to get process ID: {:?}", e))?;
+// Only WASAPI lists devices that are not active; pactl leaves them out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub enum DeviceState {
+    Active,
+    Disabled,
+    NotPresent,
+    Unplugged,
+}
 
-            let display_name_pwstr = session_control2.GetDisplayName()
-                .map_err(|e| format!("Failed to get display name: {:?}", e))?;
-            let display_name = pwstr_to_string(display_name_pwstr);
+// Roles a device can be the system default for, matching WASAPI's ERole
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceRole {
+    Console,
+    Multimedia,
+    Communications,
+}
 
-            let simple_audio_volume: ISimpleAudioVolume = session_control.cast()
-                .map_err(|e| format!("Failed to cast to ISimpleAudioVolume: {:?}", e))?;
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AudioDevice {
+    pub id: String,
+    pub name: String,
+    pub flow: DeviceFlow,
+    pub state: DeviceState,
+    // Empty unless the device is the default endpoint for its flow
+    pub default_roles: Vec<DeviceRole>,
+}
 
-            let mut volume = 0.0;
-            simple_audio_volume.GetMasterVolume(&mut volume)
-                .map_err(|e| format!("Failed to get master volume: {:?}", e))?;
+// Hardware volume range of an endpoint, as reported by IAudioEndpointVolume::GetVolumeRange
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct VolumeRange {
+    pub min_db: f32,
+    pub max_db: f32,
+    // Zero when the volume is continuous
+    pub step_db: f32,
+}
 
-            let mut muted = 0;
-            simple_audio_volume.GetMute(&mut muted)
-                .map_err(|e| format!("Failed to get mute state: {:?}", e))?;
+// Current peak meter values (0.0..=1.0) keyed by session ID and by device ID
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeakLevels {
+    pub sessions: HashMap<String, f32>,
+    pub devices: HashMap<String, f32>,
+}
+
+// Raw identity strings a backend reports for one session.
+// On WASAPI these come from IAudioSessionControl2::GetSessionIdentifier and
+// GetSessionInstanceIdentifier; other backends build equivalents from what they have.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionIdentity {
+    // Same for every run of the same application on the same device
+    pub session_identifier: String,
+    // Unique among the sessions that currently exist
+    pub instance_identifier: String,
+    pub executable_path: String,
+}
 
-            sessions.push(AudioSession {
-                id: format!("{}", process_id), // Use process ID as a simple ID for now
-                display_name,
-                volume,
-                muted: muted != 0,
-                process_id,
-            });
+// Change notifications delivered to subscribers of an audio backend
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionEvent {
+    Added(AudioSession),
+    Removed { id: String },
+    VolumeChanged { id: String, volume: f32 },
+    MuteChanged { id: String, muted: bool },
+    StateChanged { id: String, state: SessionState },
+}
+
+impl SessionEvent {
+    // Name of the Tauri event this change is emitted as
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            SessionEvent::Added(_) => "session-added",
+            SessionEvent::Removed { .. } => "session-removed",
+            SessionEvent::VolumeChanged { .. } => "session-volume-changed",
+            SessionEvent::MuteChanged { .. } => "session-mute-changed",
+            SessionEvent::StateChanged { .. } => "session-state-changed",
         }
-        Ok(sessions)
     }
 }
 
-fn pwstr_to_string(pwstr: PWSTR) -> String {
-    unsafe {
-        let len = (0..).take_while(|&i| *pwstr.0.add(i) != 0).count();
-        let slice = std::slice::from_raw_parts(pwstr.0, len);
-        String::from_utf16_lossy(slice)
+// Platform-neutral access to per-application audio sessions.
+// Every platform API (WASAPI, PulseAudio, ...) is one implementation of this trait,
+// and the rest of the application only talks to the backend chosen at startup.
+pub trait AudioBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Every render and capture endpoint, including disabled and unplugged ones
+    fn enumerate_devices(&self) -> Result<Vec<AudioDevice>, String>;
+
+    // Sessions on every active device; session IDs are unique across devices
+    fn enumerate_sessions(&self) -> Result<Vec<AudioSession>, String>;
+
+    fn enumerate_device_sessions(&self, device_id: &str) -> Result<Vec<AudioSession>, String> {
+        let sessions = self.enumerate_sessions()?;
+        Ok(sessions.into_iter().filter(|session| session.device_id == device_id).collect())
     }
-}
 
-pub fn set_session_volume(process_id: u32, volume: f32) -> Result<(), String> {
-    unsafe {
-        CoInitializeEx(None, COINIT_APARTMENTTHREADED).map_err(|e| format!("Failed to initialize COM: {:?}", e))?;
+    // Endpoint master volume as a 0.0..=1.0 scalar, like the Windows tray flyout
+    fn get_device_volume(&self, device_id: &str) -> Result<f32, String>;
 
-        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
-            .map_err(|e| format!("Failed to create device enumerator: {:?}", e))?;
+    fn set_device_volume(&self, device_id: &str, volume: f32) -> Result<(), String>;
 
-        let device = enumerator.GetDefaultAudioEndpoint(eRender, DEVICE_STATE_ACTIVE)
-            .map_err(|e| format!("Failed to get default audio endpoint: {:?}", e))?;
+    fn get_device_mute(&self, device_id: &str) -> Result<bool, String>;
 
-        let session_manager2: IAudioSessionManager2 = device.Activate(CLSCTX_ALL, None)
-            .map_err(|e| format!("Failed to activate audio session manager: {:?}", e))?;
+    fn set_device_mute(&self, device_id: &str, muted: bool) -> Result<(), String>;
 
-        let session_enumerator = session_manager2.GetSessionEnumerator()
-            .map_err(|e| format!("Failed to get session enumerator: {:?}", e))?;
+    fn get_device_volume_range(&self, device_id: &str) -> Result<VolumeRange, String>;
 
-        let count = session_enumerator.GetCount()
-            .map_err(|e| format!("Failed to get session count: {:?}", e))?;
+    fn get_session_volume(&self, session_id: &str) -> Result<f32, String>;
 
-        for i in 0..count {
-            let session_control = session_enumerator.GetSession(i)
-                .map_err(|e| format!("Failed to get session control: {:?}", e))?;
+    fn set_session_volume(&self, session_id: &str, volume: f32) -> Result<(), String>;
 
-            let session_control2: IAudioSessionControl2 = session_control.cast()
-                .map_err(|e| format!("Failed to cast to IAudioSessionControl2: {:?}", e))?;
+    // Per-channel levels relative to the session volume, as with IChannelAudioVolume;
+    // a channel plays at volume * channel volume
+    fn get_session_channel_volumes(&self, session_id: &str) -> Result<Vec<f32>, String>;
 
-            let current_process_id = session_control2.GetProcessId()
-                .map_err(|e| format!("Failed to get process ID: {:?}", e))?;
+    // Takes one 0.0..=1.0 level per channel of the session's layout
+    fn set_session_channel_volumes(&self, session_id: &str, volumes: &[f32]) -> Result<(), String>;
 
-            if current_process_id == process_id {
-                let simple_audio_volume: ISimpleAudioVolume = session_control.cast()
-                    .map_err(|e| format!("Failed to cast to ISimpleAudioVolume: {:?}", e))?;
+    fn get_session_mute(&self, session_id: &str) -> Result<bool, String>;
 
-                simple_audio_volume.SetMasterVolume(volume, None)
-                    .map_err(|e| format!("Failed to set master volume: {:?}", e))?;
-                return Ok(());
-            }
+    fn set_session_mute(&self, session_id: &str, muted: bool) -> Result<(), String>;
+
+    // Peak of every session and active device since the previous call, as reported by
+    // IAudioMeterInformation; cheap enough to poll many times per second
+    fn get_peak_levels(&self) -> Result<PeakLevels, String>;
+
+    // Product name of an executable (e.g. "Google Chrome" for chrome.exe), used when a
+    // session has no display name
+    fn describe_executable(&self, _executable_path: &str) -> Option<String> {
+        None
+    }
+
+    // Returns a channel that receives every session change seen by the backend
+    fn subscribe(&self) -> Result<Receiver<SessionEvent>, String>;
+
+    // Opens a device as the source of a processing pipeline. Render devices are captured
+    // in loopback, so the pipeline hears what is played on them.
+    fn open_capture(&self, _device_id: &str) -> Result<Box<dyn CaptureEndpoint>, String> {
+        Err(format!("The {} backend cannot capture audio", self.name()))
+    }
+
+    // Opens a render device as the destination of a processing pipeline
+    fn open_render(&self, _device_id: &str) -> Result<Box<dyn RenderEndpoint>, String> {
+        Err(format!("The {} backend cannot render audio", self.name()))
+    }
+}
+
+// Picks the native backend for the platform we are running on
+pub fn default_backend() -> Arc<dyn AudioBackend> {
+    #[cfg(windows)]
+    {
+        Arc::new(crate::wasapi_backend::WasapiBackend::new())
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if crate::pulse_backend::PulseBackend::is_available() {
+            return Arc::new(crate::pulse_backend::PulseBackend::new());
         }
-        Err(format!("Session with process ID {} not found", process_id))
+        Arc::new(NullBackend::new())
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        Arc::new(NullBackend::new())
     }
 }
 
-pub fn toggle_session_mute(process_id: u32) -> Result<(), String> {
-    unsafe {
-        CoInitializeEx(None, COINIT_APARTMENTTHREADED).map_err(|e| format!("Failed to initialize COM: {:?}", e))?;
+pub fn toggle_session_mute(backend: &dyn AudioBackend, session_id: &str) -> Result<(), String> {
+    let muted = backend.get_session_mute(session_id)?;
+    backend.set_session_mute(session_id, !muted)
+}
+
+pub fn set_session_balance(backend: &dyn AudioBackend, session_id: &str, pan: f32) -> Result<(), String> {
+    let session = backend.enumerate_sessions()?
+        .into_iter()
+        .find(|session| session.id == session_id)
+        .ok_or_else(|| session_not_found(session_id))?;
+    backend.set_session_channel_volumes(session_id, &balance_channel_volumes(&session.channel_layout, pan))
+}
+
+// Shared argument check for set_session_channel_volumes implementations
+pub fn validate_channel_volumes(volumes: &[f32], channel_count: usize) -> Result<(), String> {
+    if volumes.len() != channel_count {
+        return Err(format!(
+            "Failed to set channel volumes: expected {} channels, got {}",
+            channel_count,
+            volumes.len()
+        ));
+    }
+    if let Some(volume) = volumes.iter().find(|volume| !(0.0..=1.0).contains(*volume)) {
+        return Err(format!("Failed to set channel volumes: {} is out of range", volume));
+    }
+    Ok(())
+}
+
+pub fn session_not_found(session_id: &str) -> String {
+    format!("Session {} not found", session_id)
+}
+
+pub fn device_not_found(device_id: &str) -> String {
+    format!("Device {} not found", device_id)
+}
+
+// Derives the public session IDs for one enumeration, in the same order as `identities`.
+// The ID is a hash of the session identifier and executable path, so it survives restarts
+// of both the application and HyperVolume and never contains the PID. Sessions that share
+// that hash (e.g. several instances of one browser) get a "-2", "-3", ... suffix ordered
+// by their instance identifier, which keeps every ID unique within the enumeration.
+pub fn assign_session_ids(identities: &[SessionIdentity]) -> Vec<String> {
+    let base_ids: Vec<String> = identities.iter()
+        .map(|identity| stable_session_id(&identity.session_identifier, &identity.executable_path))
+        .collect();
+
+    let mut order: Vec<usize> = (0..identities.len()).collect();
+    order.sort_by(|&a, &b| {
+        base_ids[a].cmp(&base_ids[b])
+            .then_with(|| identities[a].instance_identifier.cmp(&identities[b].instance_identifier))
+    });
+
+    let mut ids = base_ids.clone();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for index in order {
+        let count = seen.entry(base_ids[index].as_str()).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            ids[index] = format!("{}-{}", base_ids[index], count);
+        }
+    }
+    ids
+}
 
-        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
-            .map_err(|e| format!("Failed to create device enumerator: {:?}", e))?;
+fn stable_session_id(session_identifier: &str, executable_path: &str) -> String {
+    stable_id(&[session_identifier, &executable_path.to_lowercase()])
+}
 
-        let device = enumerator.GetDefaultAudioEndpoint(eRender, DEVICE_STATE_ACTIVE)
-            .map_err(|e| format!("Failed to get default audio endpoint: {:?}", e))?;
+// 64-bit FNV-1a of the NUL-separated parts, chosen because it is stable across Rust
+// releases unlike DefaultHasher
+pub fn stable_id(parts: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (index, part) in parts.iter().enumerate() {
+        let separator = (index > 0).then_some(0u8);
+        for byte in separator.into_iter().chain(part.bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
 
-        let session_manager2: IAudioSessionManager2 = device.Activate(CLSCTX_ALL, None)
-            .map_err(|e| format!("Failed to activate audio session manager: {:?}", e))?;
+// Fan-out list of subscriber channels shared by backend implementations
+#[derive(Default)]
+pub struct SessionSubscribers {
+    senders: Mutex<Vec<Sender<SessionEvent>>>,
+}
 
-        let session_enumerator = session_manager2.GetSessionEnumerator()
-            .map_err(|e| format!("Failed to get session enumerator: {:?}", e))?;
+impl SessionSubscribers {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        let count = session_enumerator.GetCount()
-            .map_err(|e| format!("Failed to get session count: {:?}", e))?;
+    pub fn subscribe(&self) -> Result<Receiver<SessionEvent>, String> {
+        let (sender, receiver) = channel();
+        self.senders.lock().map_err(|e| format!("Lock error: {:?}", e))?.push(sender);
+        Ok(receiver)
+    }
 
-        for i in 0..count {
-            let session_control = session_enumerator.GetSession(i)
-                .map_err(|e| format!("Failed to get session control: {:?}", e))?;
+    pub fn has_subscribers(&self) -> bool {
+        self.senders.lock().map(|senders| !senders.is_empty()).unwrap_or(false)
+    }
 
-            let session_control2: IAudioSessionControl2 = session_control.cast()
-                .map_err(|e| format!("Failed to cast to IAudioSessionControl2: {:?}", e))?;
+    pub fn publish(&self, event: SessionEvent) {
+        if let Ok(mut senders) = self.senders.lock() {
+            // Dropped receivers are pruned here instead of on unsubscribe
+            senders.retain(|sender| sender.send(event.clone()).is_ok());
+        }
+    }
+}
 
-            let current_process_id = session_control2.GetProcessId()
-                .map_err(|e| format!("Failed to get process ID: {:?}", e))?;
+// Computes the events that turn the `previous` session list into `current`
+pub fn diff_sessions(previous: &[AudioSession], current: &[AudioSession]) -> Vec<SessionEvent> {
+    let previous_by_id: HashMap<&str, &AudioSession> = previous.iter().map(|s| (s.id.as_str(), s)).collect();
+    let current_by_id: HashMap<&str, &AudioSession> = current.iter().map(|s| (s.id.as_str(), s)).collect();
 
-            if current_process_id == process_id {
-                let simple_audio_volume: ISimpleAudioVolume = session_control.cast()
-                    .map_err(|e| format!("Failed to cast to ISimpleAudioVolume: {:?}", e))?;
+    let mut events = Vec::new();
 
-                let mut muted = 0;
-                simple_audio_volume.GetMute(&mut muted)
-                    .map_err(|e| format!("Failed to get mute state: {:?}", e))?;
+    for session in previous {
+        if !current_by_id.contains_key(session.id.as_str()) {
+            events.push(SessionEvent::Removed { id: session.id.clone() });
+        }
+    }
 
-                simple_audio_volume.SetMute(muted == 0, None)
-                    .map_err(|e| format!("Failed to set mute state: {:?}", e))?;
-                return Ok(());
+    for session in current {
+        match previous_by_id.get(session.id.as_str()) {
+            None => events.push(SessionEvent::Added(session.clone())),
+            Some(old) => {
+                if old.volume != session.volume {
+                    events.push(SessionEvent::VolumeChanged {
+                        id: session.id.clone(),
+                        volume: session.volume,
+                    });
+                }
+                if old.muted != session.muted {
+                    events.push(SessionEvent::MuteChanged {
+                        id: session.id.clone(),
+                        muted: session.muted,
+                    });
+                }
+                if old.state != session.state {
+                    events.push(SessionEvent::StateChanged {
+                        id: session.id.clone(),
+                        state: session.state,
+                    });
+                }
             }
         }
-        Err(format!("Session with process ID {} not found", process_id))
+    }
+
+    events
+}
+
+// Backend for platforms without native session control: no sessions, every control call fails
+#[derive(Default)]
+pub struct NullBackend {
+    subscribers: SessionSubscribers,
+}
+
+impl NullBackend {
+    pub fn new() -> Self {
+        Self::default()
     }
 }
 
+impl AudioBackend for NullBackend {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn enumerate_devices(&self) -> Result<Vec<AudioDevice>, String> {
+        Ok(Vec::new())
+    }
+
+    fn enumerate_sessions(&self) -> Result<Vec<AudioSession>, String> {
+        Ok(Vec::new())
+    }
+
+    fn get_device_volume(&self, device_id: &str) -> Result<f32, String> {
+        Err(device_not_found(device_id))
+    }
+
+    fn set_device_volume(&self, device_id: &str, _volume: f32) -> Result<(), String> {
+        Err(device_not_found(device_id))
+    }
+
+    fn get_device_mute(&self, device_id: &str) -> Result<bool, String> {
+        Err(device_not_found(device_id))
+    }
+
+    fn set_device_mute(&self, device_id: &str, _muted: bool) -> Result<(), String> {
+        Err(device_not_found(device_id))
+    }
+
+    fn get_device_volume_range(&self, device_id: &str) -> Result<VolumeRange, String> {
+        Err(device_not_found(device_id))
+    }
+
+    fn get_session_volume(&self, session_id: &str) -> Result<f32, String> {
+        Err(session_not_found(session_id))
+    }
+
+    fn set_session_volume(&self, session_id: &str, _volume: f32) -> Result<(), String> {
+        Err(session_not_found(session_id))
+    }
+
+    fn get_session_channel_volumes(&self, session_id: &str) -> Result<Vec<f32>, String> {
+        Err(session_not_found(session_id))
+    }
+
+    fn set_session_channel_volumes(&self, session_id: &str, _volumes: &[f32]) -> Result<(), String> {
+        Err(session_not_found(session_id))
+    }
+
+    fn get_session_mute(&self, session_id: &str) -> Result<bool, String> {
+        Err(session_not_found(session_id))
+    }
+
+    fn set_session_mute(&self, session_id: &str, _muted: bool) -> Result<(), String> {
+        Err(session_not_found(session_id))
+    }
+
+    fn get_peak_levels(&self) -> Result<PeakLevels, String> {
+        Ok(PeakLevels::default())
+    }
+
+    fn subscribe(&self) -> Result<Receiver<SessionEvent>, String> {
+        self.subscribers.subscribe()
+    }
+}
@@ -0,0 +1,514 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::audio_boost::{AudioProcessor, BoostControl};
+use crate::audio_endpoints::{AudioFormat, CaptureEndpoint, RenderEndpoint};
+use crate::channel_mixer::ChannelMixer;
+use crate::equalizer::EqControl;
+use crate::resampler::{Resampler, ResamplerQuality};
+use crate::virtual_device::{
+    StreamState, StreamStats, VirtualAudioDevice, VirtualDeviceConfig, VirtualDeviceHandle, VirtualDeviceInput,
+    VirtualDeviceOutput,
+};
+
+// 10 ms at 48 kHz
+pub const DEFAULT_BLOCK_FRAMES: usize = 480;
+
+// How long the runner waits when a live source has nothing to read
+const IDLE_WAIT: Duration = Duration::from_millis(2);
+
+const PIPELINE_DEVICE_NAME: &str = "HyperVolume pipeline";
+
+// Capture -> boost (AudioProcessor::process_with_control) -> render, one block at a time.
+// The capture endpoint may run at another sample rate and channel count than the render
+// endpoint; the audio is then converted to the render format first and processed in it.
+// The frames the limiter's look-ahead delays by are dropped at the start and flushed out
+// at the end, so the rendered stream lines up with the captured one.
+pub struct BoostPipeline {
+    capture: Box<dyn CaptureEndpoint>,
+    render: Box<dyn RenderEndpoint>,
+    processor: AudioProcessor,
+    boost: BoostControl,
+    // None while both endpoints share one format
+    converter: Option<FormatConverter>,
+    block_frames: usize,
+    buffer: Vec<f32>,
+    // Leading output frames that are still the limiter's empty delay line
+    skip_frames: usize,
+}
+
+impl BoostPipeline {
+    pub fn new(
+        capture: Box<dyn CaptureEndpoint>,
+        render: Box<dyn RenderEndpoint>,
+        boost: BoostControl,
+    ) -> Result<Self, String> {
+        Self::with_block_frames(capture, render, boost, DEFAULT_BLOCK_FRAMES)
+    }
+
+    pub fn with_block_frames(
+        capture: Box<dyn CaptureEndpoint>,
+        render: Box<dyn RenderEndpoint>,
+        boost: BoostControl,
+        block_frames: usize,
+    ) -> Result<Self, String> {
+        let source = capture.format();
+        let format = render.format();
+        for endpoint in [source, format] {
+            if endpoint.channels == 0 || endpoint.sample_rate == 0 || block_frames == 0 {
+                return Err(format!("Invalid pipeline block: {} frames of {:?}", block_frames, endpoint));
+            }
+        }
+
+        let converter = FormatConverter::new(source, format, block_frames, ResamplerQuality::default())?;
+        let processor = AudioProcessor::for_control(format, &boost);
+        Ok(Self {
+            capture,
+            render,
+            skip_frames: processor.latency_frames(),
+            processor,
+            boost,
+            buffer: vec![0.0; buffer_frames(converter.as_ref(), block_frames) * format.channels as usize],
+            converter,
+            block_frames,
+        })
+    }
+
+    // Runs the render device's EQ after the session's processing
+    pub fn with_device_equalizer(mut self, equalizer: EqControl) -> Self {
+        self.processor.set_device_equalizer(equalizer);
+        self
+    }
+
+    // Converts the sample rate at `quality` where the endpoints' rates differ
+    pub fn with_resampler_quality(mut self, quality: ResamplerQuality) -> Result<Self, String> {
+        if self.converter.as_ref().is_some_and(|converter| converter.resampler.is_some()) {
+            self.converter = FormatConverter::new(self.capture.format(), self.format(), self.block_frames, quality)?;
+            let samples = buffer_frames(self.converter.as_ref(), self.block_frames) * self.format().channels as usize;
+            self.buffer.resize(samples, 0.0);
+        }
+        Ok(self)
+    }
+
+    // Format the audio is processed and rendered in
+    pub fn format(&self) -> AudioFormat {
+        self.render.format()
+    }
+
+    // Moves one block from capture to render and returns the number of samples captured.
+    // The boost factor is read once per block, so a change applies from the next block on.
+    pub fn process_block(&mut self) -> Result<usize, String> {
+        let (count, converted) = match &mut self.converter {
+            Some(converter) => {
+                let count = self.capture.read(&mut converter.input)?;
+                (count, converter.convert(count, &mut self.buffer))
+            }
+            None => {
+                let count = self.capture.read(&mut self.buffer)?;
+                (count, count)
+            }
+        };
+        if count == 0 {
+            return Ok(0);
+        }
+
+        self.processor.process_with_control(&mut self.buffer[..converted], &self.boost);
+        self.write_processed(converted)?;
+        Ok(count)
+    }
+
+    fn write_processed(&mut self, count: usize) -> Result<(), String> {
+        let channels = self.format().channels as usize;
+        let skip = self.skip_frames.min(count / channels);
+        self.skip_frames -= skip;
+
+        if skip * channels < count {
+            self.render.write(&self.buffer[skip * channels..count])?;
+        }
+        Ok(())
+    }
+
+    // Pushes the audio still held by the resampler and the limiter out to the render
+    // endpoint and flushes it
+    pub fn finish(&mut self) -> Result<(), String> {
+        if let Some(converter) = &mut self.converter {
+            let count = converter.flush(&mut self.buffer);
+            if count > 0 {
+                self.processor.process_with_control(&mut self.buffer[..count], &self.boost);
+                self.write_processed(count)?;
+            }
+        }
+
+        let channels = self.format().channels as usize;
+        let mut remaining = self.processor.latency_frames() * channels;
+
+        while remaining > 0 {
+            let count = remaining.min(self.buffer.len());
+            self.buffer[..count].fill(0.0);
+            self.processor.process_samples(&mut self.buffer[..count], 1.0);
+            self.write_processed(count)?;
+            remaining -= count;
+        }
+        self.render.flush()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.capture.finished()
+    }
+
+    // Processes a finite source until it is exhausted; returns the number of samples moved
+    #[cfg(test)]
+    pub fn run_to_end(&mut self) -> Result<usize, String> {
+        let mut total = 0;
+        while !self.finished() {
+            let count = self.process_block()?;
+            if count == 0 && !self.finished() {
+                return Err("Capture endpoint stalled before the end of its stream".to_string());
+            }
+            total += count;
+        }
+        self.finish()?;
+        Ok(total)
+    }
+}
+
+// Frames of the render format one block, or the flush at the end, may turn into
+fn buffer_frames(converter: Option<&FormatConverter>, block_frames: usize) -> usize {
+    resampled_frames(converter.and_then(|converter| converter.resampler.as_ref()), block_frames)
+}
+
+fn resampled_frames(resampler: Option<&Resampler>, block_frames: usize) -> usize {
+    match resampler {
+        Some(resampler) => resampler.max_output_frames(block_frames).max(resampler.max_flush_frames()),
+        None => block_frames,
+    }
+}
+
+// Converts captured blocks to the render format: the channels on the side with fewer of
+// them, so the resampler has as little to do as possible, and the sample rate
+struct FormatConverter {
+    // One captured block
+    input: Vec<f32>,
+    mixer: Option<ChannelMixer>,
+    resampler: Option<Resampler>,
+    mix_first: bool,
+    // Between the mixer and the resampler
+    scratch: Vec<f32>,
+}
+
+impl FormatConverter {
+    fn new(source: AudioFormat, format: AudioFormat, block_frames: usize, quality: ResamplerQuality) -> Result<Option<Self>, String> {
+        if source == format {
+            return Ok(None);
+        }
+
+        let inputs = source.channels as usize;
+        let outputs = format.channels as usize;
+        let mixer = (inputs != outputs).then(|| ChannelMixer::for_channels(inputs, outputs));
+        let resampler = if source.sample_rate != format.sample_rate {
+            let channels = inputs.min(outputs);
+            Some(Resampler::new(source.sample_rate, format.sample_rate, channels, quality)?)
+        } else {
+            None
+        };
+        let mix_first = inputs > outputs;
+
+        let scratch = match (&mixer, &resampler) {
+            (Some(_), Some(_)) if mix_first => block_frames * outputs,
+            (Some(_), Some(resampler)) => resampled_frames(Some(resampler), block_frames) * inputs,
+            _ => 0,
+        };
+
+        Ok(Some(Self {
+            input: vec![0.0; block_frames * inputs],
+            mixer,
+            resampler,
+            mix_first,
+            scratch: vec![0.0; scratch],
+        }))
+    }
+
+    // Converts the first `count` samples of `input` into `output` and returns the number of
+    // samples written
+    fn convert(&mut self, count: usize, output: &mut [f32]) -> usize {
+        let input = &self.input[..count];
+        match (&self.mixer, &mut self.resampler) {
+            (Some(mixer), Some(resampler)) if self.mix_first => {
+                let mixed = mixer.process(input, &mut self.scratch);
+                resampler.process(&self.scratch[..mixed], output)
+            }
+            (Some(mixer), Some(resampler)) => {
+                let resampled = resampler.process(input, &mut self.scratch);
+                mixer.process(&self.scratch[..resampled], output)
+            }
+            (Some(mixer), None) => mixer.process(input, output),
+            (None, Some(resampler)) => resampler.process(input, output),
+            (None, None) => {
+                output[..count].copy_from_slice(input);
+                count
+            }
+        }
+    }
+
+    // Converts what the resampler still holds at the end of the stream
+    fn flush(&mut self, output: &mut [f32]) -> usize {
+        match (&self.mixer, &mut self.resampler) {
+            (Some(mixer), Some(resampler)) if !self.mix_first => {
+                let resampled = resampler.flush(&mut self.scratch);
+                mixer.process(&self.scratch[..resampled], output)
+            }
+            (_, Some(resampler)) => resampler.flush(output),
+            (_, None) => 0,
+        }
+    }
+}
+
+// Runs a pipeline until stopped or until a finite source runs out. The pipeline captures
+// and processes on one thread and writes into a virtual device; a second thread hosts the
+// device and renders its output. A render endpoint that plays at its device's pace gets a
+// period whenever one is queued or it is about to run dry, and silence where the capture
+// side fell behind, so capture and render never wait on each other's hardware clock; the
+// device's stats show where the two drift apart.
+// Each thread opens its endpoints itself, because WASAPI endpoints must stay on the thread
+// that created them.
+pub struct PipelineRunner {
+    stop_requested: Arc<AtomicBool>,
+    device: VirtualDeviceHandle,
+    thread: Option<JoinHandle<Result<(), String>>>,
+    render_thread: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl PipelineRunner {
+    pub fn start<R, F>(open_render: R, build: F) -> Result<Self, String>
+    where
+        R: FnOnce() -> Result<Box<dyn RenderEndpoint>, String> + Send + 'static,
+        F: FnOnce(Box<dyn RenderEndpoint>) -> Result<BoostPipeline, String> + Send + 'static,
+    {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        let (opened, open_result) = channel();
+
+        let render_stop_requested = Arc::clone(&stop_requested);
+        let render_thread = thread::spawn(move || {
+            let (render, device, input, output) = match open_render().and_then(open_device) {
+                Ok(opened_device) => opened_device,
+                Err(e) => {
+                    let _ = opened.send(Err(e));
+                    return Ok(());
+                }
+            };
+            let _ = opened.send(Ok((input, device.handle())));
+            render_device(render, device, output, &render_stop_requested)
+        });
+
+        let (input, device) = match open_result.recv() {
+            Ok(opened_device) => opened_device?,
+            Err(e) => return Err(format!("Failed to start audio pipeline: {:?}", e)),
+        };
+        let (started, start_result) = channel();
+
+        let thread_stop_requested = Arc::clone(&stop_requested);
+        let thread_device = device.clone();
+        let thread = thread::spawn(move || {
+            let render = DeviceInput {
+                input,
+                device: thread_device.clone(),
+                started: false,
+            };
+            let mut pipeline = match build(Box::new(render)) {
+                Ok(pipeline) => {
+                    let _ = started.send(Ok(()));
+                    pipeline
+                }
+                Err(e) => {
+                    let _ = started.send(Err(e));
+                    return Ok(());
+                }
+            };
+
+            let result = match run_pipeline(&mut pipeline, &thread_stop_requested) {
+                // Stopping the device fails a write that waits for room in it
+                Err(_) if thread_stop_requested.load(Ordering::SeqCst) => Ok(()),
+                result => result,
+            };
+            // The render thread plays out what is queued and then ends; a source that ran
+            // out before its first block still has to end the stream
+            thread_device.start();
+            thread_device.drain();
+            result
+        });
+
+        // Dropping the runner stops the render thread if the pipeline could not be built
+        let runner = Self {
+            stop_requested,
+            device,
+            thread: Some(thread),
+            render_thread: Some(render_thread),
+        };
+        start_result.recv()
+            .map_err(|e| format!("Failed to start audio pipeline: {:?}", e))??;
+        Ok(runner)
+    }
+
+    // False once the source ran out and was played, or the pipeline failed
+    pub fn is_running(&self) -> bool {
+        [&self.thread, &self.render_thread].iter()
+            .any(|thread| thread.as_ref().is_some_and(|thread| !thread.is_finished()))
+    }
+
+    // Underruns where capture fell behind render, overruns where it ran ahead
+    pub fn stats(&self) -> StreamStats {
+        self.device.stats()
+    }
+
+    // Waits for a finite source to run out and returns the pipeline's result
+    #[cfg(test)]
+    pub fn wait(mut self) -> Result<(), String> {
+        self.join()
+    }
+
+    // Stops the pipeline and returns the error it failed with, if any
+    pub fn stop(&mut self) -> Result<(), String> {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        // Releases a pipeline waiting for room in the device
+        self.device.stop();
+        self.join()
+    }
+
+    // A render error comes first: the pipeline then only failed because the device stopped
+    fn join(&mut self) -> Result<(), String> {
+        let pipeline = join_thread(self.thread.take(), "Audio pipeline thread panicked");
+        let render = join_thread(self.render_thread.take(), "Audio render thread panicked");
+        render.and(pipeline)
+    }
+}
+
+fn join_thread(thread: Option<JoinHandle<Result<(), String>>>, panicked: &str) -> Result<(), String> {
+    match thread {
+        Some(thread) => thread.join().map_err(|_| panicked.to_string())?,
+        None => Ok(()),
+    }
+}
+
+fn run_pipeline(pipeline: &mut BoostPipeline, stop_requested: &AtomicBool) -> Result<(), String> {
+    while !stop_requested.load(Ordering::SeqCst) {
+        if pipeline.process_block()? == 0 {
+            if pipeline.finished() {
+                return pipeline.finish();
+            }
+            thread::sleep(IDLE_WAIT);
+        }
+    }
+    pipeline.render.flush()
+}
+
+// The device between a pipeline and `render`, in the render endpoint's format
+type OpenedDevice = (Box<dyn RenderEndpoint>, VirtualAudioDevice, VirtualDeviceInput, VirtualDeviceOutput);
+
+fn open_device(render: Box<dyn RenderEndpoint>) -> Result<OpenedDevice, String> {
+    let mut device = VirtualAudioDevice::with_config(PIPELINE_DEVICE_NAME, VirtualDeviceConfig::for_format(render.format()));
+    device.initialize()?;
+    let input = device.take_input()?;
+    let output = device.take_output()?;
+    Ok((render, device, input, output))
+}
+
+// Hosts the device until its stream drained or the runner stopped
+fn render_device(
+    mut render: Box<dyn RenderEndpoint>,
+    mut device: VirtualAudioDevice,
+    mut output: VirtualDeviceOutput,
+    stop_requested: &AtomicBool,
+) -> Result<(), String> {
+    let result = render_periods(render.as_mut(), &mut device, &mut output, stop_requested);
+    // A pipeline waiting for room in the device would wait forever otherwise
+    device.stop();
+    result?;
+    render.flush()
+}
+
+fn render_periods(
+    render: &mut dyn RenderEndpoint,
+    device: &mut VirtualAudioDevice,
+    output: &mut VirtualDeviceOutput,
+    stop_requested: &AtomicBool,
+) -> Result<(), String> {
+    let period_frames = device.config().period_frames;
+    let mut buffer = vec![0.0; device.config().buffer_frames * device.config().channels as usize];
+
+    // The device starts with the pipeline's first block; waiting for a whole period of it
+    // keeps the stream from opening with silence
+    while !stop_requested.load(Ordering::SeqCst) {
+        match device.state() {
+            StreamState::Draining => break,
+            StreamState::Running if device.queued_frames() >= period_frames => break,
+            _ => thread::sleep(IDLE_WAIT),
+        }
+    }
+
+    while !stop_requested.load(Ordering::SeqCst) && device.state() != StreamState::Stopped {
+        let due = match render.buffered_frames()? {
+            // Processes a period when one is queued, or silence before the device starves
+            Some(buffered) => {
+                let due = device.state() == StreamState::Draining
+                    || device.queued_frames() >= period_frames
+                    || buffered < period_frames;
+                if due {
+                    device.process_period()?;
+                }
+                due
+            }
+            // Without a clock to keep, periods are processed as they fill up, and never padded
+            None => {
+                let due = device.state() == StreamState::Draining || device.queued_frames() >= period_frames;
+                if due {
+                    device.process_period()?;
+                }
+                due
+            }
+        };
+        if !due {
+            thread::sleep(IDLE_WAIT);
+            continue;
+        }
+
+        loop {
+            let count = output.read(&mut buffer)?;
+            if count == 0 {
+                break;
+            }
+            render.write(&buffer[..count])?;
+        }
+    }
+    Ok(())
+}
+
+// The pipeline's side of the device. The device starts with the first block, so its clock
+// does not run while the capture side is still opening.
+struct DeviceInput {
+    input: VirtualDeviceInput,
+    device: VirtualDeviceHandle,
+    started: bool,
+}
+
+impl RenderEndpoint for DeviceInput {
+    fn format(&self) -> AudioFormat {
+        self.input.format()
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        if !self.started {
+            self.device.start();
+            self.started = true;
+        }
+        self.input.write(samples)
+    }
+}
+
+impl Drop for PipelineRunner {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
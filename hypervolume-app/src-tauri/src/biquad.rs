@@ -0,0 +1,127 @@
+use std::f64::consts::PI;
+
+// Butterworth Q; two of these in a row make a Linkwitz-Riley filter
+pub const BUTTERWORTH_Q: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+// Normalized (a0 = 1) coefficients of a second-order section
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiquadCoefficients {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl BiquadCoefficients {
+    pub const IDENTITY: Self = Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 };
+
+    pub fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Self {
+        Self { b0, b1, b2, a1, a2 }
+    }
+
+    // Low-pass from the RBJ audio EQ cookbook
+    pub fn low_pass(sample_rate: u32, frequency: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, frequency, q);
+        let a0 = 1.0 + alpha;
+        Self::new((1.0 - cos) / 2.0 / a0, (1.0 - cos) / a0, (1.0 - cos) / 2.0 / a0, -2.0 * cos / a0, (1.0 - alpha) / a0)
+    }
+
+    pub fn high_pass(sample_rate: u32, frequency: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, frequency, q);
+        let a0 = 1.0 + alpha;
+        Self::new((1.0 + cos) / 2.0 / a0, -(1.0 + cos) / a0, (1.0 + cos) / 2.0 / a0, -2.0 * cos / a0, (1.0 - alpha) / a0)
+    }
+
+    // Boosts or cuts `gain_db` around `frequency`; `q` sets the width
+    pub fn peaking(sample_rate: u32, frequency: f64, gain_db: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, frequency, q);
+        let a = 10f64.powf(gain_db / 40.0);
+        let a0 = 1.0 + alpha / a;
+        Self::new((1.0 + alpha * a) / a0, -2.0 * cos / a0, (1.0 - alpha * a) / a0, -2.0 * cos / a0, (1.0 - alpha / a) / a0)
+    }
+
+    // Boosts or cuts `gain_db` below `frequency`
+    pub fn low_shelf(sample_rate: u32, frequency: f64, gain_db: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, frequency, q);
+        let a = 10f64.powf(gain_db / 40.0);
+        let beta = 2.0 * a.sqrt() * alpha;
+        let a0 = (a + 1.0) + (a - 1.0) * cos + beta;
+        Self::new(
+            a * ((a + 1.0) - (a - 1.0) * cos + beta) / a0,
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos) / a0,
+            a * ((a + 1.0) - (a - 1.0) * cos - beta) / a0,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos) / a0,
+            ((a + 1.0) + (a - 1.0) * cos - beta) / a0,
+        )
+    }
+
+    // Boosts or cuts `gain_db` above `frequency`
+    pub fn high_shelf(sample_rate: u32, frequency: f64, gain_db: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, frequency, q);
+        let a = 10f64.powf(gain_db / 40.0);
+        let beta = 2.0 * a.sqrt() * alpha;
+        let a0 = (a + 1.0) - (a - 1.0) * cos + beta;
+        Self::new(
+            a * ((a + 1.0) + (a - 1.0) * cos + beta) / a0,
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos) / a0,
+            a * ((a + 1.0) + (a - 1.0) * cos - beta) / a0,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos) / a0,
+            ((a + 1.0) - (a - 1.0) * cos - beta) / a0,
+        )
+    }
+
+    // Removes `frequency`; `q` sets how narrow the cut is
+    pub fn notch(sample_rate: u32, frequency: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, frequency, q);
+        let a0 = 1.0 + alpha;
+        Self::new(1.0 / a0, -2.0 * cos / a0, 1.0 / a0, -2.0 * cos / a0, (1.0 - alpha) / a0)
+    }
+
+    // Gain of the section at `frequency`, in dB; a notch bottoms out at -240 dB
+    pub fn magnitude_db(&self, sample_rate: u32, frequency: f64) -> f64 {
+        let w = 2.0 * PI * frequency / sample_rate.max(1) as f64;
+        let (cos1, sin1, cos2, sin2) = (w.cos(), w.sin(), (2.0 * w).cos(), (2.0 * w).sin());
+        let numerator = (self.b0 + self.b1 * cos1 + self.b2 * cos2).powi(2) + (self.b1 * sin1 + self.b2 * sin2).powi(2);
+        let denominator = (1.0 + self.a1 * cos1 + self.a2 * cos2).powi(2) + (self.a1 * sin1 + self.a2 * sin2).powi(2);
+        10.0 * (numerator / denominator).max(1e-24).log10()
+    }
+
+    // cos(w0) and alpha of the cookbook formulas; the frequency is kept below Nyquist
+    fn prewarp(sample_rate: u32, frequency: f64, q: f64) -> (f64, f64) {
+        let sample_rate = sample_rate.max(1) as f64;
+        let w0 = 2.0 * PI * frequency.clamp(1.0, sample_rate * 0.49) / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * q))
+    }
+}
+
+// Second-order IIR section (transposed direct form II), one per channel
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    coefficients: BiquadCoefficients,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    pub fn new(coefficients: BiquadCoefficients) -> Self {
+        Self { coefficients, z1: 0.0, z2: 0.0 }
+    }
+
+    pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        let c = &self.coefficients;
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
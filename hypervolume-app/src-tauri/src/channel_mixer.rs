@@ -0,0 +1,105 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::audio_manager::{default_channel_layout, ChannelPosition};
+
+// How deep `route` follows fallbacks before it drops a channel the output has no place for
+const MAX_FALLBACK_DEPTH: usize = 4;
+
+// Where a channel the output lacks goes instead, as alternatives in order of preference.
+// Every alternative lists the speakers the channel is split across, with their gains.
+// The fold-downs follow ITU-R BS.775: centre and surrounds join the front pair at -3 dB.
+fn fallbacks(position: ChannelPosition) -> &'static [&'static [(ChannelPosition, f32)]] {
+    use ChannelPosition::*;
+    match position {
+        // Also mono to stereo: equal power on both sides
+        FrontCenter => &[&[(FrontLeft, FRAC_1_SQRT_2), (FrontRight, FRAC_1_SQRT_2)]],
+        // Stereo to mono: the average, so a centred source keeps its level
+        FrontLeft => &[&[(FrontCenter, 0.5)]],
+        FrontRight => &[&[(FrontCenter, 0.5)]],
+        FrontLeftOfCenter => &[&[(FrontLeft, 1.0)]],
+        FrontRightOfCenter => &[&[(FrontRight, 1.0)]],
+        BackLeft => &[&[(SideLeft, 1.0)], &[(FrontLeft, FRAC_1_SQRT_2)]],
+        BackRight => &[&[(SideRight, 1.0)], &[(FrontRight, FRAC_1_SQRT_2)]],
+        SideLeft => &[&[(BackLeft, 1.0)], &[(FrontLeft, FRAC_1_SQRT_2)]],
+        SideRight => &[&[(BackRight, 1.0)], &[(FrontRight, FRAC_1_SQRT_2)]],
+        BackCenter => &[
+            &[(BackLeft, FRAC_1_SQRT_2), (BackRight, FRAC_1_SQRT_2)],
+            &[(SideLeft, FRAC_1_SQRT_2), (SideRight, FRAC_1_SQRT_2)],
+            &[(FrontLeft, 0.5), (FrontRight, 0.5)],
+        ],
+        // The LFE only plays where there is a subwoofer; unnamed channels are dropped
+        LowFrequency | Other => &[],
+    }
+}
+
+// Adds `gain` times the input channel at `position` to the matrix row of every output
+// channel it feeds
+fn route(position: ChannelPosition, gain: f32, outputs: &[ChannelPosition], column: &mut [f32], depth: usize) {
+    if position != ChannelPosition::Other {
+        if let Some(output) = outputs.iter().position(|output| *output == position) {
+            column[output] += gain;
+            return;
+        }
+    }
+    if depth == MAX_FALLBACK_DEPTH {
+        return;
+    }
+
+    let alternatives = fallbacks(position);
+    // The first alternative the output has every speaker of; failing that, the last and
+    // most frontal one, followed further
+    let chosen = alternatives.iter()
+        .find(|speakers| speakers.iter().all(|(speaker, _)| outputs.contains(speaker)))
+        .or(alternatives.last());
+    for (speaker, speaker_gain) in chosen.into_iter().flat_map(|speakers| speakers.iter()) {
+        route(*speaker, gain * speaker_gain, outputs, column, depth + 1);
+    }
+}
+
+// Up- and down-mixes interleaved audio between two speaker layouts with a fixed gain
+// matrix. Channels the output has are copied, the others folded into their neighbours;
+// upmixing leaves the extra speakers silent.
+pub struct ChannelMixer {
+    inputs: usize,
+    outputs: usize,
+    // Row per output channel, column per input channel
+    matrix: Vec<f32>,
+}
+
+impl ChannelMixer {
+    pub fn new(inputs: &[ChannelPosition], outputs: &[ChannelPosition]) -> Self {
+        let mut matrix = vec![0.0; outputs.len() * inputs.len()];
+        let mut column = vec![0.0; outputs.len()];
+        for (input, position) in inputs.iter().enumerate() {
+            column.fill(0.0);
+            route(*position, 1.0, outputs, &mut column, 0);
+            for (output, gain) in column.iter().enumerate() {
+                matrix[output * inputs.len() + input] = *gain;
+            }
+        }
+
+        Self {
+            inputs: inputs.len(),
+            outputs: outputs.len(),
+            matrix,
+        }
+    }
+
+    // Between the layouts Windows assumes for the two channel counts
+    pub fn for_channels(inputs: usize, outputs: usize) -> Self {
+        Self::new(&default_channel_layout(inputs), &default_channel_layout(outputs))
+    }
+
+    // Mixes interleaved `input` into `output`, which must hold as many frames, and returns
+    // the number of samples written
+    pub fn process(&self, input: &[f32], output: &mut [f32]) -> usize {
+        let mut written = 0;
+        for (frame, mixed) in input.chunks_exact(self.inputs).zip(output.chunks_exact_mut(self.outputs)) {
+            for (sample, row) in mixed.iter_mut().zip(self.matrix.chunks_exact(self.inputs)) {
+                *sample = row.iter().zip(frame).map(|(gain, value)| gain * value).sum();
+            }
+            written += self.outputs;
+        }
+        written
+    }
+}
@@ -0,0 +1,276 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::audio_endpoints::AudioFormat;
+use crate::limiter::db_to_linear;
+use crate::simd;
+
+// Half the length, in frames, of the oversampling filters. Upsampling and downsampling each
+// delay the audio by this much.
+const HALF_TAPS: usize = 8;
+// Passband edge of the oversampling filters, as a fraction of the original Nyquist frequency
+const CUTOFF: f64 = 0.9;
+
+pub const OVERSAMPLING_FACTORS: [usize; 3] = [1, 2, 4];
+pub const MIN_CLIP_CEILING_DB: f32 = -20.0;
+pub const MAX_CLIP_CEILING_DB: f32 = 0.0;
+
+// Transfer curve of a clipper, for a ceiling of 1. Every curve is odd, never falls, follows
+// the input with unit slope around zero and stays within -1..=1.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClipCurve {
+    Hard,
+    Tanh,
+    // x - 4/27 x^3, which reaches 1 with zero slope at 1.5
+    Cubic,
+    Arctangent,
+    // Straight up to 1 - knee, then a parabola reaching 1 with zero slope at 1 + knee; a
+    // knee of 0 is a hard clip
+    Polynomial { knee: f32 },
+}
+
+impl ClipCurve {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ClipCurve::Polynomial { knee } if !(0.0..=1.0).contains(knee) => {
+                Err(format!("Knee {} is outside 0..=1", knee))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, x: f32) -> f32 {
+        match *self {
+            ClipCurve::Hard => x.clamp(-1.0, 1.0),
+            ClipCurve::Tanh => x.tanh(),
+            ClipCurve::Cubic => {
+                let x = x.clamp(-1.5, 1.5);
+                x - 4.0 / 27.0 * x * x * x
+            }
+            ClipCurve::Arctangent => (FRAC_PI_2 * x).atan() / FRAC_PI_2,
+            ClipCurve::Polynomial { knee } => {
+                let linear_end = 1.0 - knee;
+                let magnitude = x.abs();
+                let y = if magnitude <= linear_end {
+                    magnitude
+                } else if magnitude < 1.0 + knee {
+                    let over = magnitude - linear_end;
+                    linear_end + over - over * over / (4.0 * knee)
+                } else {
+                    1.0
+                };
+                y.copysign(x)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClipperSettings {
+    pub curve: ClipCurve,
+    // Level the curve saturates towards
+    pub ceiling_db: f32,
+    // 1 (off), 2 or 4; oversampling keeps the harmonics clipping adds from folding back
+    // below the Nyquist frequency as aliasing
+    pub oversampling: usize,
+}
+
+impl Default for ClipperSettings {
+    fn default() -> Self {
+        Self {
+            curve: ClipCurve::Tanh,
+            ceiling_db: 0.0,
+            oversampling: 2,
+        }
+    }
+}
+
+impl ClipperSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        self.curve.validate()?;
+        if !(MIN_CLIP_CEILING_DB..=MAX_CLIP_CEILING_DB).contains(&self.ceiling_db) {
+            return Err(format!(
+                "Clipper ceiling {} dB is outside {}..={} dB",
+                self.ceiling_db, MIN_CLIP_CEILING_DB, MAX_CLIP_CEILING_DB
+            ));
+        }
+        if !OVERSAMPLING_FACTORS.contains(&self.oversampling) {
+            return Err(format!("Unsupported oversampling factor: {}", self.oversampling));
+        }
+        Ok(())
+    }
+}
+
+// Last `len` values pushed, newest first, stored twice in a row so they are always one
+// contiguous slice
+struct History {
+    values: Vec<f32>,
+    len: usize,
+    position: usize,
+}
+
+impl History {
+    fn new(len: usize) -> Self {
+        Self { values: vec![0.0; len * 2], len, position: 0 }
+    }
+
+    fn push(&mut self, value: f32) {
+        self.position = (self.position + self.len - 1) % self.len;
+        self.values[self.position] = value;
+        self.values[self.position + self.len] = value;
+    }
+
+    fn recent(&self) -> &[f32] {
+        &self.values[self.position..self.position + self.len]
+    }
+
+    fn clear(&mut self) {
+        self.values.fill(0.0);
+    }
+}
+
+// Blackman-windowed sinc low-pass at `factor` times the sample rate, 2 * HALF_TAPS * factor
+// + 1 taps long with its centre HALF_TAPS frames in
+struct Oversampler {
+    factor: usize,
+    // Branch p of the upsampling filter, applied to the newest input frames first
+    phases: Vec<Vec<f32>>,
+    // The whole filter again, with unity gain, for downsampling
+    decimation: Vec<f32>,
+}
+
+impl Oversampler {
+    fn new(factor: usize) -> Self {
+        let length = 2 * HALF_TAPS * factor + 1;
+        let prototype: Vec<f64> = (0..length)
+            .map(|index| {
+                let t = (index as f64 - (HALF_TAPS * factor) as f64) / factor as f64;
+                let x = CUTOFF * t;
+                let sinc = if x == 0.0 { 1.0 } else { (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x) };
+                let w = 2.0 * std::f64::consts::PI * index as f64 / (length - 1) as f64;
+                sinc * (0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos())
+            })
+            .collect();
+
+        // Every branch and the downsampling filter have unity gain at DC
+        let phases = (0..factor)
+            .map(|phase| {
+                let taps: Vec<f64> = prototype.iter().skip(phase).step_by(factor).copied().collect();
+                let sum: f64 = taps.iter().sum();
+                taps.iter().map(|tap| (tap / sum) as f32).collect()
+            })
+            .collect();
+        let sum: f64 = prototype.iter().sum();
+        let decimation = prototype.iter().map(|tap| (tap / sum) as f32).collect();
+
+        Self { factor, phases, decimation }
+    }
+}
+
+struct ChannelState {
+    input: History,
+    // Oversampled, clipped signal
+    clipped: History,
+}
+
+// Saturates the audio with a selectable curve, optionally oversampled. The output lags the
+// input by `latency_frames` whatever the settings, also while disabled, so changing them
+// never shifts the stream.
+pub struct Clipper {
+    channels: usize,
+    settings: Option<ClipperSettings>,
+    ceiling: f32,
+    // Filters of every factor above 1, built up front
+    oversamplers: Vec<Oversampler>,
+    states: Vec<ChannelState>,
+}
+
+impl Clipper {
+    pub fn new(format: AudioFormat) -> Self {
+        let oversamplers: Vec<Oversampler> = OVERSAMPLING_FACTORS.iter()
+            .filter(|factor| **factor > 1)
+            .map(|factor| Oversampler::new(*factor))
+            .collect();
+        let max_factor = OVERSAMPLING_FACTORS[OVERSAMPLING_FACTORS.len() - 1];
+
+        Self {
+            channels: format.channels.max(1) as usize,
+            settings: None,
+            ceiling: 1.0,
+            oversamplers,
+            states: (0..format.channels.max(1))
+                .map(|_| ChannelState {
+                    input: History::new(2 * HALF_TAPS + 1),
+                    clipped: History::new(2 * HALF_TAPS * max_factor + max_factor),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn settings(&self) -> Option<ClipperSettings> {
+        self.settings
+    }
+
+    // None only delays the audio
+    pub fn set_settings(&mut self, settings: Option<ClipperSettings>) {
+        let factor = |settings: Option<ClipperSettings>| settings.map_or(1, |settings| settings.oversampling);
+        // The oversampled history of another factor does not fit the new one
+        if factor(settings) != factor(self.settings) {
+            self.states.iter_mut().for_each(|state| state.clipped.clear());
+        }
+        self.settings = settings;
+        self.ceiling = settings.map_or(1.0, |settings| db_to_linear(settings.ceiling_db));
+    }
+
+    pub fn latency_frames(&self) -> usize {
+        2 * HALF_TAPS
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let (curve, factor) = match self.settings {
+            Some(settings) => (Some(settings.curve), settings.oversampling),
+            None => (None, 1),
+        };
+        let ceiling = self.ceiling;
+        let clip = |value: f32| match curve {
+            Some(curve) => ceiling * curve.apply(value / ceiling),
+            None => value,
+        };
+
+        let Some(oversampler) = self.oversamplers.iter().find(|oversampler| oversampler.factor == factor) else {
+            // Delays the whole block first, so the curve runs over it in one go
+            for frame in samples.chunks_mut(self.channels) {
+                for (sample, state) in frame.iter_mut().zip(self.states.iter_mut()) {
+                    state.input.push(*sample);
+                    *sample = state.input.recent()[2 * HALF_TAPS];
+                }
+            }
+            match curve {
+                Some(ClipCurve::Hard) => simd::hard_clip(samples, ceiling),
+                Some(ClipCurve::Cubic) => simd::cubic_clip(samples, ceiling),
+                Some(_) => samples.iter_mut().for_each(|sample| *sample = clip(*sample)),
+                None => {}
+            }
+            return;
+        };
+
+        for frame in samples.chunks_mut(self.channels) {
+            for (sample, state) in frame.iter_mut().zip(self.states.iter_mut()) {
+                state.input.push(*sample);
+                let input = state.input.recent();
+                for taps in &oversampler.phases {
+                    state.clipped.push(clip(simd::dot(taps, input)));
+                }
+                // Lined up with the first branch of the newest frame
+                *sample = simd::dot(&oversampler.decimation, &state.clipped.recent()[factor - 1..]);
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for state in &mut self.states {
+            state.input.clear();
+            state.clipped.clear();
+        }
+    }
+}
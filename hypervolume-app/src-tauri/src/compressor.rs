@@ -0,0 +1,412 @@
+use crate::audio_endpoints::AudioFormat;
+use crate::biquad::{Biquad, BiquadCoefficients, BUTTERWORTH_Q};
+use crate::gain_ramp::{GainRamp, RampSettings};
+use crate::limiter::{db_to_linear, linear_to_db};
+
+// Levels below this (-180 dBFS) are treated as silence by the level detector
+const SILENCE_FLOOR: f32 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CompressorSettings {
+    pub threshold_db: f32,
+    // Input dB above the threshold per output dB above it; 1 leaves the signal alone
+    pub ratio: f32,
+    // Width of the soft knee centred on the threshold; 0 is a hard knee
+    pub knee_db: f32,
+    // Time constants of the gain reduction growing and shrinking
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub makeup_gain_db: f32,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            threshold_db: -18.0,
+            ratio: 4.0,
+            knee_db: 6.0,
+            attack_ms: 10.0,
+            release_ms: 150.0,
+            makeup_gain_db: 0.0,
+        }
+    }
+}
+
+impl CompressorSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let checks = [
+            ("Threshold", self.threshold_db, -60.0, 0.0, "dB"),
+            ("Ratio", self.ratio, 1.0, 100.0, ":1"),
+            ("Knee", self.knee_db, 0.0, 24.0, "dB"),
+            ("Attack", self.attack_ms, 0.1, 500.0, "ms"),
+            ("Release", self.release_ms, 1.0, 5000.0, "ms"),
+            ("Makeup gain", self.makeup_gain_db, 0.0, 24.0, "dB"),
+        ];
+        for (name, value, min, max, unit) in checks {
+            if !(min..=max).contains(&value) {
+                return Err(format!("{} {} is outside {}..={} {}", name, value, min, max, unit));
+            }
+        }
+        Ok(())
+    }
+
+    // Gain reduction, in dB, the static curve applies to a steady `input_db` level
+    pub fn gain_reduction_db(&self, input_db: f32) -> f32 {
+        let over = input_db - self.threshold_db;
+        let slope = 1.0 - 1.0 / self.ratio;
+
+        if 2.0 * over <= -self.knee_db {
+            0.0
+        } else if 2.0 * over < self.knee_db {
+            // Quadratic knee joining both straight parts of the curve smoothly
+            slope * (over + self.knee_db / 2.0).powi(2) / (2.0 * self.knee_db)
+        } else {
+            slope * over
+        }
+    }
+
+    // Output level of a steady `input_db` level, makeup gain included
+    pub fn static_curve_db(&self, input_db: f32) -> f32 {
+        input_db - self.gain_reduction_db(input_db) + self.makeup_gain_db
+    }
+}
+
+pub fn time_coefficient(milliseconds: f32, sample_rate: u32) -> f32 {
+    let frames = milliseconds / 1000.0 * sample_rate.max(1) as f32;
+    if frames > 0.0 { (-1.0 / frames).exp() } else { 0.0 }
+}
+
+// Feed-forward compressor: the frame's peak level goes through the static curve and the
+// resulting gain reduction is smoothed in dB with the attack and release time constants.
+// Channels share one gain.
+pub struct Compressor {
+    settings: CompressorSettings,
+    sample_rate: u32,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    makeup_gain: GainRamp,
+    gain_reduction_db: f32,
+}
+
+impl Compressor {
+    pub fn new(format: AudioFormat, settings: CompressorSettings) -> Self {
+        let mut compressor = Self {
+            settings,
+            sample_rate: format.sample_rate,
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            makeup_gain: GainRamp::new(format, RampSettings::default()),
+            gain_reduction_db: 0.0,
+        };
+        compressor.set_settings(settings);
+        compressor
+    }
+
+    // Keeps the current gain reduction and ramps the makeup gain, so a change does not click
+    pub fn set_settings(&mut self, settings: CompressorSettings) {
+        self.settings = settings;
+        self.attack_coefficient = time_coefficient(settings.attack_ms, self.sample_rate);
+        self.release_coefficient = time_coefficient(settings.release_ms, self.sample_rate);
+        self.makeup_gain.set_target(db_to_linear(settings.makeup_gain_db));
+    }
+
+    #[cfg(test)]
+    pub fn gain_reduction_db(&self) -> f32 {
+        self.gain_reduction_db
+    }
+
+    pub fn process_frame(&mut self, frame: &mut [f32]) {
+        let peak = frame.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let target_db = self.settings.gain_reduction_db(linear_to_db(peak.max(SILENCE_FLOOR)));
+
+        let coefficient = if target_db > self.gain_reduction_db {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.gain_reduction_db = target_db + (self.gain_reduction_db - target_db) * coefficient;
+
+        let gain = db_to_linear(-self.gain_reduction_db) * self.makeup_gain.next_gain();
+        for sample in frame.iter_mut() {
+            *sample *= gain;
+        }
+    }
+
+    // Compresses interleaved samples in place
+    pub fn process(&mut self, samples: &mut [f32], channels: usize) {
+        for frame in samples.chunks_mut(channels.max(1)) {
+            self.process_frame(frame);
+        }
+    }
+
+    // Starts over from the unprocessed signal: no gain reduction, and the makeup gain
+    // ramping up from unity
+    pub fn reset(&mut self) {
+        self.gain_reduction_db = 0.0;
+        self.makeup_gain.reset();
+        self.makeup_gain.set_target(1.0);
+        self.makeup_gain.set_target(db_to_linear(self.settings.makeup_gain_db));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MultibandSettings {
+    // Low/mid and mid/high crossover frequencies
+    pub low_crossover_hz: f32,
+    pub high_crossover_hz: f32,
+    pub low: CompressorSettings,
+    pub mid: CompressorSettings,
+    pub high: CompressorSettings,
+}
+
+impl Default for MultibandSettings {
+    fn default() -> Self {
+        Self {
+            low_crossover_hz: 200.0,
+            high_crossover_hz: 2500.0,
+            low: CompressorSettings::default(),
+            mid: CompressorSettings::default(),
+            high: CompressorSettings::default(),
+        }
+    }
+}
+
+impl MultibandSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(20.0..=20000.0).contains(&self.low_crossover_hz)
+            || !(20.0..=20000.0).contains(&self.high_crossover_hz)
+            || self.low_crossover_hz >= self.high_crossover_hz
+        {
+            return Err(format!(
+                "Invalid crossovers: {} Hz and {} Hz must be rising and within 20..=20000 Hz",
+                self.low_crossover_hz, self.high_crossover_hz
+            ));
+        }
+        self.low.validate()?;
+        self.mid.validate()?;
+        self.high.validate()
+    }
+}
+
+// 4th-order Linkwitz-Riley crossover: two Butterworth sections per side. Low and high
+// are in phase at every frequency and sum to an allpass, so the bands add up flat.
+#[derive(Debug, Clone, Copy)]
+struct LinkwitzRiley {
+    low: [Biquad; 2],
+    high: [Biquad; 2],
+}
+
+impl LinkwitzRiley {
+    fn new(sample_rate: u32, frequency: f32) -> Self {
+        let low = Biquad::new(BiquadCoefficients::low_pass(sample_rate, frequency as f64, BUTTERWORTH_Q));
+        let high = Biquad::new(BiquadCoefficients::high_pass(sample_rate, frequency as f64, BUTTERWORTH_Q));
+        Self { low: [low; 2], high: [high; 2] }
+    }
+
+    fn set_frequency(&mut self, sample_rate: u32, frequency: f32) {
+        let low = BiquadCoefficients::low_pass(sample_rate, frequency as f64, BUTTERWORTH_Q);
+        let high = BiquadCoefficients::high_pass(sample_rate, frequency as f64, BUTTERWORTH_Q);
+        self.low.iter_mut().for_each(|section| section.set_coefficients(low));
+        self.high.iter_mut().for_each(|section| section.set_coefficients(high));
+    }
+
+    fn split(&mut self, input: f64) -> (f64, f64) {
+        let low = self.low[0].process(input);
+        let high = self.high[0].process(input);
+        (self.low[1].process(low), self.high[1].process(high))
+    }
+
+    fn reset(&mut self) {
+        self.low.iter_mut().chain(self.high.iter_mut()).for_each(Biquad::reset);
+    }
+}
+
+// Per-channel filters of the 3-band split. The low band also passes the high crossover's
+// allpass, so it stays in phase with the other two.
+#[derive(Debug, Clone, Copy)]
+struct BandSplitter {
+    low_crossover: LinkwitzRiley,
+    high_crossover: LinkwitzRiley,
+    low_phase: LinkwitzRiley,
+}
+
+impl BandSplitter {
+    fn new(sample_rate: u32, settings: &MultibandSettings) -> Self {
+        Self {
+            low_crossover: LinkwitzRiley::new(sample_rate, settings.low_crossover_hz),
+            high_crossover: LinkwitzRiley::new(sample_rate, settings.high_crossover_hz),
+            low_phase: LinkwitzRiley::new(sample_rate, settings.high_crossover_hz),
+        }
+    }
+
+    fn set_frequencies(&mut self, sample_rate: u32, settings: &MultibandSettings) {
+        self.low_crossover.set_frequency(sample_rate, settings.low_crossover_hz);
+        self.high_crossover.set_frequency(sample_rate, settings.high_crossover_hz);
+        self.low_phase.set_frequency(sample_rate, settings.high_crossover_hz);
+    }
+
+    fn split(&mut self, input: f64) -> [f64; 3] {
+        let (low, upper) = self.low_crossover.split(input);
+        let (low_low, low_high) = self.low_phase.split(low);
+        let (mid, high) = self.high_crossover.split(upper);
+        [low_low + low_high, mid, high]
+    }
+
+    fn reset(&mut self) {
+        self.low_crossover.reset();
+        self.high_crossover.reset();
+        self.low_phase.reset();
+    }
+}
+
+// Splits the signal into low, mid and high bands, compresses each on its own and sums
+// them back.
+pub struct MultibandCompressor {
+    settings: MultibandSettings,
+    sample_rate: u32,
+    channels: usize,
+    splitters: Vec<BandSplitter>,
+    bands: [Compressor; 3],
+    // One frame of every band
+    band_frames: Vec<f32>,
+}
+
+impl MultibandCompressor {
+    pub fn new(format: AudioFormat, settings: MultibandSettings) -> Self {
+        let channels = format.channels.max(1) as usize;
+        Self {
+            settings,
+            sample_rate: format.sample_rate,
+            channels,
+            splitters: vec![BandSplitter::new(format.sample_rate, &settings); channels],
+            bands: [
+                Compressor::new(format, settings.low),
+                Compressor::new(format, settings.mid),
+                Compressor::new(format, settings.high),
+            ],
+            band_frames: vec![0.0; channels * 3],
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: MultibandSettings) {
+        if settings.low_crossover_hz != self.settings.low_crossover_hz
+            || settings.high_crossover_hz != self.settings.high_crossover_hz
+        {
+            for splitter in &mut self.splitters {
+                splitter.set_frequencies(self.sample_rate, &settings);
+            }
+        }
+        self.bands[0].set_settings(settings.low);
+        self.bands[1].set_settings(settings.mid);
+        self.bands[2].set_settings(settings.high);
+        self.settings = settings;
+    }
+
+    // Gain reduction of the low, mid and high band
+    #[cfg(test)]
+    pub fn gain_reduction_db(&self) -> [f32; 3] {
+        [self.bands[0].gain_reduction_db(), self.bands[1].gain_reduction_db(), self.bands[2].gain_reduction_db()]
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let channels = self.channels;
+        for frame in samples.chunks_mut(channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let bands = self.splitters[channel].split(*sample as f64);
+                for (band, value) in bands.iter().enumerate() {
+                    self.band_frames[band * channels + channel] = *value as f32;
+                }
+            }
+
+            let frame_len = frame.len();
+            for (band, compressor) in self.bands.iter_mut().enumerate() {
+                compressor.process_frame(&mut self.band_frames[band * channels..band * channels + frame_len]);
+            }
+
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                *sample = (0..3).map(|band| self.band_frames[band * channels + channel]).sum();
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.splitters.iter_mut().for_each(BandSplitter::reset);
+        self.bands.iter_mut().for_each(Compressor::reset);
+    }
+}
+
+// Dynamics processing a session uses before its limiter
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DynamicsMode {
+    #[default]
+    Off,
+    Compressor(CompressorSettings),
+    Multiband(MultibandSettings),
+}
+
+impl DynamicsMode {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            DynamicsMode::Off => Ok(()),
+            DynamicsMode::Compressor(settings) => settings.validate(),
+            DynamicsMode::Multiband(settings) => settings.validate(),
+        }
+    }
+}
+
+// Runs whichever processor the mode selects. Both are built up front, so switching modes
+// on the audio thread does not allocate.
+pub struct Dynamics {
+    mode: DynamicsMode,
+    channels: usize,
+    compressor: Compressor,
+    multiband: MultibandCompressor,
+}
+
+impl Dynamics {
+    pub fn new(format: AudioFormat) -> Self {
+        Self {
+            mode: DynamicsMode::Off,
+            channels: format.channels.max(1) as usize,
+            compressor: Compressor::new(format, CompressorSettings::default()),
+            multiband: MultibandCompressor::new(format, MultibandSettings::default()),
+        }
+    }
+
+    pub fn mode(&self) -> DynamicsMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: DynamicsMode) {
+        match mode {
+            DynamicsMode::Off => {}
+            DynamicsMode::Compressor(settings) => {
+                if !matches!(self.mode, DynamicsMode::Compressor(_)) {
+                    self.compressor.reset();
+                }
+                self.compressor.set_settings(settings);
+            }
+            DynamicsMode::Multiband(settings) => {
+                if !matches!(self.mode, DynamicsMode::Multiband(_)) {
+                    self.multiband.reset();
+                }
+                self.multiband.set_settings(settings);
+            }
+        }
+        self.mode = mode;
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        match self.mode {
+            DynamicsMode::Off => {}
+            DynamicsMode::Compressor(_) => self.compressor.process(samples, self.channels),
+            DynamicsMode::Multiband(_) => self.multiband.process(samples),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.compressor.reset();
+        self.multiband.reset();
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::audio_manager::{AudioBackend, PeakLevels};
+use crate::limiter::db_to_linear;
+
+// How often the monitor reads the session peak meters; fine enough for a 50 ms attack
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// A session volume further than this from the one the ducker set was changed by the user
+const VOLUME_TOLERANCE: f32 = 1e-4;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DuckingSettings {
+    // Sessions whose playback ducks the others, typically voice chat
+    pub trigger_sessions: Vec<String>,
+    // Sessions turned down while a trigger plays
+    pub ducked_sessions: Vec<String>,
+    // How far the ducked sessions are turned down
+    pub attenuation_db: f32,
+    // Peak level, in dBFS, from which a trigger session counts as playing
+    pub threshold_db: f32,
+    // Time to fade all the way down
+    pub attack_ms: f32,
+    // How long the ducking lasts after the last trigger fell silent, so pauses between
+    // words do not let the music swell
+    pub hold_ms: f32,
+    // Time to fade back up once the hold is over
+    pub release_ms: f32,
+}
+
+impl Default for DuckingSettings {
+    fn default() -> Self {
+        Self {
+            trigger_sessions: Vec::new(),
+            ducked_sessions: Vec::new(),
+            attenuation_db: 15.0,
+            threshold_db: -45.0,
+            attack_ms: 100.0,
+            hold_ms: 800.0,
+            release_ms: 1000.0,
+        }
+    }
+}
+
+impl DuckingSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let checks = [
+            ("Attenuation", self.attenuation_db, 0.0, 60.0, "dB"),
+            ("Threshold", self.threshold_db, -90.0, 0.0, "dBFS"),
+            ("Attack", self.attack_ms, 0.0, 5000.0, "ms"),
+            ("Hold", self.hold_ms, 0.0, 10000.0, "ms"),
+            ("Release", self.release_ms, 0.0, 10000.0, "ms"),
+        ];
+        for (name, value, min, max, unit) in checks {
+            if !(min..=max).contains(&value) {
+                return Err(format!("{} {} is outside {}..={} {}", name, value, min, max, unit));
+            }
+        }
+        if let Some(session_id) = self.trigger_sessions.iter().find(|id| self.ducked_sessions.contains(id)) {
+            return Err(format!("Session {} cannot both trigger ducking and be ducked", session_id));
+        }
+        Ok(())
+    }
+}
+
+struct DuckedSession {
+    // Volume the user set, restored once the ducking ends
+    volume: f32,
+    // Volume the ducker last set
+    applied: f32,
+}
+
+// Turns the ducked sessions down while any trigger session plays above the threshold.
+// The attenuation fades in dB at the attack and release rates and is applied through the
+// session volumes; volumes the user changes meanwhile become the ones restored. Time only
+// advances through the `elapsed` arguments, like with the level meters.
+pub struct Ducker {
+    settings: DuckingSettings,
+    attenuation_db: f32,
+    hold_remaining: Duration,
+    // Sessions currently turned down, by session ID
+    ducked: HashMap<String, DuckedSession>,
+}
+
+impl Ducker {
+    pub fn new(settings: DuckingSettings) -> Result<Self, String> {
+        settings.validate()?;
+        Ok(Self {
+            settings,
+            attenuation_db: 0.0,
+            hold_remaining: Duration::ZERO,
+            ducked: HashMap::new(),
+        })
+    }
+
+    pub fn settings(&self) -> &DuckingSettings {
+        &self.settings
+    }
+
+    // Sessions no longer ducked get their volume back right away; the others follow the
+    // new settings from the next update
+    pub fn set_settings(&mut self, backend: &dyn AudioBackend, settings: DuckingSettings) -> Result<(), String> {
+        settings.validate()?;
+        let dropped: Vec<String> = self.ducked.keys()
+            .filter(|session_id| !settings.ducked_sessions.contains(session_id))
+            .cloned()
+            .collect();
+        self.settings = settings;
+        collect_errors(dropped.iter().map(|session_id| self.restore(backend, session_id)))
+    }
+
+    // Current attenuation of the ducked sessions, in dB
+    pub fn attenuation_db(&self) -> f32 {
+        self.attenuation_db
+    }
+
+    // Feeds one reading of the session peak meters, taken `elapsed` after the previous one,
+    // and sets the volumes of the ducked sessions. A session that fails does not stop the
+    // others.
+    pub fn update(&mut self, backend: &dyn AudioBackend, levels: &PeakLevels, elapsed: Duration) -> Result<(), String> {
+        let threshold = db_to_linear(self.settings.threshold_db);
+        let triggered = self.settings.trigger_sessions.iter()
+            .any(|session_id| levels.sessions.get(session_id).is_some_and(|peak| *peak >= threshold));
+
+        let depth = self.settings.attenuation_db;
+        if triggered {
+            self.hold_remaining = Duration::from_secs_f32(self.settings.hold_ms / 1000.0);
+            self.fade(depth, elapsed);
+        } else if elapsed < self.hold_remaining {
+            self.hold_remaining -= elapsed;
+            self.fade(depth, elapsed);
+        } else {
+            // Only the part of `elapsed` past the end of the hold time releases
+            let held = std::mem::take(&mut self.hold_remaining);
+            self.fade(depth, held);
+            self.fade(0.0, elapsed - held);
+        }
+
+        // Sessions that are gone take their state with them
+        self.ducked.retain(|session_id, _| levels.sessions.contains_key(session_id));
+
+        // Sessions taken off the ducked ones whose volume could not be restored yet
+        let dropped: Vec<String> = self.ducked.keys()
+            .filter(|session_id| !self.settings.ducked_sessions.contains(session_id))
+            .cloned()
+            .collect();
+        let present: Vec<String> = self.settings.ducked_sessions.iter()
+            .filter(|session_id| levels.sessions.contains_key(*session_id))
+            .cloned()
+            .collect();
+        let gain = db_to_linear(-self.attenuation_db);
+        let mut results = Vec::new();
+        for session_id in &dropped {
+            results.push(self.restore(backend, session_id));
+        }
+        for session_id in &present {
+            results.push(if self.attenuation_db > 0.0 {
+                self.duck(backend, session_id, gain)
+            } else {
+                self.restore(backend, session_id)
+            });
+        }
+        collect_errors(results.into_iter())
+    }
+
+    // Gives every ducked session its volume back at once, as when ducking is turned off
+    pub fn release(&mut self, backend: &dyn AudioBackend) -> Result<(), String> {
+        self.attenuation_db = 0.0;
+        self.hold_remaining = Duration::ZERO;
+        let session_ids: Vec<String> = self.ducked.keys().cloned().collect();
+        collect_errors(session_ids.iter().map(|session_id| self.restore(backend, session_id)))
+    }
+
+    // Moves the attenuation toward `target_db`; attack and release are the times a fade
+    // over the full depth takes
+    fn fade(&mut self, target_db: f32, time: Duration) {
+        let duration_ms = if target_db > self.attenuation_db {
+            self.settings.attack_ms
+        } else {
+            self.settings.release_ms
+        };
+        if duration_ms <= 0.0 || self.settings.attenuation_db <= 0.0 {
+            self.attenuation_db = target_db;
+            return;
+        }
+
+        let step = self.settings.attenuation_db * time.as_secs_f32() * 1000.0 / duration_ms;
+        self.attenuation_db = if target_db > self.attenuation_db {
+            (self.attenuation_db + step).min(target_db)
+        } else {
+            (self.attenuation_db - step).max(target_db)
+        };
+    }
+
+    fn duck(&mut self, backend: &dyn AudioBackend, session_id: &str, gain: f32) -> Result<(), String> {
+        let current = backend.get_session_volume(session_id)?;
+        let session = self.ducked.entry(session_id.to_string())
+            .or_insert(DuckedSession { volume: current, applied: current });
+        if (current - session.applied).abs() > VOLUME_TOLERANCE {
+            session.volume = current;
+        }
+
+        let volume = session.volume * gain;
+        if volume != current {
+            backend.set_session_volume(session_id, volume)?;
+        }
+        session.applied = volume;
+        Ok(())
+    }
+
+    fn restore(&mut self, backend: &dyn AudioBackend, session_id: &str) -> Result<(), String> {
+        let Some(session) = self.ducked.get(session_id) else {
+            return Ok(());
+        };
+        let current = backend.get_session_volume(session_id)?;
+        let volume = if (current - session.applied).abs() > VOLUME_TOLERANCE { current } else { session.volume };
+        // Kept until it worked, so the next update tries again
+        backend.set_session_volume(session_id, volume)?;
+        self.ducked.remove(session_id);
+        Ok(())
+    }
+}
+
+fn collect_errors(results: impl Iterator<Item = Result<(), String>>) -> Result<(), String> {
+    let errors: Vec<String> = results.filter_map(Result::err).collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+// Runs a ducker on a background thread fed by the backend peak meters. Stopping it gives
+// the ducked sessions their volume back.
+pub struct DuckingMonitor {
+    backend: Arc<dyn AudioBackend>,
+    ducker: Arc<Mutex<Ducker>>,
+    stop_requested: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DuckingMonitor {
+    pub fn start(backend: Arc<dyn AudioBackend>, settings: DuckingSettings) -> Result<Self, String> {
+        let ducker = Arc::new(Mutex::new(Ducker::new(settings)?));
+        // A backend without session meters could never trigger the ducking
+        backend.get_peak_levels()?;
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let thread_backend = Arc::clone(&backend);
+        let thread_ducker = Arc::clone(&ducker);
+        let thread_stop_requested = Arc::clone(&stop_requested);
+        let thread = thread::spawn(move || {
+            let mut last_poll = Instant::now();
+
+            while !thread_stop_requested.load(Ordering::SeqCst) {
+                // A failed read skips one update; a session that cannot be set is tried
+                // again with the next one
+                if let Ok(levels) = thread_backend.get_peak_levels() {
+                    let now = Instant::now();
+                    if let Ok(mut ducker) = thread_ducker.lock() {
+                        let _ = ducker.update(thread_backend.as_ref(), &levels, now - last_poll);
+                    }
+                    last_poll = now;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        Ok(Self {
+            backend,
+            ducker,
+            stop_requested,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn settings(&self) -> DuckingSettings {
+        self.ducker.lock().map(|ducker| ducker.settings().clone()).unwrap_or_default()
+    }
+
+    pub fn set_settings(&self, settings: DuckingSettings) -> Result<(), String> {
+        let mut ducker = self.ducker.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        ducker.set_settings(self.backend.as_ref(), settings)
+    }
+
+    pub fn attenuation_db(&self) -> f32 {
+        self.ducker.lock().map(|ducker| ducker.attenuation_db()).unwrap_or(0.0)
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+            if let Ok(mut ducker) = self.ducker.lock() {
+                let _ = ducker.release(self.backend.as_ref());
+            }
+        }
+    }
+}
+
+impl Drop for DuckingMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
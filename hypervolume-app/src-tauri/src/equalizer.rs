@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::audio_endpoints::AudioFormat;
+use crate::biquad::{Biquad, BiquadCoefficients};
+use crate::limiter::db_to_linear;
+
+// Bands one preset may have; equalizers preallocate this many filters per channel
+pub const MAX_EQ_BANDS: usize = 16;
+
+pub const MIN_EQ_FREQUENCY_HZ: f32 = 20.0;
+pub const MAX_EQ_FREQUENCY_HZ: f32 = 20000.0;
+pub const MAX_EQ_GAIN_DB: f32 = 24.0;
+pub const MIN_EQ_Q: f32 = 0.1;
+pub const MAX_EQ_Q: f32 = 20.0;
+
+// Sample rate frequency responses are computed at; below 20 kHz the curve barely depends
+// on it
+pub const RESPONSE_SAMPLE_RATE: u32 = 48000;
+
+// A preset change fades from the old filters to the new ones over this long, so moving a
+// band while audio plays does not click
+const CROSSFADE: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterType {
+    Peaking,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+    Notch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EqBand {
+    pub filter: FilterType,
+    pub frequency_hz: f32,
+    // Ignored by the pass and notch filters
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+impl EqBand {
+    pub fn validate(&self) -> Result<(), String> {
+        let checks = [
+            ("Frequency", self.frequency_hz, MIN_EQ_FREQUENCY_HZ, MAX_EQ_FREQUENCY_HZ, "Hz"),
+            ("Gain", self.gain_db, -MAX_EQ_GAIN_DB, MAX_EQ_GAIN_DB, "dB"),
+            ("Q", self.q, MIN_EQ_Q, MAX_EQ_Q, ""),
+        ];
+        for (name, value, min, max, unit) in checks {
+            if !(min..=max).contains(&value) {
+                return Err(format!("{} {} is outside {}..={} {}", name, value, min, max, unit));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn coefficients(&self, sample_rate: u32) -> BiquadCoefficients {
+        let (frequency, gain_db, q) = (self.frequency_hz as f64, self.gain_db as f64, self.q as f64);
+        match self.filter {
+            FilterType::Peaking => BiquadCoefficients::peaking(sample_rate, frequency, gain_db, q),
+            FilterType::LowShelf => BiquadCoefficients::low_shelf(sample_rate, frequency, gain_db, q),
+            FilterType::HighShelf => BiquadCoefficients::high_shelf(sample_rate, frequency, gain_db, q),
+            FilterType::LowPass => BiquadCoefficients::low_pass(sample_rate, frequency, q),
+            FilterType::HighPass => BiquadCoefficients::high_pass(sample_rate, frequency, q),
+            FilterType::Notch => BiquadCoefficients::notch(sample_rate, frequency, q),
+        }
+    }
+}
+
+// One point of an equalizer curve, for the UI to plot
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct FrequencyResponsePoint {
+    pub frequency_hz: f32,
+    pub gain_db: f32,
+}
+
+// Parametric equalizer settings of an application or an output device. The default has no
+// bands and leaves the audio untouched.
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct EqPreset {
+    // Gain applied before the bands, to make room for boosts
+    pub preamp_db: f32,
+    pub bands: Vec<EqBand>,
+}
+
+impl EqPreset {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-MAX_EQ_GAIN_DB..=MAX_EQ_GAIN_DB).contains(&self.preamp_db) {
+            return Err(format!("Preamp {} dB is outside {}..={} dB", self.preamp_db, -MAX_EQ_GAIN_DB, MAX_EQ_GAIN_DB));
+        }
+        if self.bands.len() > MAX_EQ_BANDS {
+            return Err(format!("{} bands exceed the maximum of {}", self.bands.len(), MAX_EQ_BANDS));
+        }
+        for (index, band) in self.bands.iter().enumerate() {
+            band.validate().map_err(|e| format!("Band {}: {}", index + 1, e))?;
+        }
+        Ok(())
+    }
+
+    // Gain of the whole preset at `frequency_hz`, in dB
+    pub fn gain_db(&self, sample_rate: u32, frequency_hz: f32) -> f32 {
+        let bands: f64 = self.bands.iter()
+            .map(|band| band.coefficients(sample_rate).magnitude_db(sample_rate, frequency_hz as f64))
+            .sum();
+        (self.preamp_db as f64 + bands) as f32
+    }
+
+    // Gain at `points` frequencies spaced logarithmically from 20 Hz to 20 kHz
+    pub fn frequency_response(&self, sample_rate: u32, points: usize) -> Vec<FrequencyResponsePoint> {
+        let octaves = (MAX_EQ_FREQUENCY_HZ / MIN_EQ_FREQUENCY_HZ).log2();
+        (0..points)
+            .map(|point| {
+                let position = point as f32 / (points.max(2) - 1) as f32;
+                let frequency_hz = MIN_EQ_FREQUENCY_HZ * (octaves * position).exp2();
+                FrequencyResponsePoint { frequency_hz, gain_db: self.gain_db(sample_rate, frequency_hz) }
+            })
+            .collect()
+    }
+
+    fn is_flat(&self) -> bool {
+        self.preamp_db == 0.0 && self.bands.is_empty()
+    }
+}
+
+// Presets by application (see app_groups::app_key) and by output device ID, as saved
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EqPresets {
+    #[serde(default)]
+    pub apps: HashMap<String, EqPreset>,
+    #[serde(default)]
+    pub devices: HashMap<String, EqPreset>,
+}
+
+// Keeps the EQ presets across runs, written to `path` on every change. Without a path they
+// only live in memory.
+#[derive(Debug, Default)]
+pub struct EqPresetStore {
+    presets: EqPresets,
+    path: Option<PathBuf>,
+}
+
+// Next to the update settings
+pub fn eq_presets_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| std::env::current_dir().unwrap())
+        .join("HyperVolume")
+        .join("eq_presets.json")
+}
+
+impl EqPresetStore {
+    // A missing or unreadable file starts out empty; invalid presets in it are dropped
+    pub fn load(path: PathBuf) -> Self {
+        let mut presets: EqPresets = fs::read_to_string(&path)
+            .ok()
+            .and_then(|presets| serde_json::from_str(&presets).ok())
+            .unwrap_or_default();
+        presets.apps.retain(|_, preset| preset.validate().is_ok());
+        presets.devices.retain(|_, preset| preset.validate().is_ok());
+        Self { presets, path: Some(path) }
+    }
+
+    pub fn app(&self, app_key: &str) -> EqPreset {
+        self.presets.apps.get(app_key).cloned().unwrap_or_default()
+    }
+
+    pub fn device(&self, device_id: &str) -> EqPreset {
+        self.presets.devices.get(device_id).cloned().unwrap_or_default()
+    }
+
+    // A flat preset removes the entry
+    pub fn set_app(&mut self, app_key: &str, preset: EqPreset) -> Result<(), String> {
+        store_preset(&mut self.presets.apps, app_key, preset);
+        self.save()
+    }
+
+    pub fn set_device(&mut self, device_id: &str, preset: EqPreset) -> Result<(), String> {
+        store_preset(&mut self.presets.devices, device_id, preset);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        let presets = serde_json::to_string_pretty(&self.presets)
+            .map_err(|e| format!("Failed to serialize EQ presets: {}", e))?;
+        fs::write(path, presets).map_err(|e| format!("Failed to write EQ presets: {}", e))
+    }
+}
+
+fn store_preset(presets: &mut HashMap<String, EqPreset>, key: &str, preset: EqPreset) {
+    if preset.is_flat() {
+        presets.remove(key);
+    } else {
+        presets.insert(key.to_string(), preset);
+    }
+}
+
+#[derive(Debug)]
+struct SharedEq {
+    // The audio thread only try-locks this after `version` moved, so setting a preset
+    // never blocks it
+    preset: Mutex<EqPreset>,
+    version: AtomicU32,
+}
+
+// Preset shared between the manager and the equalizers running it
+#[derive(Debug, Clone)]
+pub struct EqControl(Arc<SharedEq>);
+
+impl EqControl {
+    pub fn new(preset: EqPreset) -> Self {
+        // Equalizers start at version 0, so they load the preset on their first block
+        Self(Arc::new(SharedEq { preset: Mutex::new(preset), version: AtomicU32::new(1) }))
+    }
+
+    pub fn preset(&self) -> EqPreset {
+        self.0.preset.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn set_preset(&self, preset: EqPreset) {
+        *self.0.preset.lock().unwrap_or_else(|e| e.into_inner()) = preset;
+        self.0.version.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl Default for EqControl {
+    fn default() -> Self {
+        Self::new(EqPreset::default())
+    }
+}
+
+// Cascade of up to MAX_EQ_BANDS biquads per channel. Loading a preset only recomputes
+// coefficients into the preallocated filters, and the output crossfades from the previous
+// filters to the new ones, so presets can change on the audio thread.
+pub struct Equalizer {
+    channels: usize,
+    sample_rate: u32,
+    band_count: usize,
+    preamp: f32,
+    // MAX_EQ_BANDS filters per channel, channel after channel
+    filters: Vec<Biquad>,
+    // The filters before the last preset change, faded out over `fade_frames`
+    previous: Vec<Biquad>,
+    previous_band_count: usize,
+    previous_preamp: f32,
+    fade_frames: usize,
+    fade_remaining: usize,
+    // Version of the control's preset the filters run
+    version: u32,
+}
+
+impl Equalizer {
+    pub fn new(format: AudioFormat) -> Self {
+        let channels = format.channels.max(1) as usize;
+        let filters = vec![Biquad::new(BiquadCoefficients::IDENTITY); channels * MAX_EQ_BANDS];
+        Self {
+            channels,
+            sample_rate: format.sample_rate,
+            band_count: 0,
+            preamp: 1.0,
+            previous: filters.clone(),
+            filters,
+            previous_band_count: 0,
+            previous_preamp: 1.0,
+            fade_frames: ((CROSSFADE.as_secs_f64() * format.sample_rate as f64) as usize).max(1),
+            fade_remaining: 0,
+            version: 0,
+        }
+    }
+
+    // Loads the preset's coefficients; bands past MAX_EQ_BANDS are ignored
+    pub fn set_preset(&mut self, preset: &EqPreset) {
+        let band_count = preset.bands.len().min(MAX_EQ_BANDS);
+        let preamp = db_to_linear(preset.preamp_db);
+        let was_flat = self.band_count == 0 && self.preamp == 1.0;
+
+        self.previous.copy_from_slice(&self.filters);
+        self.previous_band_count = self.band_count;
+        self.previous_preamp = self.preamp;
+
+        for channel in 0..self.channels {
+            let filters = &mut self.filters[channel * MAX_EQ_BANDS..(channel + 1) * MAX_EQ_BANDS];
+            for (band, filter) in preset.bands.iter().zip(filters.iter_mut()) {
+                filter.set_coefficients(band.coefficients(self.sample_rate));
+            }
+            // Filters that were idle hold stale state
+            for filter in &mut filters[self.band_count.min(band_count)..band_count] {
+                filter.reset();
+            }
+        }
+        self.band_count = band_count;
+        self.preamp = preamp;
+
+        self.fade_remaining = if was_flat && preset.is_flat() { 0 } else { self.fade_frames };
+    }
+
+    // Loads the control's preset if it changed since the last call; never blocks. A change
+    // waits for the running crossfade to finish, so a dragged slider never cuts one short.
+    pub fn sync(&mut self, control: &EqControl) {
+        let version = control.0.version.load(Ordering::Acquire);
+        if version == self.version || self.fade_remaining > 0 {
+            return;
+        }
+        let Ok(preset) = control.0.preset.try_lock() else {
+            return;
+        };
+        self.set_preset(&preset);
+        // The first preset applies from the first sample on
+        if self.version == 0 {
+            self.fade_remaining = 0;
+        }
+        self.version = version;
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if self.band_count == 0 && self.preamp == 1.0 && self.fade_remaining == 0 {
+            return;
+        }
+
+        for frame in samples.chunks_mut(self.channels) {
+            let fade = self.fade_remaining as f64 / self.fade_frames as f64;
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let filters = channel * MAX_EQ_BANDS..channel * MAX_EQ_BANDS + self.band_count;
+                let output = self.filters[filters].iter_mut()
+                    .fold(*sample as f64 * self.preamp as f64, |value, filter| filter.process(value));
+
+                *sample = if self.fade_remaining > 0 {
+                    let filters = channel * MAX_EQ_BANDS..channel * MAX_EQ_BANDS + self.previous_band_count;
+                    let previous = self.previous[filters].iter_mut()
+                        .fold(*sample as f64 * self.previous_preamp as f64, |value, filter| filter.process(value));
+                    (output + (previous - output) * fade) as f32
+                } else {
+                    output as f32
+                };
+            }
+            self.fade_remaining = self.fade_remaining.saturating_sub(1);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(Biquad::reset);
+        self.fade_remaining = 0;
+    }
+}
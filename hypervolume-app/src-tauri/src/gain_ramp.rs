@@ -0,0 +1,177 @@
+use crate::audio_endpoints::AudioFormat;
+use crate::simd;
+
+pub const DEFAULT_RAMP_MS: f32 = 20.0;
+pub const MAX_RAMP_MS: f32 = 1000.0;
+
+// Exponential ramps start from and head to no less than this (-100 dB); a ramp to silence
+// reaches it with its last frame
+const EXPONENTIAL_FLOOR: f32 = 1e-5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RampShape {
+    // Straight line in amplitude
+    Linear,
+    // Straight line in dB, which sounds even over a wide range of gains
+    Exponential,
+}
+
+impl RampShape {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => RampShape::Linear,
+            _ => RampShape::Exponential,
+        }
+    }
+}
+
+// How gain changes are spread over time; a zero duration applies them at once
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RampSettings {
+    pub shape: RampShape,
+    pub duration_ms: f32,
+}
+
+impl Default for RampSettings {
+    fn default() -> Self {
+        Self {
+            shape: RampShape::Exponential,
+            duration_ms: DEFAULT_RAMP_MS,
+        }
+    }
+}
+
+impl RampSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=MAX_RAMP_MS).contains(&self.duration_ms) {
+            return Err(format!("Ramp duration {} ms is outside 0..={} ms", self.duration_ms, MAX_RAMP_MS));
+        }
+        Ok(())
+    }
+}
+
+// Gain that moves to a new target frame by frame instead of jumping, so changing it while
+// audio plays causes no zipper noise. A change in the middle of a ramp starts a new ramp
+// from wherever the gain is. The first target applies at once, because there is no earlier
+// audio to be continuous with.
+#[derive(Debug, Clone)]
+pub struct GainRamp {
+    channels: usize,
+    sample_rate: u32,
+    settings: RampSettings,
+    gain: f32,
+    target: f32,
+    // Added to (linear) or multiplied into (exponential) the gain every frame
+    step: f32,
+    remaining_frames: usize,
+    started: bool,
+}
+
+impl GainRamp {
+    pub fn new(format: AudioFormat, settings: RampSettings) -> Self {
+        Self {
+            channels: format.channels.max(1) as usize,
+            sample_rate: format.sample_rate,
+            settings,
+            gain: 1.0,
+            target: 1.0,
+            step: 0.0,
+            remaining_frames: 0,
+            started: false,
+        }
+    }
+
+    pub fn settings(&self) -> RampSettings {
+        self.settings
+    }
+
+    // Used from the next change on; a running ramp finishes as it started
+    pub fn set_settings(&mut self, settings: RampSettings) {
+        self.settings = settings;
+    }
+
+    // Gain of the last frame processed
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn is_ramping(&self) -> bool {
+        self.remaining_frames > 0
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        if !self.started {
+            self.started = true;
+            self.jump_to(target);
+            return;
+        }
+        if target == self.target {
+            return;
+        }
+
+        let frames = (self.settings.duration_ms / 1000.0 * self.sample_rate as f32).round() as usize;
+        if frames == 0 {
+            self.jump_to(target);
+            return;
+        }
+
+        self.target = target;
+        self.remaining_frames = frames;
+        self.step = match self.settings.shape {
+            RampShape::Linear => (target - self.gain) / frames as f32,
+            RampShape::Exponential => {
+                self.gain = self.gain.max(EXPONENTIAL_FLOOR);
+                (target.max(EXPONENTIAL_FLOOR) / self.gain).powf(1.0 / frames as f32)
+            }
+        };
+    }
+
+    fn jump_to(&mut self, gain: f32) {
+        self.gain = gain;
+        self.target = gain;
+        self.remaining_frames = 0;
+    }
+
+    // Advances the ramp by one frame and returns that frame's gain
+    pub fn next_gain(&mut self) -> f32 {
+        if self.remaining_frames > 0 {
+            self.remaining_frames -= 1;
+            self.gain = if self.remaining_frames == 0 {
+                self.target
+            } else {
+                match self.settings.shape {
+                    RampShape::Linear => self.gain + self.step,
+                    RampShape::Exponential => self.gain * self.step,
+                }
+            };
+        }
+        self.gain
+    }
+
+    // Applies the gain to interleaved samples, every channel of a frame alike
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if self.remaining_frames == 0 {
+            if self.gain != 1.0 {
+                simd::apply_gain(samples, self.gain);
+            }
+            return;
+        }
+        for frame in samples.chunks_mut(self.channels) {
+            let gain = self.next_gain();
+            for sample in frame.iter_mut() {
+                *sample *= gain;
+            }
+        }
+    }
+
+    // Forgets the gain; the next target applies at once again
+    pub fn reset(&mut self) {
+        self.started = false;
+        self.jump_to(1.0);
+    }
+}
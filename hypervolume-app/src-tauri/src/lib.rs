@@ -1,64 +1,553 @@
-use tauri::{App, Manager};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use tauri::Emitter;
 
 mod audio_manager;
 mod audio_boost;
+mod audio_endpoints;
+mod audio_pipeline;
+mod biquad;
+mod channel_mixer;
+mod clipper;
+mod compressor;
+mod ducking;
+mod equalizer;
+mod gain_ramp;
+mod limiter;
+mod loudness;
+mod noise_gate;
+mod processor;
+mod resampler;
+mod true_peak;
+// PCM conversion for the WASAPI streams and the WAV endpoints the tests use
+#[cfg(any(windows, test))]
+mod sample_format;
+pub mod simd;
+mod app_groups;
+#[cfg(test)]
+mod simulated_backend;
+// The software stream engine that boost pipelines render through
+mod ring_buffer;
+mod virtual_device;
+mod session_watcher;
+mod metering;
+#[cfg(windows)]
+mod wasapi_backend;
+#[cfg(windows)]
+mod wasapi_stream;
+#[cfg(target_os = "linux")]
+mod pulse_backend;
 mod updater;
 mod tests;
 
+// The processing path, public for the benchmarks in benches/
+pub use audio_boost::{AudioProcessor, BoostControl};
+pub use audio_endpoints::AudioFormat;
+pub use clipper::{ClipCurve, ClipperSettings};
+pub use compressor::{DynamicsMode, MultibandSettings};
+pub use equalizer::{EqBand, EqPreset, FilterType};
+pub use loudness::AutoGainSettings;
+
+// Audio backend selected at startup
+static AUDIO_BACKEND: Mutex<Option<Arc<dyn audio_manager::AudioBackend>>> = Mutex::new(None);
+
 // Global boost manager instance
 static BOOST_MANAGER: Mutex<Option<audio_boost::AudioBoostManager>> = Mutex::new(None);
 
+// Pushes session changes to the frontend for as long as the app runs
+static SESSION_WATCHER: Mutex<Option<session_watcher::SessionWatcher>> = Mutex::new(None);
+
+// Level meters only run while the frontend shows them
+static LEVEL_MONITOR: Mutex<Option<metering::LevelMonitor>> = Mutex::new(None);
+
+// Ducks background sessions while voice chat plays, once the user turned it on
+static DUCKING_MONITOR: Mutex<Option<ducking::DuckingMonitor>> = Mutex::new(None);
+
+fn get_audio_backend() -> Result<Arc<dyn audio_manager::AudioBackend>, String> {
+    let backend_guard = AUDIO_BACKEND.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    backend_guard.clone().ok_or_else(|| "Audio backend not initialized".to_string())
+}
+
 fn get_boost_manager() -> &'static Mutex<Option<audio_boost::AudioBoostManager>> {
     &BOOST_MANAGER
 }
 
+// Installs `backend` for all commands and rebuilds the boost manager on top of it, with
+// the EQ presets of `eq_presets`
+fn install_audio_backend(backend: Arc<dyn audio_manager::AudioBackend>, eq_presets: equalizer::EqPresetStore) {
+    // Ducking drives the volumes of the previous backend's sessions
+    DUCKING_MONITOR.lock().unwrap().take();
+    *AUDIO_BACKEND.lock().unwrap() = Some(Arc::clone(&backend));
+    *get_boost_manager().lock().unwrap() = Some(audio_boost::AudioBoostManager::new(backend).with_eq_presets(eq_presets));
+}
+
 #[tauri::command]
-fn get_audio_sessions() -> Result<Vec<audio_manager::AudioSession>, String> {
-    audio_manager::enumerate_audio_sessions()
+fn get_audio_devices() -> Result<Vec<audio_manager::AudioDevice>, String> {
+    get_audio_backend()?.enumerate_devices()
 }
 
+// Without a device ID, lists the sessions of every active device
 #[tauri::command]
-fn set_app_volume(process_id: u32, volume: f32) -> Result<(), String> {
+fn get_audio_sessions(device_id: Option<String>) -> Result<Vec<audio_manager::AudioSession>, String> {
+    let backend = get_audio_backend()?;
+    match device_id {
+        Some(device_id) => backend.enumerate_device_sessions(&device_id),
+        None => backend.enumerate_sessions(),
+    }
+}
+
+#[tauri::command]
+fn set_app_volume(session_id: String, volume: f32) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    let Some(ref manager) = *manager_guard else {
+        return Err("Boost manager not initialized".to_string());
+    };
     // If volume > 1.0, use boost manager
     if volume > 1.0 {
-        let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
-        if let Some(ref manager) = *manager_guard {
-            manager.set_boost(process_id, volume)
-        } else {
-            Err("Boost manager not initialized".to_string())
-        }
+        manager.set_boost(&session_id, volume)
     } else {
-        // Use standard volume control
-        audio_manager::set_session_volume(process_id, volume)
+        // Use standard volume control, with the boost of a higher setting taken back
+        if manager.get_boost(&session_id)? != 1.0 {
+            manager.set_boost(&session_id, 1.0)?;
+        }
+        get_audio_backend()?.set_session_volume(&session_id, volume)
     }
 }
 
 #[tauri::command]
-fn toggle_app_mute(process_id: u32) -> Result<(), String> {
-    audio_manager::toggle_session_mute(process_id)
+fn toggle_app_mute(session_id: String) -> Result<(), String> {
+    audio_manager::toggle_session_mute(get_audio_backend()?.as_ref(), &session_id)
+}
+
+#[tauri::command]
+fn get_app_channel_volumes(session_id: String) -> Result<Vec<f32>, String> {
+    get_audio_backend()?.get_session_channel_volumes(&session_id)
+}
+
+#[tauri::command]
+fn set_app_channel_volumes(session_id: String, volumes: Vec<f32>) -> Result<(), String> {
+    get_audio_backend()?.set_session_channel_volumes(&session_id, &volumes)
 }
 
+// Balance runs from -1.0 (full left) to 1.0 (full right)
 #[tauri::command]
-fn set_app_boost(process_id: u32, boost_factor: f32) -> Result<(), String> {
+fn set_app_balance(session_id: String, balance: f32) -> Result<(), String> {
+    audio_manager::set_session_balance(get_audio_backend()?.as_ref(), &session_id, balance)
+}
+
+#[tauri::command]
+fn set_app_boost(session_id: String, boost_factor: f32) -> Result<(), String> {
     let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
     if let Some(ref manager) = *manager_guard {
-        manager.set_boost(process_id, boost_factor)
+        manager.set_boost(&session_id, boost_factor)
     } else {
         Err("Boost manager not initialized".to_string())
     }
 }
 
 #[tauri::command]
-fn get_app_boost(process_id: u32) -> Result<f32, String> {
+fn get_app_boost(session_id: String) -> Result<f32, String> {
     let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
     if let Some(ref manager) = *manager_guard {
-        manager.get_boost(process_id)
+        manager.get_boost(&session_id)
     } else {
         Ok(1.0) // Default boost
     }
 }
 
+// Highest true peak the session's boosted audio may reach, in dBTP
+#[tauri::command]
+fn set_app_true_peak_ceiling(session_id: String, ceiling_dbtp: f32) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_true_peak_ceiling(&session_id, ceiling_dbtp)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_app_true_peak_ceiling(session_id: String) -> Result<f32, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_true_peak_ceiling(&session_id)
+    } else {
+        Ok(audio_boost::DEFAULT_TRUE_PEAK_CEILING_DBTP)
+    }
+}
+
+// Lets the session's gain follow its loudness towards a target; None turns it off
+#[tauri::command]
+fn set_app_auto_gain(session_id: String, settings: Option<loudness::AutoGainSettings>) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_auto_gain(&session_id, settings)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_app_auto_gain(session_id: String) -> Result<Option<loudness::AutoGainSettings>, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_auto_gain(&session_id)
+    } else {
+        Ok(None)
+    }
+}
+
+// Momentary, short-term and integrated loudness of a session with a running pipeline
+#[tauri::command]
+fn get_app_loudness(session_id: String) -> Result<loudness::LoudnessReading, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_loudness(&session_id)
+    } else {
+        Ok(loudness::LoudnessReading::default())
+    }
+}
+
+// Compressor, multiband compressor or none, applied to the session's boosted audio
+#[tauri::command]
+fn set_app_dynamics(session_id: String, mode: compressor::DynamicsMode) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_dynamics(&session_id, mode)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_app_dynamics(session_id: String) -> Result<compressor::DynamicsMode, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_dynamics(&session_id)
+    } else {
+        Ok(compressor::DynamicsMode::Off)
+    }
+}
+
+// Saturation curve the session's boosted audio is clipped with; None turns it off
+#[tauri::command]
+fn set_app_clipper(session_id: String, settings: Option<clipper::ClipperSettings>) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_clipper(&session_id, settings)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_app_clipper(session_id: String) -> Result<Option<clipper::ClipperSettings>, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_clipper(&session_id)
+    } else {
+        Ok(None)
+    }
+}
+
+// Effects the session's audio goes through, in order; saved and restored as JSON
+#[tauri::command]
+fn set_app_chain(session_id: String, chain: processor::ChainDescription) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_chain(&session_id, chain)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_app_chain(session_id: String) -> Result<processor::ChainDescription, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_chain(&session_id)
+    } else {
+        Ok(processor::ChainDescription::default())
+    }
+}
+
+// Latency the session's running pipeline adds, chain included
+#[tauri::command]
+fn get_app_latency(session_id: String) -> Result<Option<processor::LatencyReport>, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_latency(&session_id)
+    } else {
+        Ok(None)
+    }
+}
+
+// Sample-rate conversion of the session's pipeline, used from its next start
+#[tauri::command]
+fn set_app_resampler_quality(session_id: String, quality: resampler::ResamplerQuality) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_resampler_quality(&session_id, quality)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_app_resampler_quality(session_id: String) -> Result<resampler::ResamplerQuality, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_resampler_quality(&session_id)
+    } else {
+        Ok(resampler::ResamplerQuality::default())
+    }
+}
+
+// How fast the session's boost follows a change; a zero duration applies it at once
+#[tauri::command]
+fn set_app_gain_ramp(session_id: String, ramp: gain_ramp::RampSettings) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_gain_ramp(&session_id, ramp)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_app_gain_ramp(session_id: String) -> Result<gain_ramp::RampSettings, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_gain_ramp(&session_id)
+    } else {
+        Ok(gain_ramp::RampSettings::default())
+    }
+}
+
+// Parametric EQ preset of the session's application
+#[tauri::command]
+fn set_app_equalizer(session_id: String, preset: equalizer::EqPreset) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_equalizer(&session_id, preset)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_app_equalizer(session_id: String) -> Result<equalizer::EqPreset, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_equalizer(&session_id)
+    } else {
+        Ok(equalizer::EqPreset::default())
+    }
+}
+
+// Parametric EQ preset of an output device, applied to every pipeline rendering to it
+#[tauri::command]
+fn set_device_equalizer(device_id: String, preset: equalizer::EqPreset) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.set_device_equalizer(&device_id, preset)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_device_equalizer(device_id: String) -> Result<equalizer::EqPreset, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.get_device_equalizer(&device_id)
+    } else {
+        Ok(equalizer::EqPreset::default())
+    }
+}
+
+// Gain of a preset from 20 Hz to 20 kHz for the UI to plot; 256 points by default
+#[tauri::command]
+fn get_eq_frequency_response(
+    preset: equalizer::EqPreset,
+    points: Option<usize>,
+) -> Result<Vec<equalizer::FrequencyResponsePoint>, String> {
+    preset.validate()?;
+    let points = points.unwrap_or(256);
+    if !(2..=4096).contains(&points) {
+        return Err(format!("Invalid number of response points: {}", points));
+    }
+    Ok(preset.frequency_response(equalizer::RESPONSE_SAMPLE_RATE, points))
+}
+
+// Routes the session's audio from `capture_device_id` through its boost to `render_device_id`
+#[tauri::command]
+fn start_boost_pipeline(session_id: String, capture_device_id: String, render_device_id: String) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.start_pipeline(&session_id, &capture_device_id, &render_device_id)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn stop_boost_pipeline(session_id: String) -> Result<(), String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.stop_pipeline(&session_id)
+    } else {
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn get_boost_pipeline_running(session_id: String) -> Result<bool, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        Ok(manager.pipeline_running(&session_id))
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_boost_pipeline_stats(session_id: String) -> Result<virtual_device::StreamStats, String> {
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        manager.pipeline_stats(&session_id)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+// Sessions grouped per application; without a device ID, across every active device
+#[tauri::command]
+fn get_app_groups(device_id: Option<String>) -> Result<Vec<app_groups::AppGroup>, String> {
+    let backend = get_audio_backend()?;
+    let sessions = match device_id {
+        Some(device_id) => backend.enumerate_device_sessions(&device_id)?,
+        None => backend.enumerate_sessions()?,
+    };
+    Ok(app_groups::group_sessions(backend.as_ref(), &sessions))
+}
+
+#[tauri::command]
+fn set_group_volume(group_id: String, volume: f32) -> Result<(), String> {
+    let backend = get_audio_backend()?;
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    let Some(ref manager) = *manager_guard else {
+        return Err("Boost manager not initialized".to_string());
+    };
+    // Like set_app_volume, levels above 1.0 are boost
+    if volume > 1.0 {
+        app_groups::set_group_boost(manager, backend.as_ref(), &group_id, volume)
+    } else {
+        app_groups::set_group_volume(manager, backend.as_ref(), &group_id, volume)
+    }
+}
+
+#[tauri::command]
+fn toggle_group_mute(group_id: String) -> Result<(), String> {
+    app_groups::toggle_group_mute(get_audio_backend()?.as_ref(), &group_id)
+}
+
+#[tauri::command]
+fn set_group_boost(group_id: String, boost_factor: f32) -> Result<(), String> {
+    let backend = get_audio_backend()?;
+    let manager_guard = get_boost_manager().lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    if let Some(ref manager) = *manager_guard {
+        app_groups::set_group_boost(manager, backend.as_ref(), &group_id, boost_factor)
+    } else {
+        Err("Boost manager not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_device_volume(device_id: String) -> Result<f32, String> {
+    get_audio_backend()?.get_device_volume(&device_id)
+}
+
+#[tauri::command]
+fn set_device_volume(device_id: String, volume: f32) -> Result<(), String> {
+    get_audio_backend()?.set_device_volume(&device_id, volume)
+}
+
+#[tauri::command]
+fn get_device_mute(device_id: String) -> Result<bool, String> {
+    get_audio_backend()?.get_device_mute(&device_id)
+}
+
+#[tauri::command]
+fn set_device_mute(device_id: String, muted: bool) -> Result<(), String> {
+    get_audio_backend()?.set_device_mute(&device_id, muted)
+}
+
+#[tauri::command]
+fn get_device_volume_range(device_id: String) -> Result<audio_manager::VolumeRange, String> {
+    get_audio_backend()?.get_device_volume_range(&device_id)
+}
+
+// Starts (or restarts at a new rate) emitting "audio-levels" events
+#[tauri::command]
+fn start_level_metering(app: tauri::AppHandle, rate_hz: Option<f32>) -> Result<(), String> {
+    let mut settings = metering::MeterSettings::default();
+    if let Some(rate_hz) = rate_hz {
+        settings.sample_rate_hz = rate_hz;
+    }
+
+    let mut monitor_guard = LEVEL_MONITOR.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    // Stop the previous monitor before its replacement starts polling
+    monitor_guard.take();
+    let pipeline_levels = || {
+        get_boost_manager().lock().ok()
+            .and_then(|manager_guard| manager_guard.as_ref().map(|manager| manager.pipeline_levels()))
+            .unwrap_or_default()
+    };
+    *monitor_guard = Some(metering::LevelMonitor::start(get_audio_backend()?, settings, pipeline_levels, move |snapshot| {
+        if let Err(e) = app.emit("audio-levels", snapshot) {
+            eprintln!("Failed to emit audio-levels: {}", e);
+        }
+    }));
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_level_metering() -> Result<(), String> {
+    LEVEL_MONITOR.lock().map_err(|e| format!("Lock error: {:?}", e))?.take();
+    Ok(())
+}
+
+// Turns background sessions down while trigger sessions play; None turns ducking off and
+// gives the ducked sessions their volume back
+#[tauri::command]
+fn set_ducking(settings: Option<ducking::DuckingSettings>) -> Result<(), String> {
+    let mut monitor_guard = DUCKING_MONITOR.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    match (settings, monitor_guard.as_ref()) {
+        (Some(settings), Some(monitor)) => monitor.set_settings(settings),
+        (Some(settings), None) => {
+            *monitor_guard = Some(ducking::DuckingMonitor::start(get_audio_backend()?, settings)?);
+            Ok(())
+        }
+        (None, _) => {
+            monitor_guard.take();
+            Ok(())
+        }
+    }
+}
+
+#[tauri::command]
+fn get_ducking() -> Result<Option<ducking::DuckingSettings>, String> {
+    let monitor_guard = DUCKING_MONITOR.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    Ok(monitor_guard.as_ref().map(|monitor| monitor.settings()))
+}
+
+// How far the ducked sessions are turned down right now, in dB
+#[tauri::command]
+fn get_ducking_attenuation() -> Result<f32, String> {
+    let monitor_guard = DUCKING_MONITOR.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+    Ok(monitor_guard.as_ref().map_or(0.0, |monitor| monitor.attenuation_db()))
+}
+
 #[tauri::command]
 async fn check_for_updates() -> Result<Option<updater::UpdateInfo>, String> {
     let current_version = updater::AppVersion::new(1, 0, 0); // Current app version
@@ -97,25 +586,87 @@ fn save_update_config(config: updater::UpdateConfig) -> Result<(), String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // Initialize boost manager
-    {
-        let mut manager_guard = get_boost_manager().lock().unwrap();
-        *manager_guard = Some(audio_boost::AudioBoostManager::new());
-    }
+    // Initialize audio backend and boost manager
+    install_audio_backend(
+        audio_manager::default_backend(),
+        equalizer::EqPresetStore::load(equalizer::eq_presets_path()),
+    );
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .setup(|app| {
+            let handle = app.handle().clone();
+            let watcher = session_watcher::SessionWatcher::start(get_audio_backend()?, move |event| {
+                if let Err(e) = handle.emit(event.event_name(), event) {
+                    eprintln!("Failed to emit {}: {}", event.event_name(), e);
+                }
+            })?;
+            *SESSION_WATCHER.lock().unwrap() = Some(watcher);
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
+            get_audio_devices,
             get_audio_sessions,
             set_app_volume,
             toggle_app_mute,
+            get_app_channel_volumes,
+            set_app_channel_volumes,
+            set_app_balance,
             set_app_boost,
             get_app_boost,
+            set_app_true_peak_ceiling,
+            get_app_true_peak_ceiling,
+            set_app_auto_gain,
+            get_app_auto_gain,
+            get_app_loudness,
+            set_app_dynamics,
+            get_app_dynamics,
+            set_app_clipper,
+            get_app_clipper,
+            set_app_chain,
+            get_app_chain,
+            get_app_latency,
+            set_app_resampler_quality,
+            get_app_resampler_quality,
+            set_app_gain_ramp,
+            get_app_gain_ramp,
+            set_app_equalizer,
+            get_app_equalizer,
+            set_device_equalizer,
+            get_device_equalizer,
+            get_eq_frequency_response,
+            start_boost_pipeline,
+            stop_boost_pipeline,
+            get_boost_pipeline_running,
+            get_boost_pipeline_stats,
+            get_app_groups,
+            set_group_volume,
+            toggle_group_mute,
+            set_group_boost,
+            get_device_volume,
+            set_device_volume,
+            get_device_mute,
+            set_device_mute,
+            get_device_volume_range,
+            start_level_metering,
+            stop_level_metering,
+            set_ducking,
+            get_ducking,
+            get_ducking_attenuation,
             check_for_updates,
             download_and_install_update,
             get_update_config,
             save_update_config
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            // Statics are never dropped, and Windows keeps session volumes across runs, so
+            // the sessions still ducked get their volume back before the app quits
+            if let tauri::RunEvent::Exit = event {
+                if let Some(mut monitor) = DUCKING_MONITOR.lock().ok().and_then(|mut monitor| monitor.take()) {
+                    monitor.stop();
+                }
+            }
+        });
 }
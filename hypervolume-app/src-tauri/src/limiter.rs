@@ -0,0 +1,212 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::audio_endpoints::AudioFormat;
+use crate::true_peak::TruePeakDetector;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimiterSettings {
+    // Highest absolute sample value the limiter lets through, linear
+    pub ceiling: f32,
+    // How long the gain takes to reach a new reduction; at most the look-ahead
+    pub attack: Duration,
+    // Time constant of the gain recovering after a peak
+    pub release: Duration,
+    // How far ahead peaks are seen; this is also the latency the limiter adds
+    pub lookahead: Duration,
+    // Limit the oversampled (inter-sample) peak instead of the sample peak, so the ceiling
+    // is in dBTP; adds the true-peak detector's latency
+    pub true_peak: bool,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            ceiling: 0.95,
+            attack: Duration::from_millis(5),
+            release: Duration::from_millis(100),
+            lookahead: Duration::from_millis(5),
+            true_peak: false,
+        }
+    }
+}
+
+impl LimiterSettings {
+    // Limits the true peak to `ceiling_dbtp`
+    pub fn true_peak(ceiling_dbtp: f32) -> Self {
+        Self {
+            ceiling: db_to_linear(ceiling_dbtp),
+            true_peak: true,
+            ..Self::default()
+        }
+    }
+}
+
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+pub fn linear_to_db(linear: f32) -> f32 {
+    20.0 * linear.log10()
+}
+
+// Look-ahead brickwall limiter. Every frame needs a gain of at most ceiling / peak; the
+// limiter holds the lowest such gain over the look-ahead window, smooths it with a moving
+// average over the attack time and delays the audio by the look-ahead, so the gain has
+// fully come down by the time a peak leaves the delay line. Channels share one gain, so
+// the stereo image does not shift.
+pub struct LookaheadLimiter {
+    settings: LimiterSettings,
+    channels: usize,
+    lookahead_frames: usize,
+    // Reports peaks `detector_latency` frames late, so the audio is delayed that much longer
+    detector: Option<TruePeakDetector>,
+    detector_latency: usize,
+    release_coefficient: f32,
+    // Frame index and required gain, increasing gains from front to back (sliding minimum)
+    required_gains: VecDeque<(u64, f32)>,
+    // Last `attack` held gains and their sum, for the moving average
+    attack_window: Vec<f32>,
+    attack_position: usize,
+    attack_sum: f64,
+    gain: f32,
+    delay_line: Vec<f32>,
+    delay_position: usize,
+    frame_index: u64,
+    max_gain_reduction_db: f32,
+}
+
+impl LookaheadLimiter {
+    pub fn new(format: AudioFormat, settings: LimiterSettings) -> Self {
+        let sample_rate = format.sample_rate.max(1) as f32;
+        let channels = format.channels.max(1) as usize;
+
+        let lookahead_frames = ((settings.lookahead.as_secs_f32() * sample_rate).round() as usize).max(1);
+        let attack_frames = ((settings.attack.as_secs_f32() * sample_rate).round() as usize).clamp(1, lookahead_frames);
+        let release_frames = settings.release.as_secs_f32() * sample_rate;
+        let release_coefficient = if release_frames > 0.0 { (-1.0 / release_frames).exp() } else { 0.0 };
+        let detector = settings.true_peak.then(|| TruePeakDetector::new(format));
+        let detector_latency = detector.as_ref().map_or(0, TruePeakDetector::latency_frames);
+
+        Self {
+            settings,
+            channels,
+            lookahead_frames,
+            detector,
+            detector_latency,
+            release_coefficient,
+            required_gains: VecDeque::with_capacity(lookahead_frames + 1),
+            attack_window: vec![1.0; attack_frames],
+            attack_position: 0,
+            attack_sum: attack_frames as f64,
+            gain: 1.0,
+            // A peak must leave the delay line on the last frame of its hold window
+            delay_line: vec![0.0; (lookahead_frames - 1 + detector_latency) * channels],
+            delay_position: 0,
+            frame_index: 0,
+            max_gain_reduction_db: 0.0,
+        }
+    }
+
+    pub fn settings(&self) -> LimiterSettings {
+        self.settings
+    }
+
+    // Takes effect from the next frame on; the gain follows with the usual attack/release
+    pub fn set_ceiling(&mut self, ceiling: f32) {
+        self.settings.ceiling = ceiling;
+    }
+
+    // Frames between a sample going in and coming out
+    pub fn latency_frames(&self) -> usize {
+        self.lookahead_frames - 1 + self.detector_latency
+    }
+
+    // Limits interleaved samples in place, delayed by `latency_frames`
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let ceiling = self.settings.ceiling;
+
+        for frame in samples.chunks_mut(self.channels) {
+            let peak = match &mut self.detector {
+                Some(detector) => detector.process_frame(frame),
+                None => frame.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs())),
+            };
+            let required_gain = if peak > ceiling { ceiling / peak } else { 1.0 };
+            let held_gain = self.hold(required_gain);
+
+            self.attack_sum += (held_gain - self.attack_window[self.attack_position]) as f64;
+            self.attack_window[self.attack_position] = held_gain;
+            self.attack_position = (self.attack_position + 1) % self.attack_window.len();
+            let smoothed_gain = (self.attack_sum / self.attack_window.len() as f64) as f32;
+
+            // Down at once (the moving average already shaped the attack), up with the release
+            self.gain = if smoothed_gain < self.gain {
+                smoothed_gain
+            } else {
+                smoothed_gain + (self.gain - smoothed_gain) * self.release_coefficient
+            };
+
+            let gain_reduction_db = -linear_to_db(self.gain);
+            if gain_reduction_db > self.max_gain_reduction_db {
+                self.max_gain_reduction_db = gain_reduction_db;
+            }
+
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let delayed = if self.delay_line.is_empty() {
+                    *sample
+                } else {
+                    let slot = self.delay_position + channel;
+                    std::mem::replace(&mut self.delay_line[slot], *sample)
+                };
+                // Rounding in the running average must not let a sample slip past the ceiling
+                *sample = (delayed * self.gain).clamp(-ceiling, ceiling);
+            }
+            if !self.delay_line.is_empty() {
+                self.delay_position = (self.delay_position + self.channels) % self.delay_line.len();
+            }
+        }
+    }
+
+    // Lowest required gain of the last `lookahead_frames` frames
+    fn hold(&mut self, required_gain: f32) -> f32 {
+        let frame_index = self.frame_index;
+        self.frame_index += 1;
+
+        while self.required_gains.front()
+            .is_some_and(|(index, _)| *index + self.lookahead_frames as u64 <= frame_index)
+        {
+            self.required_gains.pop_front();
+        }
+        while self.required_gains.back().is_some_and(|(_, gain)| *gain >= required_gain) {
+            self.required_gains.pop_back();
+        }
+        self.required_gains.push_back((frame_index, required_gain));
+
+        self.required_gains.front().map(|(_, gain)| *gain).unwrap_or(1.0)
+    }
+
+    // Current gain reduction in dB, 0 when the limiter is idle
+    pub fn gain_reduction_db(&self) -> f32 {
+        -linear_to_db(self.gain)
+    }
+
+    // Largest gain reduction since the previous call
+    pub fn take_max_gain_reduction_db(&mut self) -> f32 {
+        std::mem::take(&mut self.max_gain_reduction_db)
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(detector) = &mut self.detector {
+            detector.reset();
+        }
+        self.required_gains.clear();
+        self.attack_window.fill(1.0);
+        self.attack_position = 0;
+        self.attack_sum = self.attack_window.len() as f64;
+        self.gain = 1.0;
+        self.delay_line.fill(0.0);
+        self.delay_position = 0;
+        self.frame_index = 0;
+        self.max_gain_reduction_db = 0.0;
+    }
+}
@@ -0,0 +1,310 @@
+use std::time::Duration;
+
+use crate::audio_endpoints::AudioFormat;
+use crate::audio_manager::{default_channel_layout, ChannelPosition};
+use crate::biquad::{Biquad, BiquadCoefficients};
+
+// Blocks below this never count towards the integrated loudness (BS.1770 absolute gate)
+pub const ABSOLUTE_GATE_LUFS: f32 = -70.0;
+// Blocks more than this below the absolute-gated loudness are dropped as well
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+// Momentary and gating blocks are 400 ms, short-term 3 s; both advance in 100 ms steps
+const STEP_DURATION: Duration = Duration::from_millis(100);
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+
+// Histogram of gating blocks for the integrated loudness, 0.1 LU per bin from the
+// absolute gate up to +10 LUFS; louder blocks go into the last bin
+const HISTOGRAM_BIN_LU: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 800;
+
+fn energy_to_lufs(energy: f64) -> f32 {
+    (-0.691 + 10.0 * energy.log10()) as f32
+}
+
+// BS.1770 channel weights: surround channels count 1.41 (+1.5 dB), LFE not at all
+fn channel_weight(position: ChannelPosition) -> f64 {
+    match position {
+        ChannelPosition::LowFrequency => 0.0,
+        ChannelPosition::BackLeft
+        | ChannelPosition::BackRight
+        | ChannelPosition::BackCenter
+        | ChannelPosition::SideLeft
+        | ChannelPosition::SideRight => 1.41,
+        _ => 1.0,
+    }
+}
+
+// BS.1770 K-weighting: a high shelf modelling the head followed by the RLB high-pass.
+// The standard lists coefficients for 48 kHz only; these are derived from the analog
+// prototypes so that every sample rate gets the same curve.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let sample_rate = sample_rate.max(1) as f64;
+
+    let shelf_frequency = 1681.974450955533;
+    let shelf_gain_db = 3.999843853973347;
+    let shelf_q = 0.7071752369554196;
+    let k = (std::f64::consts::PI * shelf_frequency / sample_rate).tan();
+    let vh = 10f64.powf(shelf_gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / shelf_q + k * k;
+    let shelf = BiquadCoefficients::new(
+        (vh + vb * k / shelf_q + k * k) / a0,
+        2.0 * (k * k - vh) / a0,
+        (vh - vb * k / shelf_q + k * k) / a0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / shelf_q + k * k) / a0,
+    );
+
+    let high_pass_frequency = 38.13547087602444;
+    let high_pass_q = 0.5003270373238773;
+    let k = (std::f64::consts::PI * high_pass_frequency / sample_rate).tan();
+    let a0 = 1.0 + k / high_pass_q + k * k;
+    let high_pass = BiquadCoefficients::new(
+        1.0,
+        -2.0,
+        1.0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / high_pass_q + k * k) / a0,
+    );
+
+    [Biquad::new(shelf), Biquad::new(high_pass)]
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct GateBin {
+    blocks: u64,
+    energy: f64,
+}
+
+// One reading of a loudness meter, in LUFS. A value is None until its window has filled,
+// and while the window is silent (below the absolute gate).
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize)]
+pub struct LoudnessReading {
+    pub momentary_lufs: Option<f32>,
+    pub short_term_lufs: Option<f32>,
+    pub integrated_lufs: Option<f32>,
+}
+
+// EBU R128 / ITU-R BS.1770 loudness meter: momentary (400 ms), short-term (3 s) and gated
+// integrated loudness.
+pub struct LoudnessMeter {
+    channels: usize,
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+    step_frames: usize,
+    // Weighted sum of squares of the step being filled
+    step_frames_done: usize,
+    step_energy: f64,
+    // Mean square of the last SHORT_TERM_STEPS steps, oldest overwritten first
+    steps: [f64; SHORT_TERM_STEPS],
+    step_position: usize,
+    completed_steps: usize,
+    histogram: Vec<GateBin>,
+}
+
+impl LoudnessMeter {
+    pub fn new(format: AudioFormat) -> Self {
+        Self::with_layout(format, &default_channel_layout(format.channels.max(1) as usize))
+    }
+
+    // `layout` gives the position of every channel in a frame
+    pub fn with_layout(format: AudioFormat, layout: &[ChannelPosition]) -> Self {
+        let channels = format.channels.max(1) as usize;
+        let weights = (0..channels)
+            .map(|channel| layout.get(channel).map_or(1.0, |position| channel_weight(*position)))
+            .collect();
+
+        Self {
+            channels,
+            weights,
+            filters: vec![k_weighting(format.sample_rate); channels],
+            step_frames: ((STEP_DURATION.as_secs_f64() * format.sample_rate as f64).round() as usize).max(1),
+            step_frames_done: 0,
+            step_energy: 0.0,
+            steps: [0.0; SHORT_TERM_STEPS],
+            step_position: 0,
+            completed_steps: 0,
+            histogram: vec![GateBin::default(); HISTOGRAM_BINS],
+        }
+    }
+
+    // Measures interleaved samples
+    pub fn process(&mut self, samples: &[f32]) {
+        for frame in samples.chunks(self.channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let [shelf, high_pass] = &mut self.filters[channel];
+                let weighted = high_pass.process(shelf.process(*sample as f64));
+                self.step_energy += self.weights[channel] * weighted * weighted;
+            }
+
+            self.step_frames_done += 1;
+            if self.step_frames_done == self.step_frames {
+                self.complete_step();
+            }
+        }
+    }
+
+    fn complete_step(&mut self) {
+        self.steps[self.step_position] = self.step_energy / self.step_frames as f64;
+        self.step_position = (self.step_position + 1) % SHORT_TERM_STEPS;
+        self.completed_steps += 1;
+        self.step_frames_done = 0;
+        self.step_energy = 0.0;
+
+        // Every step completes a new 400 ms gating block
+        if let Some(energy) = self.window_energy(MOMENTARY_STEPS) {
+            let lufs = energy_to_lufs(energy);
+            if lufs >= ABSOLUTE_GATE_LUFS {
+                let bin = (((lufs - ABSOLUTE_GATE_LUFS) as f64 / HISTOGRAM_BIN_LU) as usize).min(HISTOGRAM_BINS - 1);
+                self.histogram[bin].blocks += 1;
+                self.histogram[bin].energy += energy;
+            }
+        }
+    }
+
+    // Mean square of the last `steps` steps, once that many are complete
+    fn window_energy(&self, steps: usize) -> Option<f64> {
+        if self.completed_steps < steps {
+            return None;
+        }
+        let sum: f64 = (1..=steps)
+            .map(|age| self.steps[(self.step_position + SHORT_TERM_STEPS - age) % SHORT_TERM_STEPS])
+            .sum();
+        Some(sum / steps as f64)
+    }
+
+    fn window_lufs(&self, steps: usize) -> Option<f32> {
+        self.window_energy(steps)
+            .map(energy_to_lufs)
+            .filter(|lufs| *lufs >= ABSOLUTE_GATE_LUFS)
+    }
+
+    pub fn momentary_lufs(&self) -> Option<f32> {
+        self.window_lufs(MOMENTARY_STEPS)
+    }
+
+    pub fn short_term_lufs(&self) -> Option<f32> {
+        self.window_lufs(SHORT_TERM_STEPS)
+    }
+
+    // Gated loudness of everything measured since the last reset
+    pub fn integrated_lufs(&self) -> Option<f32> {
+        let gated_mean = |threshold: f64| {
+            let (blocks, energy) = self.histogram.iter()
+                .enumerate()
+                .filter(|(bin, _)| ABSOLUTE_GATE_LUFS as f64 + (*bin as f64 + 0.5) * HISTOGRAM_BIN_LU >= threshold)
+                .fold((0, 0.0), |(blocks, energy), (_, bin)| (blocks + bin.blocks, energy + bin.energy));
+            (blocks > 0).then(|| energy / blocks as f64)
+        };
+
+        let absolute_gated = gated_mean(ABSOLUTE_GATE_LUFS as f64)?;
+        let relative_gate = energy_to_lufs(absolute_gated) as f64 + RELATIVE_GATE_LU;
+        gated_mean(relative_gate).map(energy_to_lufs)
+    }
+
+    pub fn reading(&self) -> LoudnessReading {
+        LoudnessReading {
+            momentary_lufs: self.momentary_lufs(),
+            short_term_lufs: self.short_term_lufs(),
+            integrated_lufs: self.integrated_lufs(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for filters in &mut self.filters {
+            filters.iter_mut().for_each(Biquad::reset);
+        }
+        self.step_frames_done = 0;
+        self.step_energy = 0.0;
+        self.steps = [0.0; SHORT_TERM_STEPS];
+        self.step_position = 0;
+        self.completed_steps = 0;
+        self.histogram.fill(GateBin::default());
+    }
+}
+
+// Loudness auto-gain of a session
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AutoGainSettings {
+    pub target_lufs: f32,
+    // Most the gain may boost a quiet session by
+    pub max_gain_db: f32,
+    // Fastest the gain may change, so it follows the programme instead of pumping
+    pub max_slew_db_per_second: f32,
+}
+
+pub const MIN_TARGET_LUFS: f32 = -50.0;
+pub const MAX_TARGET_LUFS: f32 = -5.0;
+pub const MAX_AUTO_GAIN_DB: f32 = 24.0;
+// Loud sessions are never turned down further than this
+pub const MIN_AUTO_GAIN_DB: f32 = -40.0;
+
+impl Default for AutoGainSettings {
+    fn default() -> Self {
+        Self {
+            target_lufs: -23.0,
+            max_gain_db: 12.0,
+            max_slew_db_per_second: 3.0,
+        }
+    }
+}
+
+impl AutoGainSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_TARGET_LUFS..=MAX_TARGET_LUFS).contains(&self.target_lufs) {
+            return Err(format!(
+                "Target loudness {} LUFS is outside {}..={} LUFS",
+                self.target_lufs, MIN_TARGET_LUFS, MAX_TARGET_LUFS
+            ));
+        }
+        if !(0.0..=MAX_AUTO_GAIN_DB).contains(&self.max_gain_db) {
+            return Err(format!("Maximum gain {} dB is outside 0..={} dB", self.max_gain_db, MAX_AUTO_GAIN_DB));
+        }
+        if !(self.max_slew_db_per_second > 0.0 && self.max_slew_db_per_second.is_finite()) {
+            return Err(format!("Invalid gain slew rate: {} dB/s", self.max_slew_db_per_second));
+        }
+        Ok(())
+    }
+}
+
+// Steers a gain towards the one that brings the measured loudness to the target
+#[derive(Debug, Clone)]
+pub struct AutoGain {
+    gain_db: f32,
+}
+
+impl AutoGain {
+    pub fn new() -> Self {
+        Self { gain_db: 0.0 }
+    }
+
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    // Moves the gain towards the target for `elapsed` of audio measured at `loudness_lufs`
+    // (before the gain) and returns the new gain. Silence (None) holds the gain, so pauses
+    // are not boosted up to the target.
+    pub fn update(&mut self, settings: &AutoGainSettings, loudness_lufs: Option<f32>, elapsed: Duration) -> f32 {
+        let desired_db = match loudness_lufs {
+            Some(lufs) => (settings.target_lufs - lufs).clamp(MIN_AUTO_GAIN_DB, settings.max_gain_db),
+            None => self.gain_db.min(settings.max_gain_db),
+        };
+
+        let max_step = settings.max_slew_db_per_second * elapsed.as_secs_f32();
+        self.gain_db += (desired_db - self.gain_db).clamp(-max_step, max_step);
+        self.gain_db
+    }
+
+    pub fn reset(&mut self) {
+        self.gain_db = 0.0;
+    }
+}
+
+impl Default for AutoGain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::audio_manager::{AudioBackend, PeakLevels};
+
+// Levels below this (-100 dBFS) are shown as silence instead of decaying forever
+const SILENCE_FLOOR: f32 = 1e-5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterSettings {
+    // How often the backend peak meters are read
+    pub sample_rate_hz: f32,
+    // Minimum time between two published snapshots
+    pub publish_interval: Duration,
+    // How long the peak-hold marker stays at a new maximum before it starts to fall
+    pub hold_time: Duration,
+    pub decay_db_per_second: f32,
+    // Time constant of the RMS average, 300 ms like a VU meter
+    pub rms_window: Duration,
+}
+
+impl Default for MeterSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate_hz: 30.0,
+            publish_interval: Duration::from_millis(66),
+            hold_time: Duration::from_millis(1500),
+            decay_db_per_second: 20.0,
+            rms_window: Duration::from_millis(300),
+        }
+    }
+}
+
+// One meter as shown in the UI; all levels are linear, 1.0 being full scale
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct MeterReading {
+    pub peak: f32,
+    // None until the meter has been fed samples; backend peak meters carry no RMS
+    pub rms: Option<f32>,
+    pub peak_hold: f32,
+    // The last input reached or exceeded full scale
+    pub clipping: bool,
+}
+
+// Peak/RMS meter with peak-hold and decay ballistics. Time only advances through the
+// `elapsed` arguments, so the meter behaves the same in tests as in the polling thread.
+#[derive(Debug, Clone)]
+pub struct LevelMeter {
+    settings: MeterSettings,
+    peak: f32,
+    peak_hold: f32,
+    hold_remaining: Duration,
+    mean_square: Option<f32>,
+    clipping: bool,
+}
+
+impl LevelMeter {
+    pub fn new(settings: MeterSettings) -> Self {
+        Self {
+            settings,
+            peak: 0.0,
+            peak_hold: 0.0,
+            hold_remaining: Duration::ZERO,
+            mean_square: None,
+            clipping: false,
+        }
+    }
+
+    // Feeds a peak value read from a hardware or session meter, `elapsed` after the previous one
+    pub fn process_peak(&mut self, peak: f32, elapsed: Duration) {
+        let peak = peak.abs();
+
+        self.peak = peak.max(self.peak * self.decay_factor(elapsed));
+
+        if peak >= self.peak_hold {
+            self.peak_hold = peak;
+            self.hold_remaining = self.settings.hold_time;
+        } else if elapsed < self.hold_remaining {
+            self.hold_remaining -= elapsed;
+        } else {
+            // Only the part of `elapsed` past the end of the hold time decays
+            let decay_time = elapsed - self.hold_remaining;
+            self.hold_remaining = Duration::ZERO;
+            self.peak_hold = peak.max(self.peak_hold * self.decay_factor(decay_time));
+        }
+
+        if self.peak < SILENCE_FLOOR {
+            self.peak = 0.0;
+        }
+        if self.peak_hold < SILENCE_FLOOR {
+            self.peak_hold = 0.0;
+        }
+        self.clipping = peak >= 1.0;
+    }
+
+    // Feeds a buffer of samples (any channel count, interleaved) lasting `elapsed`
+    pub fn process_samples(&mut self, samples: &[f32], elapsed: Duration) {
+        if samples.is_empty() {
+            self.process_peak(0.0, elapsed);
+            return;
+        }
+
+        let peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let block_mean_square = samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32;
+
+        // Exponential average, so the result does not depend on how the signal is split into buffers
+        let alpha = 1.0 - (-elapsed.as_secs_f32() / self.settings.rms_window.as_secs_f32()).exp();
+        let mean_square = self.mean_square.unwrap_or(0.0);
+        self.mean_square = Some(mean_square + alpha * (block_mean_square - mean_square));
+
+        self.process_peak(peak, elapsed);
+    }
+
+    pub fn reading(&self) -> MeterReading {
+        MeterReading {
+            peak: self.peak,
+            rms: self.mean_square.map(f32::sqrt),
+            peak_hold: self.peak_hold,
+            clipping: self.clipping,
+        }
+    }
+
+    fn decay_factor(&self, elapsed: Duration) -> f32 {
+        10f32.powf(-self.settings.decay_db_per_second * elapsed.as_secs_f32() / 20.0)
+    }
+}
+
+// Payload of the "audio-levels" event, keyed by session ID and device ID
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct LevelSnapshot {
+    pub sessions: HashMap<String, MeterReading>,
+    pub devices: HashMap<String, MeterReading>,
+}
+
+// Keeps one meter per session and device and throttles how often snapshots go out
+pub struct LevelMetering {
+    settings: MeterSettings,
+    sessions: HashMap<String, LevelMeter>,
+    devices: HashMap<String, LevelMeter>,
+    since_publish: Duration,
+}
+
+impl LevelMetering {
+    pub fn new(settings: MeterSettings) -> Self {
+        Self {
+            settings,
+            sessions: HashMap::new(),
+            devices: HashMap::new(),
+            // Publish the very first reading right away
+            since_publish: settings.publish_interval,
+        }
+    }
+
+    // Feeds one backend reading taken `elapsed` after the previous one.
+    // Returns a snapshot when at least `publish_interval` has passed since the last one.
+    pub fn update(&mut self, levels: &PeakLevels, elapsed: Duration) -> Option<LevelSnapshot> {
+        update_meters(&mut self.sessions, &levels.sessions, self.settings, elapsed);
+        update_meters(&mut self.devices, &levels.devices, self.settings, elapsed);
+
+        self.since_publish += elapsed;
+        if self.since_publish < self.settings.publish_interval {
+            return None;
+        }
+        self.since_publish = Duration::ZERO;
+        Some(self.snapshot())
+    }
+
+    pub fn snapshot(&self) -> LevelSnapshot {
+        LevelSnapshot {
+            sessions: readings(&self.sessions),
+            devices: readings(&self.devices),
+        }
+    }
+}
+
+// Meters of sessions and devices that are gone are dropped with them
+fn update_meters(
+    meters: &mut HashMap<String, LevelMeter>,
+    peaks: &HashMap<String, f32>,
+    settings: MeterSettings,
+    elapsed: Duration,
+) {
+    meters.retain(|id, _| peaks.contains_key(id));
+    for (id, peak) in peaks {
+        meters.entry(id.clone())
+            .or_insert_with(|| LevelMeter::new(settings))
+            .process_peak(*peak, elapsed);
+    }
+}
+
+fn readings(meters: &HashMap<String, LevelMeter>) -> HashMap<String, MeterReading> {
+    meters.iter().map(|(id, meter)| (id.clone(), meter.reading())).collect()
+}
+
+// Polls the backend peak meters on a background thread and hands throttled snapshots
+// to a sink, in the app the Tauri event bus. Sessions boosted through a pipeline are
+// shown with the levels `pipeline_levels` metered on their processed audio instead.
+pub struct LevelMonitor {
+    stop_requested: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LevelMonitor {
+    pub fn start<P, F>(backend: Arc<dyn AudioBackend>, settings: MeterSettings, pipeline_levels: P, sink: F) -> Self
+    where
+        P: Fn() -> HashMap<String, MeterReading> + Send + 'static,
+        F: Fn(&LevelSnapshot) + Send + 'static,
+    {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        let poll_interval = Duration::from_secs_f32(1.0 / settings.sample_rate_hz.clamp(1.0, 120.0));
+
+        let thread_stop_requested = Arc::clone(&stop_requested);
+        let thread = thread::spawn(move || {
+            let mut metering = LevelMetering::new(settings);
+            let mut last_poll = Instant::now();
+
+            while !thread_stop_requested.load(Ordering::SeqCst) {
+                // A failed read (device lost, backend without meters) skips one update
+                if let Ok(levels) = backend.get_peak_levels() {
+                    let now = Instant::now();
+                    if let Some(mut snapshot) = metering.update(&levels, now - last_poll) {
+                        snapshot.sessions.extend(pipeline_levels());
+                        sink(&snapshot);
+                    }
+                    last_poll = now;
+                }
+                thread::sleep(poll_interval);
+            }
+        });
+
+        Self {
+            stop_requested,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for LevelMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
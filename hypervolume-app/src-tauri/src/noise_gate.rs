@@ -0,0 +1,197 @@
+use crate::audio_endpoints::AudioFormat;
+use crate::biquad::{Biquad, BiquadCoefficients, BUTTERWORTH_Q};
+use crate::compressor::time_coefficient;
+use crate::limiter::{db_to_linear, linear_to_db};
+
+// Levels below this (-180 dBFS) are treated as silence by the level detector
+const SILENCE_FLOOR: f32 = 1e-9;
+
+// How fast the detected level falls after a peak. Long enough to bridge the zero crossings
+// of the lowest voice frequencies, short enough to follow syllables.
+const DETECTOR_RELEASE_MS: f32 = 10.0;
+
+const MIN_SIDECHAIN_HIGH_PASS_HZ: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GateSettings {
+    // The gate opens once the sidechain level reaches it
+    pub threshold_db: f32,
+    // ...and closes only once the level falls this far below it, so a level hovering
+    // around the threshold does not make it chatter
+    pub hysteresis_db: f32,
+    // dB of attenuation per dB the level lies below the threshold while closed; 1 leaves
+    // the signal alone, 100 is a gate
+    pub ratio: f32,
+    // Most attenuation while closed
+    pub range_db: f32,
+    // Time constants of the gate opening and closing
+    pub attack_ms: f32,
+    // How long the gate stays open after the level fell below the close threshold
+    pub hold_ms: f32,
+    pub release_ms: f32,
+    // Only the sidechain above this frequency opens the gate, so hum and fan rumble do not;
+    // 0 turns the high-pass off
+    pub sidechain_high_pass_hz: f32,
+}
+
+impl Default for GateSettings {
+    fn default() -> Self {
+        Self {
+            threshold_db: -50.0,
+            hysteresis_db: 4.0,
+            ratio: 4.0,
+            range_db: 40.0,
+            attack_ms: 1.0,
+            hold_ms: 50.0,
+            release_ms: 150.0,
+            sidechain_high_pass_hz: 120.0,
+        }
+    }
+}
+
+impl GateSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let checks = [
+            ("Threshold", self.threshold_db, -90.0, 0.0, "dB"),
+            ("Hysteresis", self.hysteresis_db, 0.0, 24.0, "dB"),
+            ("Ratio", self.ratio, 1.0, 100.0, ":1"),
+            ("Range", self.range_db, 0.0, 90.0, "dB"),
+            ("Attack", self.attack_ms, 0.1, 100.0, "ms"),
+            ("Hold", self.hold_ms, 0.0, 2000.0, "ms"),
+            ("Release", self.release_ms, 1.0, 5000.0, "ms"),
+            ("Sidechain high-pass", self.sidechain_high_pass_hz, 0.0, 2000.0, "Hz"),
+        ];
+        for (name, value, min, max, unit) in checks {
+            if !(min..=max).contains(&value) {
+                return Err(format!("{} {} is outside {}..={} {}", name, value, min, max, unit));
+            }
+        }
+        if self.sidechain_high_pass_hz > 0.0 && self.sidechain_high_pass_hz < MIN_SIDECHAIN_HIGH_PASS_HZ {
+            return Err(format!(
+                "Sidechain high-pass {} Hz is below {} Hz; use 0 to turn it off",
+                self.sidechain_high_pass_hz, MIN_SIDECHAIN_HIGH_PASS_HZ
+            ));
+        }
+        Ok(())
+    }
+
+    // Attenuation, in dB, of a closed gate at a steady `level_db`
+    pub fn closed_attenuation_db(&self, level_db: f32) -> f32 {
+        ((self.ratio - 1.0) * (self.threshold_db - level_db)).clamp(0.0, self.range_db)
+    }
+}
+
+// Noise gate and downward expander. The peak level of the high-passed sidechain opens the
+// gate at the threshold; it closes once the level stayed below threshold - hysteresis for
+// the hold time. While closed, the signal is expanded downward by the ratio, down to the
+// range. The attenuation is smoothed in dB with the attack and release time constants.
+// Channels share one gain.
+pub struct NoiseGate {
+    settings: GateSettings,
+    sample_rate: u32,
+    channels: usize,
+    // One per channel; skipped while the high-pass is off
+    sidechain: Vec<Biquad>,
+    filter_sidechain: bool,
+    detector_decay: f32,
+    level: f32,
+    open: bool,
+    hold_frames: usize,
+    hold_remaining: usize,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    attenuation_db: f32,
+}
+
+impl NoiseGate {
+    pub fn new(format: AudioFormat, settings: GateSettings) -> Self {
+        let channels = format.channels.max(1) as usize;
+        let mut gate = Self {
+            settings,
+            sample_rate: format.sample_rate,
+            channels,
+            sidechain: vec![Biquad::new(BiquadCoefficients::IDENTITY); channels],
+            filter_sidechain: false,
+            detector_decay: time_coefficient(DETECTOR_RELEASE_MS, format.sample_rate),
+            level: 0.0,
+            open: false,
+            hold_frames: 0,
+            hold_remaining: 0,
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            attenuation_db: settings.range_db,
+        };
+        gate.set_settings(settings);
+        gate
+    }
+
+    pub fn settings(&self) -> GateSettings {
+        self.settings
+    }
+
+    // Keeps the gate's state, so a change takes over smoothly
+    pub fn set_settings(&mut self, settings: GateSettings) {
+        self.settings = settings;
+        self.attack_coefficient = time_coefficient(settings.attack_ms, self.sample_rate);
+        self.release_coefficient = time_coefficient(settings.release_ms, self.sample_rate);
+        self.hold_frames = (settings.hold_ms / 1000.0 * self.sample_rate as f32) as usize;
+
+        let frequency = settings.sidechain_high_pass_hz;
+        self.filter_sidechain = frequency > 0.0 && frequency < self.sample_rate as f32 / 2.0;
+        if self.filter_sidechain {
+            let coefficients = BiquadCoefficients::high_pass(self.sample_rate, frequency as f64, BUTTERWORTH_Q);
+            self.sidechain.iter_mut().for_each(|filter| filter.set_coefficients(coefficients));
+        }
+    }
+
+    fn process_frame(&mut self, frame: &mut [f32]) {
+        let peak = frame.iter().zip(&mut self.sidechain).fold(0.0f32, |peak, (sample, filter)| {
+            let detected = if self.filter_sidechain { filter.process(*sample as f64) as f32 } else { *sample };
+            peak.max(detected.abs())
+        });
+        self.level = peak.max(self.level * self.detector_decay);
+        let level_db = linear_to_db(self.level.max(SILENCE_FLOOR));
+
+        if level_db >= self.settings.threshold_db {
+            self.open = true;
+            self.hold_remaining = self.hold_frames;
+        } else if self.open && level_db < self.settings.threshold_db - self.settings.hysteresis_db {
+            if self.hold_remaining == 0 {
+                self.open = false;
+            } else {
+                self.hold_remaining -= 1;
+            }
+        }
+
+        let target_db = if self.open { 0.0 } else { self.settings.closed_attenuation_db(level_db) };
+        let coefficient = if target_db < self.attenuation_db {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.attenuation_db = target_db + (self.attenuation_db - target_db) * coefficient;
+
+        if self.attenuation_db > 0.0 {
+            let gain = db_to_linear(-self.attenuation_db);
+            for sample in frame.iter_mut() {
+                *sample *= gain;
+            }
+        }
+    }
+
+    // Gates interleaved samples in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_mut(self.channels) {
+            self.process_frame(frame);
+        }
+    }
+
+    // Starts over closed, from silence
+    pub fn reset(&mut self) {
+        self.sidechain.iter_mut().for_each(Biquad::reset);
+        self.level = 0.0;
+        self.open = false;
+        self.hold_remaining = 0;
+        self.attenuation_db = self.settings.range_db;
+    }
+}
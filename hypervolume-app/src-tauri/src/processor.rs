@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::audio_boost::{MAX_TRUE_PEAK_CEILING_DBTP, MIN_TRUE_PEAK_CEILING_DBTP};
+use crate::audio_endpoints::AudioFormat;
+use crate::clipper::{Clipper, ClipperSettings};
+use crate::compressor::{CompressorSettings, Dynamics, DynamicsMode, MultibandSettings};
+use crate::equalizer::{EqPreset, Equalizer};
+use crate::limiter::{db_to_linear, LimiterSettings, LookaheadLimiter};
+use crate::noise_gate::{GateSettings, NoiseGate};
+use crate::simd;
+
+// Processors one chain may hold
+pub const MAX_CHAIN_PROCESSORS: usize = 16;
+pub const MAX_GAIN_DB: f32 = 24.0;
+
+// Block-based effect that can run in a chain. Processors are built and prepared off the
+// audio thread; `process`, `reset` and `set_parameters` run on it, so they neither block
+// nor allocate. The DSP types behind them, from the limiter to the resampler, keep to the
+// same contract: they allocate when built, never while processing.
+pub trait Processor: Send {
+    // Sizes the processor for `format` and clears its state; may allocate. Parameters have
+    // to be set again afterwards.
+    fn prepare(&mut self, format: AudioFormat);
+
+    // Processes interleaved samples in place
+    fn process(&mut self, samples: &mut [f32]);
+
+    fn reset(&mut self);
+
+    // Frames the output lags the input by
+    fn latency_frames(&self) -> usize {
+        0
+    }
+
+    // Takes parameters of the processor's own kind; others are refused and change nothing
+    fn set_parameters(&mut self, parameters: &ProcessorParameters) -> Result<(), String>;
+}
+
+// Kind and parameters of one processor of a chain
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProcessorParameters {
+    Gain { gain_db: f32 },
+    // Noise gate or downward expander
+    Gate(GateSettings),
+    Equalizer(EqPreset),
+    Compressor(CompressorSettings),
+    Multiband(MultibandSettings),
+    Clipper(ClipperSettings),
+    // True-peak limiter
+    Limiter { ceiling_dbtp: f32 },
+}
+
+impl ProcessorParameters {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProcessorParameters::Gain { .. } => "gain",
+            ProcessorParameters::Gate(_) => "gate",
+            ProcessorParameters::Equalizer(_) => "equalizer",
+            ProcessorParameters::Compressor(_) => "compressor",
+            ProcessorParameters::Multiband(_) => "multiband",
+            ProcessorParameters::Clipper(_) => "clipper",
+            ProcessorParameters::Limiter { .. } => "limiter",
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ProcessorParameters::Gain { gain_db } => {
+                if !(-MAX_GAIN_DB..=MAX_GAIN_DB).contains(gain_db) {
+                    return Err(format!("Gain {} dB is outside {}..={} dB", gain_db, -MAX_GAIN_DB, MAX_GAIN_DB));
+                }
+                Ok(())
+            }
+            ProcessorParameters::Gate(settings) => settings.validate(),
+            ProcessorParameters::Equalizer(preset) => preset.validate(),
+            ProcessorParameters::Compressor(settings) => settings.validate(),
+            ProcessorParameters::Multiband(settings) => settings.validate(),
+            ProcessorParameters::Clipper(settings) => settings.validate(),
+            ProcessorParameters::Limiter { ceiling_dbtp } => {
+                if !(MIN_TRUE_PEAK_CEILING_DBTP..=MAX_TRUE_PEAK_CEILING_DBTP).contains(ceiling_dbtp) {
+                    return Err(format!(
+                        "Limiter ceiling {} dBTP is outside {}..={} dBTP",
+                        ceiling_dbtp, MIN_TRUE_PEAK_CEILING_DBTP, MAX_TRUE_PEAK_CEILING_DBTP
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Processor of this kind for `format`, running these parameters
+    fn processor(&self, format: AudioFormat) -> Box<dyn Processor> {
+        let mut processor: Box<dyn Processor> = match self {
+            ProcessorParameters::Gain { .. } => Box::new(Gain::new(0.0)),
+            ProcessorParameters::Gate(settings) => Box::new(NoiseGate::new(format, *settings)),
+            ProcessorParameters::Equalizer(_) => Box::new(Equalizer::new(format)),
+            ProcessorParameters::Compressor(_) | ProcessorParameters::Multiband(_) => Box::new(Dynamics::new(format)),
+            ProcessorParameters::Clipper(_) => Box::new(Clipper::new(format)),
+            ProcessorParameters::Limiter { ceiling_dbtp } => {
+                Box::new(LookaheadLimiter::new(format, LimiterSettings::true_peak(*ceiling_dbtp)))
+            }
+        };
+        // Cannot fail: the processor was made for this kind
+        let _ = processor.set_parameters(self);
+        processor
+    }
+
+    fn refused_by(&self, processor: &str) -> Result<(), String> {
+        Err(format!("A {} processor cannot take {} parameters", processor, self.kind()))
+    }
+}
+
+// Fixed gain
+pub struct Gain {
+    gain: f32,
+}
+
+impl Gain {
+    pub fn new(gain_db: f32) -> Self {
+        Self { gain: db_to_linear(gain_db) }
+    }
+}
+
+impl Processor for Gain {
+    fn prepare(&mut self, _format: AudioFormat) {}
+
+    fn process(&mut self, samples: &mut [f32]) {
+        if self.gain != 1.0 {
+            simd::apply_gain(samples, self.gain);
+        }
+    }
+
+    fn reset(&mut self) {}
+
+    fn set_parameters(&mut self, parameters: &ProcessorParameters) -> Result<(), String> {
+        match parameters {
+            ProcessorParameters::Gain { gain_db } => {
+                self.gain = db_to_linear(*gain_db);
+                Ok(())
+            }
+            _ => parameters.refused_by("gain"),
+        }
+    }
+}
+
+impl Processor for NoiseGate {
+    fn prepare(&mut self, format: AudioFormat) {
+        *self = NoiseGate::new(format, self.settings());
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        NoiseGate::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        NoiseGate::reset(self);
+    }
+
+    fn set_parameters(&mut self, parameters: &ProcessorParameters) -> Result<(), String> {
+        match parameters {
+            ProcessorParameters::Gate(settings) => {
+                self.set_settings(*settings);
+                Ok(())
+            }
+            _ => parameters.refused_by("gate"),
+        }
+    }
+}
+
+impl Processor for Equalizer {
+    fn prepare(&mut self, format: AudioFormat) {
+        *self = Equalizer::new(format);
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        Equalizer::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        Equalizer::reset(self);
+    }
+
+    // A new preset crossfades in
+    fn set_parameters(&mut self, parameters: &ProcessorParameters) -> Result<(), String> {
+        match parameters {
+            ProcessorParameters::Equalizer(preset) => {
+                self.set_preset(preset);
+                Ok(())
+            }
+            _ => parameters.refused_by("equalizer"),
+        }
+    }
+}
+
+// Runs compressor and multiband parameters alike
+impl Processor for Dynamics {
+    fn prepare(&mut self, format: AudioFormat) {
+        *self = Dynamics::new(format);
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        Dynamics::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        Dynamics::reset(self);
+    }
+
+    fn set_parameters(&mut self, parameters: &ProcessorParameters) -> Result<(), String> {
+        match parameters {
+            ProcessorParameters::Compressor(settings) => self.set_mode(DynamicsMode::Compressor(*settings)),
+            ProcessorParameters::Multiband(settings) => self.set_mode(DynamicsMode::Multiband(*settings)),
+            _ => return parameters.refused_by("dynamics"),
+        }
+        Ok(())
+    }
+}
+
+impl Processor for Clipper {
+    fn prepare(&mut self, format: AudioFormat) {
+        *self = Clipper::new(format);
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        Clipper::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        Clipper::reset(self);
+    }
+
+    fn latency_frames(&self) -> usize {
+        Clipper::latency_frames(self)
+    }
+
+    fn set_parameters(&mut self, parameters: &ProcessorParameters) -> Result<(), String> {
+        match parameters {
+            ProcessorParameters::Clipper(settings) => {
+                self.set_settings(Some(*settings));
+                Ok(())
+            }
+            _ => parameters.refused_by("clipper"),
+        }
+    }
+}
+
+impl Processor for LookaheadLimiter {
+    fn prepare(&mut self, format: AudioFormat) {
+        *self = LookaheadLimiter::new(format, self.settings());
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        LookaheadLimiter::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        LookaheadLimiter::reset(self);
+    }
+
+    fn latency_frames(&self) -> usize {
+        LookaheadLimiter::latency_frames(self)
+    }
+
+    fn set_parameters(&mut self, parameters: &ProcessorParameters) -> Result<(), String> {
+        match parameters {
+            ProcessorParameters::Limiter { ceiling_dbtp } => {
+                self.set_ceiling(db_to_linear(*ceiling_dbtp));
+                Ok(())
+            }
+            _ => parameters.refused_by("limiter"),
+        }
+    }
+}
+
+// Ordered processors of a chain, as saved with a session's settings and restored from them
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct ChainDescription {
+    pub processors: Vec<ProcessorParameters>,
+}
+
+impl ChainDescription {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.processors.len() > MAX_CHAIN_PROCESSORS {
+            return Err(format!(
+                "{} processors exceed the maximum of {}",
+                self.processors.len(),
+                MAX_CHAIN_PROCESSORS
+            ));
+        }
+        for (index, parameters) in self.processors.iter().enumerate() {
+            parameters.validate().map_err(|e| format!("Processor {} ({}): {}", index + 1, parameters.kind(), e))?;
+        }
+        Ok(())
+    }
+
+    // Allocates; build chains before they reach the audio thread
+    pub fn build(&self, format: AudioFormat) -> ProcessorChain {
+        ProcessorChain {
+            format,
+            processors: self.processors.iter().map(|parameters| parameters.processor(format)).collect(),
+            description: self.clone(),
+        }
+    }
+}
+
+// Processors run one after the other, in the order of the description they were built from
+pub struct ProcessorChain {
+    format: AudioFormat,
+    description: ChainDescription,
+    processors: Vec<Box<dyn Processor>>,
+}
+
+impl ProcessorChain {
+    // Passes the audio through untouched
+    pub fn empty(format: AudioFormat) -> Self {
+        ChainDescription::default().build(format)
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    pub fn description(&self) -> &ChainDescription {
+        &self.description
+    }
+
+    pub fn len(&self) -> usize {
+        self.processors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    // Resizes every processor for `format` and loads its parameters again; may allocate
+    pub fn prepare(&mut self, format: AudioFormat) {
+        self.format = format;
+        for (processor, parameters) in self.processors.iter_mut().zip(&self.description.processors) {
+            processor.prepare(format);
+            let _ = processor.set_parameters(parameters);
+        }
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for processor in &mut self.processors {
+            processor.process(samples);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.processors.iter_mut().for_each(|processor| processor.reset());
+    }
+
+    // Latency of the whole chain, the sum of its processors'
+    pub fn latency_frames(&self) -> usize {
+        self.processors.iter().map(|processor| processor.latency_frames()).sum()
+    }
+}
+
+// Latency a session's processing adds, as last reported by its pipeline
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct LatencyReport {
+    pub frames: usize,
+    pub milliseconds: f32,
+}
+
+impl LatencyReport {
+    pub fn new(frames: usize, sample_rate: u32) -> Self {
+        Self { frames, milliseconds: frames as f32 * 1000.0 / sample_rate.max(1) as f32 }
+    }
+}
+
+// What the control keeps for one processor running its chain
+struct Attachment {
+    format: AudioFormat,
+    // Built for the current description off the audio thread, waiting to be swapped in
+    prepared: Option<ProcessorChain>,
+    // The chain the last swap replaced. It is dropped here with the next change, so the
+    // audio thread never frees memory.
+    retired: Option<ProcessorChain>,
+}
+
+struct ChainState {
+    description: ChainDescription,
+    // Processors running the chain, by attachment ID; each gets a chain of its own
+    attachments: HashMap<u32, Attachment>,
+    next_id: u32,
+}
+
+struct SharedChain {
+    // The audio thread only try-locks this after `version` moved
+    state: Mutex<ChainState>,
+    version: AtomicU32,
+}
+
+// Chain description shared between the manager and the processors running it. Changing it
+// builds a new chain for every attached processor right away, on the caller's thread;
+// each processor swaps its chain in with its next block.
+#[derive(Clone)]
+pub struct ChainControl(Arc<SharedChain>);
+
+impl std::fmt::Debug for ChainControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ChainControl").field(&self.description()).finish()
+    }
+}
+
+// A processor's link to a ChainControl; dropping it detaches the processor
+pub struct ChainAttachment {
+    shared: Arc<SharedChain>,
+    id: u32,
+    // Version of the description the processor's chain was built for
+    seen: u32,
+}
+
+impl Drop for ChainAttachment {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner()).attachments.remove(&self.id);
+    }
+}
+
+impl ChainControl {
+    pub fn new(description: ChainDescription) -> Self {
+        Self(Arc::new(SharedChain {
+            state: Mutex::new(ChainState { description, attachments: HashMap::new(), next_id: 0 }),
+            version: AtomicU32::new(0),
+        }))
+    }
+
+    pub fn description(&self) -> ChainDescription {
+        self.0.state.lock().unwrap_or_else(|e| e.into_inner()).description.clone()
+    }
+
+    pub fn set_description(&self, description: ChainDescription) {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        for attachment in state.attachments.values_mut() {
+            attachment.retired = None;
+            attachment.prepared = Some(description.build(attachment.format));
+        }
+        state.description = description;
+        self.0.version.fetch_add(1, Ordering::Release);
+    }
+
+    // The current chain, built for a processor running in `format`, and the attachment to
+    // pass to `sync` from then on. Later chains are built for that format.
+    pub fn attach(&self, format: AudioFormat) -> (ProcessorChain, ChainAttachment) {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        let id = state.next_id;
+        state.next_id = state.next_id.wrapping_add(1);
+        state.attachments.insert(id, Attachment { format, prepared: None, retired: None });
+        let attachment = ChainAttachment {
+            shared: Arc::clone(&self.0),
+            id,
+            seen: self.0.version.load(Ordering::Acquire),
+        };
+        (state.description.build(format), attachment)
+    }
+
+    // Swaps the attached processor's newest chain in if the description changed since it
+    // last did; never blocks, allocates or frees. A swap that cannot happen yet is tried
+    // again with the next call.
+    pub fn sync(&self, chain: &mut ProcessorChain, attachment: &mut ChainAttachment) {
+        let version = self.0.version.load(Ordering::Acquire);
+        if version == attachment.seen {
+            return;
+        }
+        let Ok(mut state) = self.0.state.try_lock() else {
+            return;
+        };
+        let Some(attached) = state.attachments.get_mut(&attachment.id) else {
+            return;
+        };
+        if let Some(prepared) = attached.prepared.take() {
+            attached.retired = Some(std::mem::replace(chain, prepared));
+        }
+        attachment.seen = version;
+    }
+}
+
+impl Default for ChainControl {
+    fn default() -> Self {
+        Self::new(ChainDescription::default())
+    }
+}
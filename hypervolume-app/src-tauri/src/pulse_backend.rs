@@ -0,0 +1,539 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::thread;
+
+use crate::audio_manager::{
+    assign_session_ids,
+    device_not_found,
+    diff_sessions,
+    session_not_found,
+    validate_channel_volumes,
+    AudioBackend,
+    AudioDevice,
+    AudioSession,
+    ChannelPosition,
+    DeviceFlow,
+    DeviceRole,
+    DeviceState,
+    PeakLevels,
+    SessionEvent,
+    SessionIdentity,
+    SessionState,
+    SessionSubscribers,
+    VolumeRange,
+};
+
+// PulseAudio's "100%" volume, see PA_VOLUME_NORM in pulse/volume.h
+const PA_VOLUME_NORM: f32 = 65536.0;
+
+// Software volume spans silence (PA_DECIBEL_MININFTY) to PA_VOLUME_NORM in continuous steps
+const PULSE_VOLUME_RANGE: VolumeRange = VolumeRange {
+    min_db: -200.0,
+    max_db: 0.0,
+    step_db: 0.0,
+};
+
+// One playback stream as reported by `pactl list sink-inputs`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkInput {
+    pub index: u32,
+    pub process_id: u32,
+    pub application_name: String,
+    pub media_role: String,
+    pub executable_path: String,
+    // Freedesktop icon theme name, e.g. "firefox"
+    pub icon_name: String,
+    // Loudest channel, which is what pavucontrol shows as the stream volume
+    pub volume: f32,
+    pub channel_map: Vec<ChannelPosition>,
+    pub channel_volumes: Vec<f32>,
+    pub muted: bool,
+    // Paused streams are "corked" in PulseAudio terms
+    pub corked: bool,
+    // Index of the sink the stream plays on
+    pub sink: u32,
+}
+
+impl SinkInput {
+    // PulseAudio has no persistent stream identifier, so the application name and media role
+    // stand in for it; the sink-input index is unique while the stream lives
+    pub fn identity(&self) -> SessionIdentity {
+        SessionIdentity {
+            session_identifier: format!("pulse|{}|{}", self.application_name, self.media_role),
+            instance_identifier: format!("{:010}", self.index),
+            executable_path: self.executable_path.clone(),
+        }
+    }
+
+    // PulseAudio only stores absolute channel levels. Relative to the loudest one they match
+    // the channel volumes of the other backends, which scale the session volume.
+    pub fn relative_channel_volumes(&self) -> Vec<f32> {
+        if self.volume > 0.0 {
+            self.channel_volumes.iter().map(|channel| channel / self.volume).collect()
+        } else {
+            // A silenced stream keeps no balance
+            vec![1.0; self.channel_volumes.len()]
+        }
+    }
+
+    // Absolute levels of relative channel volumes at the stream's current volume. The
+    // loudest channel keeps that volume, so setting a balance never turns the stream down.
+    pub fn absolute_channel_volumes(&self, volumes: &[f32]) -> Vec<f32> {
+        let loudest = volumes.iter().fold(0.0f32, |loudest, channel| loudest.max(*channel));
+        if loudest > 0.0 {
+            volumes.iter().map(|channel| channel / loudest * self.volume).collect()
+        } else {
+            vec![0.0; volumes.len()]
+        }
+    }
+}
+
+// One sink or source as reported by `pactl list sinks` / `pactl list sources`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PulseDevice {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    pub flow: DeviceFlow,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+// PulseAudio backend built on the `pactl` command line client.
+// PipeWire exposes the same interface through pipewire-pulse, so this covers both.
+pub struct PulseBackend {
+    subscribers: Arc<SessionSubscribers>,
+    watcher_running: Arc<AtomicBool>,
+}
+
+impl PulseBackend {
+    pub fn new() -> Self {
+        Self {
+            subscribers: Arc::new(SessionSubscribers::new()),
+            watcher_running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // True when `pactl` is installed and a PulseAudio-compatible server answers
+    pub fn is_available() -> bool {
+        pactl(&["info"]).is_ok()
+    }
+
+    fn find_sink_input(&self, session_id: &str) -> Result<SinkInput, String> {
+        let inputs = list_sink_inputs()?;
+        to_audio_sessions(&inputs, &list_sinks()?)
+            .iter()
+            .position(|session| session.id == session_id)
+            .map(|position| inputs[position].clone())
+            .ok_or_else(|| session_not_found(session_id))
+    }
+
+    fn find_device(&self, device_id: &str) -> Result<PulseDevice, String> {
+        list_devices()?
+            .into_iter()
+            .find(|device| device.name == device_id)
+            .ok_or_else(|| device_not_found(device_id))
+    }
+
+    fn start_watcher(&self) -> Result<(), String> {
+        if self.watcher_running.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let mut child = match Command::new("pactl").arg("subscribe").stdout(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                self.watcher_running.store(false, Ordering::SeqCst);
+                return Err(format!("Failed to run pactl subscribe: {}", e));
+            }
+        };
+        let stdout = child.stdout.take().ok_or("Failed to capture pactl subscribe output")?;
+
+        let subscribers = Arc::clone(&self.subscribers);
+        let watcher_running = Arc::clone(&self.watcher_running);
+        thread::spawn(move || {
+            let mut previous = list_sessions().unwrap_or_default();
+
+            // pactl prints one line per server event, e.g. "Event 'change' on sink-input #42"
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if !subscribers.has_subscribers() {
+                    break;
+                }
+                if !line.contains("sink-input") {
+                    continue;
+                }
+                if let Ok(current) = list_sessions() {
+                    let events = diff_sessions(&previous, &current);
+                    for event in events {
+                        subscribers.publish(event);
+                    }
+                    previous = current;
+                }
+            }
+
+            let _ = child.kill();
+            let _ = child.wait();
+            watcher_running.store(false, Ordering::SeqCst);
+        });
+        Ok(())
+    }
+}
+
+impl Default for PulseBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioBackend for PulseBackend {
+    fn name(&self) -> &'static str {
+        "pulseaudio"
+    }
+
+    fn enumerate_devices(&self) -> Result<Vec<AudioDevice>, String> {
+        let default_sink = pactl(&["get-default-sink"])?;
+        let default_source = pactl(&["get-default-source"])?;
+
+        Ok(to_audio_devices(&list_devices()?, default_sink.trim(), default_source.trim()))
+    }
+
+    fn get_device_volume(&self, device_id: &str) -> Result<f32, String> {
+        Ok(self.find_device(device_id)?.volume)
+    }
+
+    fn set_device_volume(&self, device_id: &str, volume: f32) -> Result<(), String> {
+        let device = self.find_device(device_id)?;
+        let raw_volume = format!("{}", (volume.clamp(0.0, 1.0) * PA_VOLUME_NORM).round() as u32);
+        let command = match device.flow {
+            DeviceFlow::Render => "set-sink-volume",
+            DeviceFlow::Capture => "set-source-volume",
+        };
+        pactl(&[command, &device.name, &raw_volume])?;
+        Ok(())
+    }
+
+    fn get_device_mute(&self, device_id: &str) -> Result<bool, String> {
+        Ok(self.find_device(device_id)?.muted)
+    }
+
+    fn set_device_mute(&self, device_id: &str, muted: bool) -> Result<(), String> {
+        let device = self.find_device(device_id)?;
+        let command = match device.flow {
+            DeviceFlow::Render => "set-sink-mute",
+            DeviceFlow::Capture => "set-source-mute",
+        };
+        pactl(&[command, &device.name, if muted { "1" } else { "0" }])?;
+        Ok(())
+    }
+
+    fn get_device_volume_range(&self, device_id: &str) -> Result<VolumeRange, String> {
+        self.find_device(device_id)?;
+        Ok(PULSE_VOLUME_RANGE)
+    }
+
+    fn enumerate_sessions(&self) -> Result<Vec<AudioSession>, String> {
+        list_sessions()
+    }
+
+    fn get_session_volume(&self, session_id: &str) -> Result<f32, String> {
+        Ok(self.find_sink_input(session_id)?.volume)
+    }
+
+    // Scales every channel so the loudest one lands on `volume`, keeping the balance
+    fn set_session_volume(&self, session_id: &str, volume: f32) -> Result<(), String> {
+        let input = self.find_sink_input(session_id)?;
+        let volume = volume.clamp(0.0, 1.0);
+        let volumes: Vec<f32> = if input.volume > 0.0 {
+            input.channel_volumes.iter().map(|channel| channel * volume / input.volume).collect()
+        } else {
+            vec![volume; input.channel_volumes.len().max(1)]
+        };
+        set_sink_input_volumes(input.index, &volumes)
+    }
+
+    // PulseAudio has no session level separate from the channels, so the channel volumes
+    // are converted to and from levels relative to the loudest channel
+    fn get_session_channel_volumes(&self, session_id: &str) -> Result<Vec<f32>, String> {
+        Ok(self.find_sink_input(session_id)?.relative_channel_volumes())
+    }
+
+    fn set_session_channel_volumes(&self, session_id: &str, volumes: &[f32]) -> Result<(), String> {
+        let input = self.find_sink_input(session_id)?;
+        validate_channel_volumes(volumes, input.channel_map.len())?;
+        set_sink_input_volumes(input.index, &input.absolute_channel_volumes(volumes))
+    }
+
+    fn get_session_mute(&self, session_id: &str) -> Result<bool, String> {
+        Ok(self.find_sink_input(session_id)?.muted)
+    }
+
+    fn set_session_mute(&self, session_id: &str, muted: bool) -> Result<(), String> {
+        let input = self.find_sink_input(session_id)?;
+        let mute_flag = if muted { "1" } else { "0" };
+        pactl(&["set-sink-input-mute", &input.index.to_string(), mute_flag])?;
+        Ok(())
+    }
+
+    // pactl has no peak meter; reading levels would mean recording every stream's monitor
+    fn get_peak_levels(&self) -> Result<PeakLevels, String> {
+        Err("Peak metering is not supported by the PulseAudio backend".to_string())
+    }
+
+    fn subscribe(&self) -> Result<Receiver<SessionEvent>, String> {
+        let receiver = self.subscribers.subscribe()?;
+        self.start_watcher()?;
+        Ok(receiver)
+    }
+}
+
+fn pactl(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run pactl: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "pactl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// pactl takes one raw volume per channel, in the stream's channel map order
+fn set_sink_input_volumes(index: u32, volumes: &[f32]) -> Result<(), String> {
+    let index = index.to_string();
+    let raw_volumes: Vec<String> = volumes.iter()
+        .map(|volume| format!("{}", (volume.clamp(0.0, 1.0) * PA_VOLUME_NORM).round() as u32))
+        .collect();
+
+    let mut args = vec!["set-sink-input-volume", index.as_str()];
+    args.extend(raw_volumes.iter().map(String::as_str));
+    pactl(&args)?;
+    Ok(())
+}
+
+fn list_sessions() -> Result<Vec<AudioSession>, String> {
+    Ok(to_audio_sessions(&list_sink_inputs()?, &list_sinks()?))
+}
+
+fn list_sinks() -> Result<Vec<PulseDevice>, String> {
+    parse_devices(&pactl(&["--format=json", "list", "sinks"])?, DeviceFlow::Render)
+}
+
+// Sinks followed by sources
+fn list_devices() -> Result<Vec<PulseDevice>, String> {
+    let mut devices = list_sinks()?;
+    devices.extend(parse_devices(&pactl(&["--format=json", "list", "sources"])?, DeviceFlow::Capture)?);
+    Ok(devices)
+}
+
+fn list_sink_inputs() -> Result<Vec<SinkInput>, String> {
+    // JSON output needs pactl 16 or newer
+    let mut inputs = parse_sink_inputs(&pactl(&["--format=json", "list", "sink-inputs"])?)?;
+
+    // Prefer the real executable over the binary name the client reported
+    for input in inputs.iter_mut().filter(|input| input.process_id != 0) {
+        if let Ok(path) = std::fs::read_link(format!("/proc/{}/exe", input.process_id)) {
+            input.executable_path = path.to_string_lossy().into_owned();
+        }
+    }
+    Ok(inputs)
+}
+
+// Sink-inputs only know their sink by index; the device ID is the sink name
+pub fn to_audio_sessions(inputs: &[SinkInput], sinks: &[PulseDevice]) -> Vec<AudioSession> {
+    let identities: Vec<SessionIdentity> = inputs.iter().map(SinkInput::identity).collect();
+
+    inputs.iter()
+        .zip(assign_session_ids(&identities))
+        .map(|(input, id)| AudioSession {
+            id,
+            display_name: input.application_name.clone(),
+            volume: input.volume,
+            muted: input.muted,
+            process_id: input.process_id,
+            executable_path: input.executable_path.clone(),
+            icon_path: input.icon_name.clone(),
+            state: if input.corked { SessionState::Inactive } else { SessionState::Active },
+            device_id: sinks.iter()
+                .find(|sink| sink.index == input.sink)
+                .map(|sink| sink.name.clone())
+                .unwrap_or_default(),
+            channel_count: input.channel_map.len() as u32,
+            channel_layout: input.channel_map.clone(),
+        })
+        .collect()
+}
+
+// PulseAudio has a single default per direction, so it holds every role
+pub fn to_audio_devices(devices: &[PulseDevice], default_sink: &str, default_source: &str) -> Vec<AudioDevice> {
+    devices.iter()
+        .map(|device| {
+            let default_name = match device.flow {
+                DeviceFlow::Render => default_sink,
+                DeviceFlow::Capture => default_source,
+            };
+            let default_roles = if device.name == default_name {
+                vec![DeviceRole::Console, DeviceRole::Multimedia, DeviceRole::Communications]
+            } else {
+                Vec::new()
+            };
+
+            AudioDevice {
+                id: device.name.clone(),
+                name: device.description.clone(),
+                flow: device.flow,
+                // pactl only lists devices that are present
+                state: DeviceState::Active,
+                default_roles,
+            }
+        })
+        .collect()
+}
+
+// Parses `pactl --format=json list sinks` or `list sources`; monitor sources are skipped
+// since they mirror a sink rather than being a separate recording device
+pub fn parse_devices(json: &str, flow: DeviceFlow) -> Result<Vec<PulseDevice>, String> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| format!("Failed to parse pactl output: {}", e))?;
+
+    let entries = value.as_array().ok_or("Unexpected pactl output: expected a JSON array")?;
+
+    let mut devices = Vec::new();
+    for entry in entries {
+        if entry["properties"]["device.class"].as_str() == Some("monitor") {
+            continue;
+        }
+
+        let index = entry["index"].as_u64().ok_or("Missing index in device")? as u32;
+        let name = entry["name"].as_str().ok_or("Missing name in device")?.to_string();
+        let description = entry["description"].as_str().unwrap_or(&name).to_string();
+
+        devices.push(PulseDevice {
+            index,
+            name,
+            description,
+            flow,
+            volume: parse_volume(entry),
+            muted: entry["mute"].as_bool().unwrap_or(false),
+        });
+    }
+    Ok(devices)
+}
+
+pub fn parse_sink_inputs(json: &str) -> Result<Vec<SinkInput>, String> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| format!("Failed to parse pactl output: {}", e))?;
+
+    let entries = value.as_array().ok_or("Unexpected pactl output: expected a JSON array")?;
+
+    entries.iter().map(parse_sink_input).collect()
+}
+
+fn parse_sink_input(entry: &serde_json::Value) -> Result<SinkInput, String> {
+    let index = entry["index"]
+        .as_u64()
+        .ok_or("Missing index in sink-input")? as u32;
+
+    let properties = &entry["properties"];
+
+    // Property values are always strings; clients that do not report a PID get 0
+    let process_id = properties["application.process.id"]
+        .as_str()
+        .and_then(|pid| pid.parse().ok())
+        .unwrap_or(0);
+
+    let application_name = ["application.name", "application.process.binary", "media.name"]
+        .iter()
+        .find_map(|key| properties[*key].as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let media_role = properties["media.role"].as_str().unwrap_or_default().to_string();
+
+    let executable_path = properties["application.process.binary"].as_str().unwrap_or_default().to_string();
+
+    let icon_name = properties["application.icon_name"].as_str().unwrap_or_default().to_string();
+
+    let (channel_map, channel_volumes) = parse_channels(entry);
+
+    let volume = parse_volume(entry);
+
+    let muted = entry["mute"].as_bool().unwrap_or(false);
+
+    let corked = entry["corked"].as_bool().unwrap_or(false);
+
+    let sink = entry["sink"].as_u64().unwrap_or_default() as u32;
+
+    Ok(SinkInput {
+        index,
+        process_id,
+        application_name,
+        media_role,
+        executable_path,
+        icon_name,
+        volume,
+        channel_map,
+        channel_volumes,
+        muted,
+        corked,
+        sink,
+    })
+}
+
+// Reports the loudest channel, matching pa_cvolume_max()
+fn parse_volume(entry: &serde_json::Value) -> f32 {
+    entry["volume"]
+        .as_object()
+        .map(|channels| {
+            channels.values()
+                .filter_map(|channel| channel["value"].as_f64())
+                .fold(0.0, f64::max) as f32 / PA_VOLUME_NORM
+        })
+        .unwrap_or(1.0)
+}
+
+// Channel positions and levels in the stream's channel map order.
+// The volume object is keyed by channel name and does not keep that order itself.
+fn parse_channels(entry: &serde_json::Value) -> (Vec<ChannelPosition>, Vec<f32>) {
+    let names: Vec<String> = match entry["channel_map"].as_str() {
+        Some(channel_map) if !channel_map.is_empty() => channel_map.split(',').map(str::to_string).collect(),
+        _ => entry["volume"].as_object().map(|channels| channels.keys().cloned().collect()).unwrap_or_default(),
+    };
+
+    names.iter()
+        .map(|name| {
+            let volume = entry["volume"][name.as_str()]["value"]
+                .as_f64()
+                .map(|value| value as f32 / PA_VOLUME_NORM)
+                .unwrap_or(1.0);
+            (channel_position(name), volume)
+        })
+        .unzip()
+}
+
+// Channel names from pa_channel_position_to_string()
+fn channel_position(name: &str) -> ChannelPosition {
+    match name {
+        "mono" | "front-center" => ChannelPosition::FrontCenter,
+        "front-left" => ChannelPosition::FrontLeft,
+        "front-right" => ChannelPosition::FrontRight,
+        "lfe" => ChannelPosition::LowFrequency,
+        "rear-left" => ChannelPosition::BackLeft,
+        "rear-right" => ChannelPosition::BackRight,
+        "rear-center" => ChannelPosition::BackCenter,
+        "front-left-of-center" => ChannelPosition::FrontLeftOfCenter,
+        "front-right-of-center" => ChannelPosition::FrontRightOfCenter,
+        "side-left" => ChannelPosition::SideLeft,
+        "side-right" => ChannelPosition::SideRight,
+        _ => ChannelPosition::Other,
+    }
+}
@@ -0,0 +1,225 @@
+use crate::simd;
+
+// How much work the resampler puts into suppressing aliasing and imaging
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResamplerQuality {
+    // 60 dB stopband, flat to 80% of the lower Nyquist frequency
+    Low,
+    // 90 dB stopband, flat to 90%
+    Medium,
+    // 120 dB stopband, flat to 92% (20.3 kHz at 44.1 kHz)
+    #[default]
+    High,
+}
+
+impl ResamplerQuality {
+    fn stopband_attenuation_db(self) -> f64 {
+        match self {
+            ResamplerQuality::Low => 60.0,
+            ResamplerQuality::Medium => 90.0,
+            ResamplerQuality::High => 120.0,
+        }
+    }
+
+    // Upper passband edge as a fraction of the lower of the two Nyquist frequencies; the
+    // stopband starts right at it
+    fn passband(self) -> f64 {
+        match self {
+            ResamplerQuality::Low => 0.8,
+            ResamplerQuality::Medium => 0.9,
+            ResamplerQuality::High => 0.92,
+        }
+    }
+
+    // Positions between two input samples the filter is tabulated at. The filter between
+    // them is interpolated linearly, which must stay below the stopband.
+    fn phases(self) -> usize {
+        match self {
+            ResamplerQuality::Low => 64,
+            ResamplerQuality::Medium => 256,
+            ResamplerQuality::High => 1024,
+        }
+    }
+}
+
+fn greatest_common_divisor(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// Zeroth-order modified Bessel function of the first kind, for the Kaiser window
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+    while term > sum * 1e-17 {
+        term *= (x / (2.0 * k)) * (x / (2.0 * k));
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+// Converts interleaved audio between any two sample rates with a Kaiser-windowed sinc
+// (band-limited interpolation). The filter is tabulated at `phases` positions between two
+// input samples; every output sample interpolates between the two nearest rows. Output
+// sample n lies at input time n * from / to, so the stream keeps its timing: the first
+// output is the first input instant, and `flush` emits the tail. The newest `half_taps`
+// input frames are held back until the filter has seen enough of them.
+pub struct Resampler {
+    channels: usize,
+    // Input frames per `step_up` output frames, reduced
+    step_up: u64,
+    step_down: u64,
+    half_taps: usize,
+    taps: usize,
+    phases: usize,
+    // Row p holds the filter for an output p / phases of a sample past the input
+    // `half_taps` frames back, newest input first; phases + 1 rows
+    table: Vec<f32>,
+    // Last `taps` samples of every channel, newest first, stored twice in a row so the
+    // filter always reads one contiguous slice
+    history: Vec<f32>,
+    position: usize,
+    // Input frames pushed so far, including the zeros of `flush`
+    pushed: u64,
+    // Real input frames; outputs past them are not emitted
+    input_frames: u64,
+    // Time of the next output frame, in 1 / step_up input frames
+    next_output: u64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32, channels: usize, quality: ResamplerQuality) -> Result<Self, String> {
+        if from_rate == 0 || to_rate == 0 || channels == 0 {
+            return Err(format!("Invalid resampler: {} Hz to {} Hz, {} channels", from_rate, to_rate, channels));
+        }
+        let divisor = greatest_common_divisor(from_rate as u64, to_rate as u64);
+        let step_up = to_rate as u64 / divisor;
+        let step_down = from_rate as u64 / divisor;
+
+        // Band edges in cycles per input sample; downsampling moves them down to the
+        // output's Nyquist frequency
+        let ratio = (to_rate as f64 / from_rate as f64).min(1.0);
+        let passband = quality.passband();
+        let transition = (1.0 - passband) * 0.5 * ratio;
+        let cutoff = (1.0 + passband) * 0.25 * ratio;
+        let attenuation = quality.stopband_attenuation_db();
+        // Kaiser's estimates of the window shape and length a stopband needs
+        let beta = 0.1102 * (attenuation - 8.7);
+        let length = (attenuation - 7.95) / (14.36 * transition);
+        let half_taps = (length / 2.0).ceil() as usize;
+        let taps = 2 * half_taps;
+        let phases = quality.phases();
+
+        let mut table = Vec::with_capacity((phases + 1) * taps);
+        for phase in 0..=phases {
+            let offset = phase as f64 / phases as f64;
+            let row: Vec<f64> = (0..taps)
+                .map(|k| {
+                    let t = k as f64 - half_taps as f64 + offset;
+                    let x = 2.0 * cutoff * t;
+                    let sinc = if x == 0.0 { 1.0 } else { (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x) };
+                    let edge = t / half_taps as f64;
+                    let window = if edge.abs() >= 1.0 {
+                        0.0
+                    } else {
+                        bessel_i0(beta * (1.0 - edge * edge).sqrt()) / bessel_i0(beta)
+                    };
+                    sinc * window
+                })
+                .collect();
+            // Unity gain at DC for every row
+            let sum: f64 = row.iter().sum();
+            table.extend(row.iter().map(|tap| (tap / sum) as f32));
+        }
+
+        Ok(Self {
+            channels,
+            step_up,
+            step_down,
+            half_taps,
+            taps,
+            phases,
+            table,
+            history: vec![0.0; channels * 2 * taps],
+            position: 0,
+            pushed: 0,
+            input_frames: 0,
+            next_output: 0,
+        })
+    }
+
+    // Output frames `process` may write for `input_frames` input frames, at most
+    pub fn max_output_frames(&self, input_frames: usize) -> usize {
+        (input_frames as u64 * self.step_up).div_ceil(self.step_down) as usize + 1
+    }
+
+    // Output frames `flush` may write, at most
+    pub fn max_flush_frames(&self) -> usize {
+        self.max_output_frames(self.half_taps)
+    }
+
+    // Resamples interleaved `input` and returns the number of samples written to `output`,
+    // which must hold max_output_frames of the input's frames
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> usize {
+        let mut written = 0;
+        for frame in input.chunks_exact(self.channels) {
+            self.input_frames += 1;
+            self.push(|channel| frame[channel]);
+            written = self.emit(output, written, u64::MAX);
+        }
+        written
+    }
+
+    // Emits the outputs the held-back input frames still owe and returns the number of
+    // samples written to `output`, which must hold max_flush_frames
+    pub fn flush(&mut self, output: &mut [f32]) -> usize {
+        let end = self.input_frames * self.step_up;
+        let mut written = 0;
+        while self.next_output < end && written + self.channels <= output.len() {
+            self.push(|_| 0.0);
+            written = self.emit(output, written, end);
+        }
+        written
+    }
+
+    fn push(&mut self, sample: impl Fn(usize) -> f32) {
+        self.position = (self.position + self.taps - 1) % self.taps;
+        for channel in 0..self.channels {
+            let history = &mut self.history[channel * 2 * self.taps..(channel + 1) * 2 * self.taps];
+            let value = sample(channel);
+            history[self.position] = value;
+            history[self.position + self.taps] = value;
+        }
+        self.pushed += 1;
+    }
+
+    // Writes every output frame before `end` the history reaches `half_taps` frames past
+    fn emit(&mut self, output: &mut [f32], mut written: usize, end: u64) -> usize {
+        while self.next_output < end
+            && self.next_output / self.step_up + (self.half_taps as u64) < self.pushed
+            && written + self.channels <= output.len()
+        {
+            let scaled = (self.next_output % self.step_up) * self.phases as u64;
+            let row = (scaled / self.step_up) as usize;
+            let fraction = (scaled % self.step_up) as f32 / self.step_up as f32;
+            let before = &self.table[row * self.taps..(row + 1) * self.taps];
+            let after = &self.table[(row + 1) * self.taps..(row + 2) * self.taps];
+
+            for channel in 0..self.channels {
+                let start = channel * 2 * self.taps + self.position;
+                let recent = &self.history[start..start + self.taps];
+                let a = simd::dot(before, recent);
+                let b = simd::dot(after, recent);
+                output[written] = a + (b - a) * fraction;
+                written += 1;
+            }
+            self.next_output += self.step_down;
+        }
+        written
+    }
+}
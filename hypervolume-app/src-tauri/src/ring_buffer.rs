@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Single-producer single-consumer ring of f32 samples. Neither side ever blocks, locks
+// or allocates, so both ends can be used from a realtime audio thread.
+struct Ring {
+    // Samples stored as f32 bits, which keeps the ring free of unsafe code
+    slots: Box<[AtomicU32]>,
+    // Total samples ever written and read; their difference is the fill level
+    written: AtomicUsize,
+    read: AtomicUsize,
+}
+
+pub struct RingProducer {
+    ring: Arc<Ring>,
+}
+
+pub struct RingConsumer {
+    ring: Arc<Ring>,
+}
+
+pub fn ring_buffer(capacity: usize) -> (RingProducer, RingConsumer) {
+    let ring = Arc::new(Ring {
+        slots: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+        written: AtomicUsize::new(0),
+        read: AtomicUsize::new(0),
+    });
+
+    (RingProducer { ring: Arc::clone(&ring) }, RingConsumer { ring })
+}
+
+impl Ring {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn len(&self) -> usize {
+        self.written.load(Ordering::Acquire).wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+}
+
+impl RingProducer {
+    #[cfg(test)]
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+
+    // Room left for `push`
+    pub fn free(&self) -> usize {
+        self.ring.capacity() - self.ring.len()
+    }
+
+    // Appends as many of `samples` as fit and returns how many that was
+    pub fn push(&mut self, samples: &[f32]) -> usize {
+        let written = self.ring.written.load(Ordering::Relaxed);
+        let read = self.ring.read.load(Ordering::Acquire);
+        let count = samples.len().min(self.ring.capacity() - written.wrapping_sub(read));
+
+        for (offset, sample) in samples[..count].iter().enumerate() {
+            let slot = written.wrapping_add(offset) % self.ring.capacity();
+            self.ring.slots[slot].store(sample.to_bits(), Ordering::Relaxed);
+        }
+        // Publishes the samples stored above to the consumer
+        self.ring.written.store(written.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+impl RingConsumer {
+    #[cfg(test)]
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+
+    // Samples ready for `pop`
+    pub fn available(&self) -> usize {
+        self.ring.len()
+    }
+
+    // Fills the start of `samples` and returns how many were read
+    pub fn pop(&mut self, samples: &mut [f32]) -> usize {
+        let read = self.ring.read.load(Ordering::Relaxed);
+        let written = self.ring.written.load(Ordering::Acquire);
+        let count = samples.len().min(written.wrapping_sub(read));
+
+        for (offset, sample) in samples[..count].iter_mut().enumerate() {
+            let slot = read.wrapping_add(offset) % self.ring.capacity();
+            *sample = f32::from_bits(self.ring.slots[slot].load(Ordering::Relaxed));
+        }
+        // Hands the slots read above back to the producer
+        self.ring.read.store(read.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    // Drops everything currently queued and returns how many samples that was
+    pub fn clear(&mut self) -> usize {
+        let read = self.ring.read.load(Ordering::Relaxed);
+        let written = self.ring.written.load(Ordering::Acquire);
+        self.ring.read.store(written, Ordering::Release);
+        written.wrapping_sub(read)
+    }
+}
@@ -0,0 +1,203 @@
+use crate::audio_endpoints::AudioFormat;
+
+// wFormatTag values, see mmreg.h
+pub const WAVE_FORMAT_PCM: u16 = 1;
+pub const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+pub const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+// Noise-shaping filter on the quantization error, newest error first (Wannamaker's
+// three-tap psychoacoustic filter). It moves the noise from the low and middle frequencies,
+// where hearing is most sensitive, up toward the top of the band: -12 dB at DC, +11 dB at
+// the Nyquist frequency.
+const NOISE_SHAPING: [f32; 3] = [1.623, -0.982, 0.109];
+
+// The error fed back, in LSB, is capped so clipped samples cannot make the filter ring
+const MAX_SHAPED_ERROR: f32 = 2.0;
+
+// Encoding of one sample of an interleaved stream, little-endian. Integers map
+// -2^(bits-1)..2^(bits-1) onto -1.0..1.0; f32 passes through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleFormat {
+    I16,
+    // Packed into three bytes
+    I24,
+    // Also 24 valid bits in a 32-bit container; the low byte then goes unused
+    I32,
+    F32,
+}
+
+impl SampleFormat {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            SampleFormat::I16 => 2,
+            SampleFormat::I24 => 3,
+            SampleFormat::I32 | SampleFormat::F32 => 4,
+        }
+    }
+
+    pub fn bits_per_sample(self) -> u16 {
+        self.bytes_per_sample() as u16 * 8
+    }
+
+    pub fn format_tag(self) -> u16 {
+        match self {
+            SampleFormat::F32 => WAVE_FORMAT_IEEE_FLOAT,
+            _ => WAVE_FORMAT_PCM,
+        }
+    }
+}
+
+// Parses a WAVEFORMATEX, or the WAVEFORMATEXTENSIBLE it starts, as found in the fmt chunk
+// of a WAV file or returned by IAudioClient::GetMixFormat
+pub fn parse_wave_format(bytes: &[u8]) -> Result<(AudioFormat, SampleFormat), String> {
+    if bytes.len() < 16 {
+        return Err("Wave format is too short".to_string());
+    }
+
+    let read_u16 = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+    let mut format_tag = read_u16(0);
+    let channels = read_u16(2);
+    let sample_rate = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    let bits_per_sample = read_u16(14);
+
+    // The first two bytes of the extensible sub-format GUID hold the actual format tag
+    if format_tag == WAVE_FORMAT_EXTENSIBLE && bytes.len() >= 26 {
+        format_tag = read_u16(24);
+    }
+
+    let sample_format = match (format_tag, bits_per_sample) {
+        (WAVE_FORMAT_PCM, 16) => SampleFormat::I16,
+        (WAVE_FORMAT_PCM, 24) => SampleFormat::I24,
+        (WAVE_FORMAT_PCM, 32) => SampleFormat::I32,
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => SampleFormat::F32,
+        _ => return Err(format!("Unsupported wave format: format {} with {} bits", format_tag, bits_per_sample)),
+    };
+    if channels == 0 || sample_rate == 0 {
+        return Err(format!("Invalid wave format: {} Hz, {} channels", sample_rate, channels));
+    }
+
+    Ok((AudioFormat::new(sample_rate, channels), sample_format))
+}
+
+// Converts whole samples of `bytes` into `output` and returns how many were converted
+pub fn decode(sample_format: SampleFormat, bytes: &[u8], output: &mut [f32]) -> usize {
+    let chunks = bytes.chunks_exact(sample_format.bytes_per_sample());
+    let count = chunks.len().min(output.len());
+    for (sample, bytes) in output.iter_mut().zip(chunks) {
+        *sample = match sample_format {
+            SampleFormat::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            // Into the top three bytes of an i32, which keeps the sign
+            SampleFormat::I24 => i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2_147_483_648.0,
+            SampleFormat::I32 => (i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / 2_147_483_648.0) as f32,
+            SampleFormat::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        };
+    }
+    count
+}
+
+// What is added ahead of the rounding to 16 bits. 24 bits and more are rounded plainly,
+// their error lies below the noise of any converter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dither {
+    // Plain rounding: exact for audio that already has 16 bits, but quiet signals turn
+    // into distortion that follows them
+    Off,
+    // Triangular noise of up to ±1 LSB, which leaves a constant, signal-independent hiss
+    #[default]
+    Tpdf,
+    // TPDF with the noise shaped toward high frequencies, quieter where hearing is most
+    // sensitive and louder overall
+    Shaped,
+}
+
+// Converts f32 samples to a sample format, rounding to the nearest step and clipping to its
+// range.
+pub struct Quantizer {
+    sample_format: SampleFormat,
+    dither: Dither,
+    channels: usize,
+    // Last quantization errors of every channel, newest first, in LSB
+    errors: Vec<[f32; 3]>,
+    // xorshift32 state of the dither
+    random: u32,
+}
+
+impl Quantizer {
+    pub fn new(sample_format: SampleFormat, channels: usize, dither: Dither) -> Self {
+        let channels = channels.max(1);
+        Self {
+            sample_format,
+            dither: if sample_format == SampleFormat::I16 { dither } else { Dither::Off },
+            channels,
+            errors: vec![[0.0; 3]; channels],
+            random: 0x9E37_79B9,
+        }
+    }
+
+    pub fn sample_format(&self) -> SampleFormat {
+        self.sample_format
+    }
+
+    // Uniform in 0..1
+    fn next_random(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        (self.random >> 8) as f32 / (1 << 24) as f32
+    }
+
+    // Converts whole frames of `samples` into `bytes` and returns the number of samples
+    // converted
+    pub fn encode(&mut self, samples: &[f32], bytes: &mut [u8]) -> usize {
+        let bytes_per_sample = self.sample_format.bytes_per_sample();
+        let fit = samples.len().min(bytes.len() / bytes_per_sample);
+        let count = fit - fit % self.channels;
+
+        for (index, (sample, bytes)) in samples[..count].iter().zip(bytes.chunks_exact_mut(bytes_per_sample)).enumerate() {
+            match self.sample_format {
+                SampleFormat::I16 => {
+                    let value = self.quantize_16(*sample, index % self.channels);
+                    bytes.copy_from_slice(&value.to_le_bytes());
+                }
+                SampleFormat::I24 => {
+                    let value = round_to_step(*sample, SampleFormat::I24);
+                    bytes.copy_from_slice(&(value as i32).to_le_bytes()[..3]);
+                }
+                SampleFormat::I32 => {
+                    let value = round_to_step(*sample, SampleFormat::I32);
+                    bytes.copy_from_slice(&(value as i32).to_le_bytes());
+                }
+                SampleFormat::F32 => bytes.copy_from_slice(&sample.to_le_bytes()),
+            }
+        }
+        count
+    }
+
+    fn quantize_16(&mut self, sample: f32, channel: usize) -> i16 {
+        let mut target = sample * 32768.0;
+        if self.dither == Dither::Shaped {
+            let errors = self.errors[channel];
+            target -= NOISE_SHAPING.iter().zip(errors).map(|(coefficient, error)| coefficient * error).sum::<f32>();
+        }
+        let noise = match self.dither {
+            Dither::Off => 0.0,
+            Dither::Tpdf | Dither::Shaped => self.next_random() - self.next_random(),
+        };
+
+        let value = (target + noise).round().clamp(i16::MIN as f32, i16::MAX as f32);
+        if self.dither == Dither::Shaped {
+            let errors = &mut self.errors[channel];
+            errors.rotate_right(1);
+            errors[0] = (value - target).clamp(-MAX_SHAPED_ERROR, MAX_SHAPED_ERROR);
+        }
+        value as i16
+    }
+}
+
+// `sample` in steps of the integer `sample_format`, rounded and clipped to its range
+fn round_to_step(sample: f32, sample_format: SampleFormat) -> i64 {
+    let scale = 1i64 << (sample_format.bits_per_sample() - 1);
+    ((sample as f64 * scale as f64).round() as i64).clamp(-scale, scale - 1)
+}
@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::audio_manager::{AudioBackend, SessionEvent};
+
+// How often the watcher thread checks whether it has been asked to stop
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Forwards session changes from an audio backend to a sink, in the app the Tauri event bus.
+// The sink is a plain closure so tests can collect the events instead of emitting them.
+pub struct SessionWatcher {
+    stop_requested: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SessionWatcher {
+    pub fn start<F>(backend: Arc<dyn AudioBackend>, sink: F) -> Result<Self, String>
+    where
+        F: Fn(&SessionEvent) + Send + 'static,
+    {
+        let events = backend.subscribe()?;
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let thread_stop_requested = Arc::clone(&stop_requested);
+        let thread = thread::spawn(move || {
+            while !thread_stop_requested.load(Ordering::SeqCst) {
+                match events.recv_timeout(STOP_CHECK_INTERVAL) {
+                    Ok(event) => sink(&event),
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            stop_requested,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SessionWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
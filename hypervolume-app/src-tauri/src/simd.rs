@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Vectorized kernels of the processing path: the gain stage, the filters of the true-peak
+// detector the limiter runs on and of the clipper's oversampling, and the clip curves that
+// vectorize. Each call dispatches to the widest instruction set the CPU supports, detected
+// once, and falls back to plain scalar code everywhere else. None of them allocates.
+
+// 4/27, the cubic term of ClipCurve::Cubic
+const CUBIC_COEFFICIENT: f32 = 4.0 / 27.0;
+// Input, relative to the ceiling, at which the cubic curve flattens out
+const CUBIC_LIMIT: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    Scalar,
+    // 8 lanes with fused multiply-add, x86_64 only
+    Avx,
+}
+
+// 0 until the CPU was checked, then 1 + the SimdLevel
+static DETECTED_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+// Instruction set the kernels run on
+pub fn simd_level() -> SimdLevel {
+    match DETECTED_LEVEL.load(Ordering::Relaxed) {
+        1 => SimdLevel::Scalar,
+        2 => SimdLevel::Avx,
+        _ => {
+            let level = detect_level();
+            DETECTED_LEVEL.store(level as u8 + 1, Ordering::Relaxed);
+            level
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn detect_level() -> SimdLevel {
+    if is_x86_feature_detected!("avx") && is_x86_feature_detected!("fma") {
+        SimdLevel::Avx
+    } else {
+        SimdLevel::Scalar
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn detect_level() -> SimdLevel {
+    SimdLevel::Scalar
+}
+
+// Sum of the products of `a` and `b`, over the shorter of the two
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    #[cfg(target_arch = "x86_64")]
+    if simd_level() == SimdLevel::Avx {
+        // SAFETY: the CPU supports AVX and FMA
+        return unsafe { avx::dot(a, b) };
+    }
+    scalar::dot(a, b)
+}
+
+pub fn apply_gain(samples: &mut [f32], gain: f32) {
+    #[cfg(target_arch = "x86_64")]
+    if simd_level() == SimdLevel::Avx {
+        // SAFETY: the CPU supports AVX and FMA
+        return unsafe { avx::apply_gain(samples, gain) };
+    }
+    scalar::apply_gain(samples, gain)
+}
+
+// ClipCurve::Hard scaled to `ceiling`
+pub fn hard_clip(samples: &mut [f32], ceiling: f32) {
+    #[cfg(target_arch = "x86_64")]
+    if simd_level() == SimdLevel::Avx {
+        // SAFETY: the CPU supports AVX and FMA
+        return unsafe { avx::hard_clip(samples, ceiling) };
+    }
+    scalar::hard_clip(samples, ceiling)
+}
+
+// ClipCurve::Cubic scaled to `ceiling`
+pub fn cubic_clip(samples: &mut [f32], ceiling: f32) {
+    #[cfg(target_arch = "x86_64")]
+    if simd_level() == SimdLevel::Avx {
+        // SAFETY: the CPU supports AVX and FMA
+        return unsafe { avx::cubic_clip(samples, ceiling) };
+    }
+    scalar::cubic_clip(samples, ceiling)
+}
+
+// Reference versions, run where no vector instruction set is available. The vector ones
+// match them up to rounding.
+pub mod scalar {
+    use super::{CUBIC_COEFFICIENT, CUBIC_LIMIT};
+
+    pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(a, b)| a * b).sum()
+    }
+
+    pub fn apply_gain(samples: &mut [f32], gain: f32) {
+        for sample in samples {
+            *sample *= gain;
+        }
+    }
+
+    pub fn hard_clip(samples: &mut [f32], ceiling: f32) {
+        for sample in samples {
+            *sample = sample.clamp(-ceiling, ceiling);
+        }
+    }
+
+    pub fn cubic_clip(samples: &mut [f32], ceiling: f32) {
+        for sample in samples {
+            let x = (*sample / ceiling).clamp(-CUBIC_LIMIT, CUBIC_LIMIT);
+            *sample = ceiling * (x - CUBIC_COEFFICIENT * x * x * x);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx {
+    use std::arch::x86_64::*;
+
+    use super::{scalar, CUBIC_COEFFICIENT, CUBIC_LIMIT};
+
+    const LANES: usize = 8;
+
+    #[target_feature(enable = "avx,fma")]
+    pub unsafe fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let len = a.len().min(b.len());
+        let vectorized = len - len % LANES;
+        let mut sum = _mm256_setzero_ps();
+        for offset in (0..vectorized).step_by(LANES) {
+            let a = _mm256_loadu_ps(a.as_ptr().add(offset));
+            let b = _mm256_loadu_ps(b.as_ptr().add(offset));
+            sum = _mm256_fmadd_ps(a, b, sum);
+        }
+
+        // Adds up the eight lanes
+        let half = _mm_add_ps(_mm256_castps256_ps128(sum), _mm256_extractf128_ps(sum, 1));
+        let quarter = _mm_add_ps(half, _mm_movehl_ps(half, half));
+        let total = _mm_add_ss(quarter, _mm_shuffle_ps(quarter, quarter, 1));
+        _mm_cvtss_f32(total) + scalar::dot(&a[vectorized..len], &b[vectorized..len])
+    }
+
+    #[target_feature(enable = "avx,fma")]
+    pub unsafe fn apply_gain(samples: &mut [f32], gain: f32) {
+        let mut chunks = samples.chunks_exact_mut(LANES);
+        let gain_vector = _mm256_set1_ps(gain);
+        for chunk in &mut chunks {
+            let values = _mm256_loadu_ps(chunk.as_ptr());
+            _mm256_storeu_ps(chunk.as_mut_ptr(), _mm256_mul_ps(values, gain_vector));
+        }
+        scalar::apply_gain(chunks.into_remainder(), gain);
+    }
+
+    #[target_feature(enable = "avx,fma")]
+    pub unsafe fn hard_clip(samples: &mut [f32], ceiling: f32) {
+        let mut chunks = samples.chunks_exact_mut(LANES);
+        let high = _mm256_set1_ps(ceiling);
+        let low = _mm256_set1_ps(-ceiling);
+        for chunk in &mut chunks {
+            let values = _mm256_loadu_ps(chunk.as_ptr());
+            _mm256_storeu_ps(chunk.as_mut_ptr(), _mm256_min_ps(_mm256_max_ps(values, low), high));
+        }
+        scalar::hard_clip(chunks.into_remainder(), ceiling);
+    }
+
+    #[target_feature(enable = "avx,fma")]
+    pub unsafe fn cubic_clip(samples: &mut [f32], ceiling: f32) {
+        let mut chunks = samples.chunks_exact_mut(LANES);
+        let scale = _mm256_set1_ps(1.0 / ceiling);
+        let ceiling_vector = _mm256_set1_ps(ceiling);
+        let high = _mm256_set1_ps(CUBIC_LIMIT);
+        let low = _mm256_set1_ps(-CUBIC_LIMIT);
+        let coefficient = _mm256_set1_ps(CUBIC_COEFFICIENT);
+        for chunk in &mut chunks {
+            let values = _mm256_mul_ps(_mm256_loadu_ps(chunk.as_ptr()), scale);
+            let x = _mm256_min_ps(_mm256_max_ps(values, low), high);
+            let cube = _mm256_mul_ps(_mm256_mul_ps(x, x), x);
+            let curve = _mm256_fnmadd_ps(coefficient, cube, x);
+            _mm256_storeu_ps(chunk.as_mut_ptr(), _mm256_mul_ps(curve, ceiling_vector));
+        }
+        scalar::cubic_clip(chunks.into_remainder(), ceiling);
+    }
+}
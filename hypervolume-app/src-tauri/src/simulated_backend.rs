@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+
+use crate::audio_endpoints::{AudioFormat, CaptureEndpoint, MemoryCapture, MemoryRender, RenderEndpoint};
+use crate::audio_manager::{
+    assign_session_ids,
+    default_channel_layout,
+    device_not_found,
+    diff_sessions,
+    session_not_found,
+    validate_channel_volumes,
+    AudioBackend,
+    AudioDevice,
+    AudioSession,
+    ChannelPosition,
+    DeviceFlow,
+    DeviceRole,
+    DeviceState,
+    PeakLevels,
+    SessionEvent,
+    SessionIdentity,
+    SessionState,
+    SessionSubscribers,
+    VolumeRange,
+};
+
+pub const DEFAULT_DEVICE_ID: &str = "simulated-speakers";
+
+// Backend operations that can be scripted to fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimulatedOperation {
+    Enumerate,
+    GetVolume,
+    SetVolume,
+    GetMute,
+    SetMute,
+    GetChannelVolumes,
+    SetChannelVolumes,
+    GetDeviceVolume,
+    SetDeviceVolume,
+    GetDeviceMute,
+    SetDeviceMute,
+    GetPeakLevels,
+    Subscribe,
+    OpenCapture,
+    OpenRender,
+}
+
+// Range of a typical laptop speaker endpoint
+pub const DEFAULT_VOLUME_RANGE: VolumeRange = VolumeRange {
+    min_db: -65.25,
+    max_db: 0.0,
+    step_db: 0.03125,
+};
+
+#[derive(Debug, Clone)]
+pub struct SimulatedProcess {
+    pub process_id: u32,
+    pub name: String,
+    pub executable_path: String,
+}
+
+#[derive(Debug, Clone)]
+struct SimulatedDevice {
+    info: AudioDevice,
+    volume: f32,
+    muted: bool,
+    volume_range: VolumeRange,
+    // Level of a signal entering the device itself, e.g. a microphone
+    input_peak: f32,
+    format: AudioFormat,
+    // What a capture endpoint opened on the device delivers
+    capture: Vec<f32>,
+    // Everything render endpoints opened on the device were given
+    rendered: Arc<Mutex<Vec<f32>>>,
+}
+
+impl SimulatedDevice {
+    fn new(id: &str, name: &str, flow: DeviceFlow, default_roles: Vec<DeviceRole>) -> Self {
+        Self {
+            info: AudioDevice {
+                id: id.to_string(),
+                name: name.to_string(),
+                flow,
+                state: DeviceState::Active,
+                default_roles,
+            },
+            volume: 1.0,
+            muted: false,
+            volume_range: DEFAULT_VOLUME_RANGE,
+            input_peak: 0.0,
+            format: AudioFormat::default(),
+            capture: Vec::new(),
+            rendered: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SimulatedSession {
+    process_id: u32,
+    instance: u64,
+    executable_path: String,
+    device_id: String,
+    display_name: String,
+    volume: f32,
+    muted: bool,
+    state: SessionState,
+    channel_layout: Vec<ChannelPosition>,
+    channel_volumes: Vec<f32>,
+    // Peak of what the application plays, before the session volume
+    peak: f32,
+}
+
+#[derive(Debug, Clone)]
+enum Failure {
+    Once(String),
+    Always(String),
+}
+
+struct SimulatedState {
+    processes: HashMap<u32, SimulatedProcess>,
+    // Version-resource product names by executable path
+    product_names: HashMap<String, String>,
+    devices: Vec<SimulatedDevice>,
+    sessions: Vec<SimulatedSession>,
+    failures: HashMap<SimulatedOperation, Failure>,
+    next_instance: u64,
+}
+
+impl SimulatedState {
+    // Every session including those on inactive devices, which the backend keeps but hides
+    fn all_sessions(&self) -> Vec<AudioSession> {
+        let identities: Vec<SessionIdentity> = self.sessions.iter().map(identity).collect();
+
+        self.sessions.iter()
+            .zip(assign_session_ids(&identities))
+            .map(|(session, id)| AudioSession {
+                id,
+                display_name: session.display_name.clone(),
+                volume: session.volume,
+                muted: session.muted,
+                process_id: session.process_id,
+                executable_path: session.executable_path.clone(),
+                icon_path: String::new(),
+                state: session.state,
+                device_id: session.device_id.clone(),
+                channel_count: session.channel_layout.len() as u32,
+                channel_layout: session.channel_layout.clone(),
+            })
+            .collect()
+    }
+
+    fn audio_sessions(&self) -> Vec<AudioSession> {
+        self.all_sessions()
+            .into_iter()
+            .filter(|session| self.device_active(&session.device_id))
+            .collect()
+    }
+
+    fn device_active(&self, device_id: &str) -> bool {
+        self.devices.iter().any(|device| device.info.id == device_id && device.info.state == DeviceState::Active)
+    }
+
+    // Like sessions, endpoint controls are only reachable while the device is active
+    fn active_device(&mut self, device_id: &str) -> Option<&mut SimulatedDevice> {
+        self.devices.iter_mut()
+            .find(|device| device.info.id == device_id && device.info.state == DeviceState::Active)
+    }
+
+    fn position(&self, session_id: &str) -> Option<usize> {
+        self.all_sessions()
+            .iter()
+            .position(|session| session.id == session_id && self.device_active(&session.device_id))
+    }
+}
+
+// In-memory audio backend for deterministic tests and hardware-less development.
+// Sessions, processes and devices are scripted through the inherent methods,
+// and every state change is published to subscribers like a real backend would.
+pub struct SimulatedBackend {
+    state: Mutex<SimulatedState>,
+    subscribers: SessionSubscribers,
+}
+
+impl SimulatedBackend {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(SimulatedState {
+                processes: HashMap::new(),
+                product_names: HashMap::new(),
+                devices: vec![SimulatedDevice::new(
+                    DEFAULT_DEVICE_ID,
+                    "Simulated Speakers",
+                    DeviceFlow::Render,
+                    vec![DeviceRole::Console, DeviceRole::Multimedia, DeviceRole::Communications],
+                )],
+                sessions: Vec::new(),
+                failures: HashMap::new(),
+                next_instance: 1,
+            }),
+            subscribers: SessionSubscribers::new(),
+        }
+    }
+
+    // Adds an active, non-default device
+    pub fn add_device(&self, id: &str, name: &str, flow: DeviceFlow) {
+        let mut state = self.state.lock().unwrap();
+        state.devices.retain(|device| device.info.id != id);
+        state.devices.push(SimulatedDevice::new(id, name, flow, Vec::new()));
+    }
+
+    pub fn devices(&self) -> Vec<AudioDevice> {
+        self.state.lock().unwrap().devices.iter().map(|device| device.info.clone()).collect()
+    }
+
+    pub fn set_device_volume_range(&self, device_id: &str, volume_range: VolumeRange) -> Result<(), String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        let device = state.devices.iter_mut()
+            .find(|device| device.info.id == device_id)
+            .ok_or_else(|| device_not_found(device_id))?;
+        device.volume_range = volume_range;
+        Ok(())
+    }
+
+    // Simulates disabling or unplugging a device; its sessions disappear until it is active again
+    pub fn set_device_state(&self, device_id: &str, new_state: DeviceState) -> Result<(), String> {
+        let events = {
+            let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+            let before = state.audio_sessions();
+            let device = state.devices.iter_mut()
+                .find(|device| device.info.id == device_id)
+                .ok_or_else(|| device_not_found(device_id))?;
+            device.info.state = new_state;
+            diff_sessions(&before, &state.audio_sessions())
+        };
+
+        for event in events {
+            self.subscribers.publish(event);
+        }
+        Ok(())
+    }
+
+    // Makes `device_id` the default for `role`, taking the role from the previous default of the same flow
+    pub fn set_default_device(&self, device_id: &str, role: DeviceRole) -> Result<(), String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        let flow = state.devices.iter()
+            .find(|device| device.info.id == device_id)
+            .map(|device| device.info.flow)
+            .ok_or_else(|| device_not_found(device_id))?;
+
+        for device in state.devices.iter_mut().map(|device| &mut device.info).filter(|info| info.flow == flow) {
+            device.default_roles.retain(|default_role| *default_role != role);
+            if device.id == device_id {
+                device.default_roles.push(role);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn processes(&self) -> Vec<SimulatedProcess> {
+        let mut processes: Vec<_> = self.state.lock().unwrap().processes.values().cloned().collect();
+        processes.sort_by_key(|process| process.process_id);
+        processes
+    }
+
+    pub fn sessions_on(&self, device_id: &str) -> Vec<AudioSession> {
+        let state = self.state.lock().unwrap();
+        state.audio_sessions()
+            .into_iter()
+            .filter(|session| session.device_id == device_id)
+            .collect()
+    }
+
+    // Starts a process (if needed) and opens a session for it on the default device
+    pub fn spawn_session(&self, process_id: u32, name: &str) -> AudioSession {
+        self.spawn_session_on(DEFAULT_DEVICE_ID, process_id, name)
+    }
+
+    // Calling this twice for one process gives it two independent sessions.
+    // A session opened on an inactive device stays hidden until the device becomes active.
+    pub fn spawn_session_on(&self, device_id: &str, process_id: u32, name: &str) -> AudioSession {
+        let (audio_session, visible) = {
+            let mut state = self.state.lock().unwrap();
+            let executable_path = state.processes.entry(process_id)
+                .or_insert_with(|| SimulatedProcess {
+                    process_id,
+                    name: name.to_string(),
+                    executable_path: format!("C:\\Program Files\\{0}\\{0}.exe", name),
+                })
+                .executable_path
+                .clone();
+
+            let instance = state.next_instance;
+            state.next_instance += 1;
+            state.sessions.push(SimulatedSession {
+                process_id,
+                instance,
+                executable_path,
+                device_id: device_id.to_string(),
+                display_name: name.to_string(),
+                volume: 1.0,
+                muted: false,
+                state: SessionState::Active,
+                channel_layout: default_channel_layout(2),
+                channel_volumes: vec![1.0; 2],
+                peak: 0.0,
+            });
+            (state.all_sessions().pop().unwrap(), state.device_active(device_id))
+        };
+
+        if visible {
+            self.subscribers.publish(SessionEvent::Added(audio_session.clone()));
+        }
+        audio_session
+    }
+
+    // Terminates a process, closing every session it owned
+    pub fn exit_process(&self, process_id: u32) {
+        let removed: Vec<AudioSession> = {
+            let mut state = self.state.lock().unwrap();
+            state.processes.remove(&process_id);
+            let removed = state.audio_sessions()
+                .into_iter()
+                .filter(|session| session.process_id == process_id)
+                .collect();
+            state.sessions.retain(|session| session.process_id != process_id);
+            removed
+        };
+
+        for session in removed {
+            self.subscribers.publish(SessionEvent::Removed { id: session.id });
+        }
+    }
+
+    // Simulates the application starting or stopping playback on an existing session
+    pub fn set_session_state(&self, session_id: &str, new_state: SessionState) -> Result<(), String> {
+        let changed = {
+            let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+            let position = state.position(session_id).ok_or_else(|| session_not_found(session_id))?;
+            let session = &mut state.sessions[position];
+            let changed = session.state != new_state;
+            session.state = new_state;
+            changed
+        };
+
+        if changed {
+            self.subscribers.publish(SessionEvent::StateChanged {
+                id: session_id.to_string(),
+                state: new_state,
+            });
+        }
+        Ok(())
+    }
+
+    // Simulates the application reopening its stream with another channel layout,
+    // which resets every channel volume to full scale
+    pub fn set_session_layout(&self, session_id: &str, layout: Vec<ChannelPosition>) -> Result<(), String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        let position = state.position(session_id).ok_or_else(|| session_not_found(session_id))?;
+        let session = &mut state.sessions[position];
+        session.channel_volumes = vec![1.0; layout.len()];
+        session.channel_layout = layout;
+        Ok(())
+    }
+
+    // Many applications never call SetDisplayName, which leaves the session name empty
+    pub fn set_display_name(&self, session_id: &str, display_name: &str) -> Result<(), String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        let position = state.position(session_id).ok_or_else(|| session_not_found(session_id))?;
+        state.sessions[position].display_name = display_name.to_string();
+        Ok(())
+    }
+
+    pub fn set_product_name(&self, executable_path: &str, product_name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.product_names.insert(executable_path.to_string(), product_name.to_string());
+    }
+
+    // Sets the level the application is playing at; the session meter reports it after
+    // the session volume and mute, like the WASAPI session meter
+    pub fn set_session_peak(&self, session_id: &str, peak: f32) -> Result<(), String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        let position = state.position(session_id).ok_or_else(|| session_not_found(session_id))?;
+        state.sessions[position].peak = peak;
+        Ok(())
+    }
+
+    pub fn set_device_input_peak(&self, device_id: &str, peak: f32) -> Result<(), String> {
+        self.update_device(device_id, |device| device.input_peak = peak)
+    }
+
+    pub fn set_device_format(&self, device_id: &str, format: AudioFormat) -> Result<(), String> {
+        self.update_device(device_id, |device| device.format = format)
+    }
+
+    // Sets the interleaved samples the next capture endpoint opened on the device reads
+    pub fn set_device_capture(&self, device_id: &str, samples: Vec<f32>) -> Result<(), String> {
+        self.update_device(device_id, |device| device.capture = samples)
+    }
+
+    pub fn rendered_samples(&self, device_id: &str) -> Result<Vec<f32>, String> {
+        let rendered = self.update_device(device_id, |device| Arc::clone(&device.rendered))?;
+        let samples = rendered.lock().map_err(|e| format!("Lock error: {:?}", e))?.clone();
+        Ok(samples)
+    }
+
+    // Makes the next call of `operation` fail with `message`
+    pub fn fail_next(&self, operation: SimulatedOperation, message: &str) {
+        self.state.lock().unwrap().failures.insert(operation, Failure::Once(message.to_string()));
+    }
+
+    // Makes every call of `operation` fail with `message` until `clear_failures`
+    pub fn fail_always(&self, operation: SimulatedOperation, message: &str) {
+        self.state.lock().unwrap().failures.insert(operation, Failure::Always(message.to_string()));
+    }
+
+    pub fn clear_failures(&self) {
+        self.state.lock().unwrap().failures.clear();
+    }
+
+    fn check_failure(state: &mut SimulatedState, operation: SimulatedOperation) -> Result<(), String> {
+        match state.failures.get(&operation).cloned() {
+            Some(Failure::Once(message)) => {
+                state.failures.remove(&operation);
+                Err(message)
+            }
+            Some(Failure::Always(message)) => Err(message),
+            None => Ok(()),
+        }
+    }
+
+    fn with_session<T>(
+        &self,
+        operation: SimulatedOperation,
+        session_id: &str,
+        f: impl FnOnce(&mut SimulatedSession) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Self::check_failure(&mut state, operation)?;
+
+        let position = state.position(session_id).ok_or_else(|| session_not_found(session_id))?;
+        f(&mut state.sessions[position])
+    }
+
+    // Like `with_device`, but for test setup: reaches inactive devices and never fails
+    fn update_device<T>(&self, device_id: &str, f: impl FnOnce(&mut SimulatedDevice) -> T) -> Result<T, String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        state.devices.iter_mut()
+            .find(|device| device.info.id == device_id)
+            .map(f)
+            .ok_or_else(|| device_not_found(device_id))
+    }
+
+    fn with_device<T>(
+        &self,
+        operation: SimulatedOperation,
+        device_id: &str,
+        f: impl FnOnce(&mut SimulatedDevice) -> T,
+    ) -> Result<T, String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Self::check_failure(&mut state, operation)?;
+
+        state.active_device(device_id).map(f).ok_or_else(|| device_not_found(device_id))
+    }
+}
+
+impl Default for SimulatedBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioBackend for SimulatedBackend {
+    fn name(&self) -> &'static str {
+        "simulated"
+    }
+
+    fn enumerate_devices(&self) -> Result<Vec<AudioDevice>, String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Self::check_failure(&mut state, SimulatedOperation::Enumerate)?;
+        Ok(state.devices.iter().map(|device| device.info.clone()).collect())
+    }
+
+    fn enumerate_sessions(&self) -> Result<Vec<AudioSession>, String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Self::check_failure(&mut state, SimulatedOperation::Enumerate)?;
+        Ok(state.audio_sessions())
+    }
+
+    fn get_device_volume(&self, device_id: &str) -> Result<f32, String> {
+        self.with_device(SimulatedOperation::GetDeviceVolume, device_id, |device| device.volume)
+    }
+
+    fn set_device_volume(&self, device_id: &str, volume: f32) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(format!("Failed to set endpoint volume: {} is out of range", volume));
+        }
+        self.with_device(SimulatedOperation::SetDeviceVolume, device_id, |device| device.volume = volume)
+    }
+
+    fn get_device_mute(&self, device_id: &str) -> Result<bool, String> {
+        self.with_device(SimulatedOperation::GetDeviceMute, device_id, |device| device.muted)
+    }
+
+    fn set_device_mute(&self, device_id: &str, muted: bool) -> Result<(), String> {
+        self.with_device(SimulatedOperation::SetDeviceMute, device_id, |device| device.muted = muted)
+    }
+
+    fn get_device_volume_range(&self, device_id: &str) -> Result<VolumeRange, String> {
+        self.with_device(SimulatedOperation::GetDeviceVolume, device_id, |device| device.volume_range)
+    }
+
+    fn get_session_volume(&self, session_id: &str) -> Result<f32, String> {
+        self.with_session(SimulatedOperation::GetVolume, session_id, |session| Ok(session.volume))
+    }
+
+    fn set_session_volume(&self, session_id: &str, volume: f32) -> Result<(), String> {
+        // Mirror WASAPI, which rejects levels outside 0.0..=1.0 with E_INVALIDARG
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(format!("Failed to set master volume: {} is out of range", volume));
+        }
+
+        let event = self.with_session(SimulatedOperation::SetVolume, session_id, |session| {
+            let changed = session.volume != volume;
+            session.volume = volume;
+            Ok(changed.then(|| SessionEvent::VolumeChanged {
+                id: session_id.to_string(),
+                volume,
+            }))
+        })?;
+
+        if let Some(event) = event {
+            self.subscribers.publish(event);
+        }
+        Ok(())
+    }
+
+    fn get_session_channel_volumes(&self, session_id: &str) -> Result<Vec<f32>, String> {
+        self.with_session(SimulatedOperation::GetChannelVolumes, session_id, |session| {
+            Ok(session.channel_volumes.clone())
+        })
+    }
+
+    fn set_session_channel_volumes(&self, session_id: &str, volumes: &[f32]) -> Result<(), String> {
+        self.with_session(SimulatedOperation::SetChannelVolumes, session_id, |session| {
+            validate_channel_volumes(volumes, session.channel_layout.len())?;
+            session.channel_volumes = volumes.to_vec();
+            Ok(())
+        })
+    }
+
+    fn get_session_mute(&self, session_id: &str) -> Result<bool, String> {
+        self.with_session(SimulatedOperation::GetMute, session_id, |session| Ok(session.muted))
+    }
+
+    fn set_session_mute(&self, session_id: &str, muted: bool) -> Result<(), String> {
+        let event = self.with_session(SimulatedOperation::SetMute, session_id, |session| {
+            let changed = session.muted != muted;
+            session.muted = muted;
+            Ok(changed.then(|| SessionEvent::MuteChanged {
+                id: session_id.to_string(),
+                muted,
+            }))
+        })?;
+
+        if let Some(event) = event {
+            self.subscribers.publish(event);
+        }
+        Ok(())
+    }
+
+    // A device meter shows the loudest of its own input and the sessions mixed into it
+    fn get_peak_levels(&self) -> Result<PeakLevels, String> {
+        let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+        Self::check_failure(&mut state, SimulatedOperation::GetPeakLevels)?;
+
+        let mut levels = PeakLevels::default();
+        for (session, audio_session) in state.sessions.iter().zip(state.all_sessions()) {
+            if !state.device_active(&session.device_id) {
+                continue;
+            }
+            let peak = if session.muted { 0.0 } else { session.peak * session.volume };
+            levels.sessions.insert(audio_session.id, peak);
+
+            let device_peak = levels.devices.entry(session.device_id.clone()).or_insert(0.0);
+            *device_peak = device_peak.max(peak);
+        }
+        for device in state.devices.iter().filter(|device| device.info.state == DeviceState::Active) {
+            let device_peak = levels.devices.entry(device.info.id.clone()).or_insert(0.0);
+            *device_peak = device_peak.max(device.input_peak);
+        }
+        Ok(levels)
+    }
+
+    fn describe_executable(&self, executable_path: &str) -> Option<String> {
+        self.state.lock().ok()?.product_names.get(executable_path).cloned()
+    }
+
+    fn subscribe(&self) -> Result<Receiver<SessionEvent>, String> {
+        {
+            let mut state = self.state.lock().map_err(|e| format!("Lock error: {:?}", e))?;
+            Self::check_failure(&mut state, SimulatedOperation::Subscribe)?;
+        }
+        self.subscribers.subscribe()
+    }
+
+    fn open_capture(&self, device_id: &str) -> Result<Box<dyn CaptureEndpoint>, String> {
+        let capture = self.with_device(SimulatedOperation::OpenCapture, device_id, |device| {
+            MemoryCapture::new(device.format, device.capture.clone())
+        })?;
+        Ok(Box::new(capture))
+    }
+
+    fn open_render(&self, device_id: &str) -> Result<Box<dyn RenderEndpoint>, String> {
+        let render = self.with_device(SimulatedOperation::OpenRender, device_id, |device| {
+            (device.info.flow == DeviceFlow::Render)
+                .then(|| MemoryRender::with_buffer(device.format, Arc::clone(&device.rendered)))
+        })?;
+        let render = render.ok_or_else(|| format!("Failed to open {} for rendering: it is a capture device", device_id))?;
+        Ok(Box::new(render))
+    }
+}
+
+// Mirrors the shape of WASAPI session identifiers: "<device>|<exe>%b<grouping GUID>"
+fn identity(session: &SimulatedSession) -> SessionIdentity {
+    let session_identifier = format!(
+        "{}|{}%b{{00000000-0000-0000-0000-000000000000}}",
+        session.device_id, session.executable_path
+    );
+    SessionIdentity {
+        instance_identifier: format!("{}|1%b{}|{:010}", session_identifier, session.process_id, session.instance),
+        session_identifier,
+        executable_path: session.executable_path.clone(),
+    }
+}
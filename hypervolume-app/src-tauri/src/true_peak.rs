@@ -0,0 +1,95 @@
+use crate::audio_endpoints::AudioFormat;
+use crate::simd;
+
+// Taps of every polyphase branch of the interpolation filter, as in BS.1770
+const TAPS_PER_PHASE: usize = 12;
+
+// ITU-R BS.1770 true-peak measurement oversamples to at least 192 kHz: 4x at 48 kHz,
+// 2x at 96 kHz and not at all from 192 kHz on
+pub fn oversampling_factor(sample_rate: u32) -> usize {
+    if sample_rate < 96_000 {
+        4
+    } else if sample_rate < 192_000 {
+        2
+    } else {
+        1
+    }
+}
+
+// Blackman-windowed sinc, split into one branch per oversampled position. Branch `p`
+// estimates the signal `p / factor` of a sample after the one TAPS_PER_PHASE / 2 frames
+// back; branch 0 reproduces that sample exactly.
+fn interpolation_phases(factor: usize) -> Vec<[f32; TAPS_PER_PHASE]> {
+    let center = (TAPS_PER_PHASE / 2) as f64;
+    let window_half_width = center + 1.0;
+
+    (0..factor)
+        .map(|phase| {
+            let offset = phase as f64 / factor as f64;
+            let mut taps = [0.0f64; TAPS_PER_PHASE];
+            for (k, tap) in taps.iter_mut().enumerate() {
+                let t = center - k as f64 - offset;
+                let sinc = if t == 0.0 { 1.0 } else { (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t) };
+                let x = std::f64::consts::PI * t / window_half_width;
+                *tap = sinc * (0.42 + 0.5 * x.cos() + 0.08 * (2.0 * x).cos());
+            }
+            // Unity gain at DC for every branch
+            let sum: f64 = taps.iter().sum();
+            taps.map(|tap| (tap / sum) as f32)
+        })
+        .collect()
+}
+
+// Oversampled peak detector per ITU-R BS.1770 Annex 2. It sees the peaks of the
+// reconstructed analog signal, which lie between samples and can be several dB above
+// the highest sample.
+pub struct TruePeakDetector {
+    channels: usize,
+    phases: Vec<[f32; TAPS_PER_PHASE]>,
+    // Last TAPS_PER_PHASE samples of every channel, newest first, stored twice in a row so
+    // the filter always reads one contiguous slice
+    history: Vec<f32>,
+    position: usize,
+}
+
+impl TruePeakDetector {
+    pub fn new(format: AudioFormat) -> Self {
+        let channels = format.channels.max(1) as usize;
+        Self {
+            channels,
+            phases: interpolation_phases(oversampling_factor(format.sample_rate)),
+            history: vec![0.0; channels * TAPS_PER_PHASE * 2],
+            position: 0,
+        }
+    }
+
+    // Frames between a sample going in and its peak being reported
+    pub fn latency_frames(&self) -> usize {
+        TAPS_PER_PHASE / 2
+    }
+
+    // Takes one interleaved frame and returns the highest absolute value, over all channels,
+    // of the signal from the sample `latency_frames` back up to the one after it
+    pub fn process_frame(&mut self, frame: &[f32]) -> f32 {
+        self.position = (self.position + TAPS_PER_PHASE - 1) % TAPS_PER_PHASE;
+        let mut peak = 0.0f32;
+
+        for (channel, sample) in frame.iter().enumerate().take(self.channels) {
+            let history = &mut self.history[channel * TAPS_PER_PHASE * 2..(channel + 1) * TAPS_PER_PHASE * 2];
+            history[self.position] = *sample;
+            history[self.position + TAPS_PER_PHASE] = *sample;
+
+            let recent = &history[self.position..self.position + TAPS_PER_PHASE];
+            for taps in &self.phases {
+                peak = peak.max(simd::dot(taps, recent).abs());
+            }
+        }
+
+        peak
+    }
+
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.position = 0;
+    }
+}
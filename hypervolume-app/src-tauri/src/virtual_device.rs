@@ -0,0 +1,542 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+use crate::audio_boost::{AudioProcessor, BoostControl};
+use crate::audio_endpoints::{AudioFormat, CaptureEndpoint, RenderEndpoint};
+use crate::ring_buffer::{ring_buffer, RingConsumer, RingProducer};
+
+// How long a write waits for the engine to make room in the input ring
+const INPUT_WAIT: Duration = Duration::from_millis(1);
+
+// Time source that paces the device; tests substitute a FakeClock
+pub trait Clock: Send + Sync {
+    // Time since an arbitrary fixed origin
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { origin: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+// Clock that only moves when told to; clones share the same time
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct FakeClock {
+    nanos: Arc<AtomicU64>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.nanos.fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualDeviceConfig {
+    pub sample_rate: u32,
+    pub channels: u16,
+    // Frames handed to the processing callback at once
+    pub period_frames: usize,
+    // Capacity of each of the input and output rings
+    pub buffer_frames: usize,
+}
+
+impl Default for VirtualDeviceConfig {
+    fn default() -> Self {
+        Self::for_format(AudioFormat::new(44100, 2))
+    }
+}
+
+impl VirtualDeviceConfig {
+    // 10 ms periods in a 200 ms buffer
+    pub fn for_format(format: AudioFormat) -> Self {
+        let period_frames = (format.sample_rate as usize / 100).max(1);
+        Self {
+            sample_rate: format.sample_rate,
+            channels: format.channels,
+            period_frames,
+            buffer_frames: period_frames * 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamState {
+    Stopped,
+    Running,
+    // Accepts no new input and stops once the queued input has been processed
+    Draining,
+}
+
+impl StreamState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => StreamState::Running,
+            2 => StreamState::Draining,
+            _ => StreamState::Stopped,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct StreamStats {
+    // Periods that found less input than they needed while running
+    pub underruns: u64,
+    // Writes that did not fit into the input ring
+    pub input_overruns: u64,
+    // Periods whose output did not fit because nobody read the output ring
+    pub output_overruns: u64,
+    pub processed_frames: u64,
+}
+
+// Lifecycle and counters, shared by the engine and every handle
+struct SharedState {
+    clock: Arc<dyn Clock>,
+    state: AtomicU8,
+    // Incremented by every start, with the clock time it happened at
+    run: AtomicU64,
+    started_at_nanos: AtomicU64,
+    underruns: AtomicU64,
+    input_overruns: AtomicU64,
+    output_overruns: AtomicU64,
+    processed_frames: AtomicU64,
+}
+
+impl SharedState {
+    fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            state: AtomicU8::new(StreamState::Stopped as u8),
+            run: AtomicU64::new(0),
+            started_at_nanos: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+            input_overruns: AtomicU64::new(0),
+            output_overruns: AtomicU64::new(0),
+            processed_frames: AtomicU64::new(0),
+        }
+    }
+
+    fn state(&self) -> StreamState {
+        StreamState::from_u8(self.state.load(Ordering::SeqCst))
+    }
+
+    fn set_state(&self, state: StreamState) {
+        self.state.store(state as u8, Ordering::SeqCst);
+    }
+}
+
+// Controls a device from any thread while the engine runs on the audio thread
+#[derive(Clone)]
+pub struct VirtualDeviceHandle {
+    shared: Arc<SharedState>,
+}
+
+impl VirtualDeviceHandle {
+    // Starts a stopped stream, or cancels a drain
+    pub fn start(&self) {
+        let shared = &self.shared;
+        if shared.state.swap(StreamState::Running as u8, Ordering::SeqCst) == StreamState::Stopped as u8 {
+            // Periods are counted from now, so a restarted stream does not catch up on
+            // the time it spent stopped
+            shared.started_at_nanos.store(shared.clock.now().as_nanos() as u64, Ordering::SeqCst);
+            shared.run.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Stops processing at once; queued input is discarded
+    pub fn stop(&self) {
+        self.shared.set_state(StreamState::Stopped);
+    }
+
+    // Stops once everything already written has been processed
+    pub fn drain(&self) {
+        let _ = self.shared.state.compare_exchange(
+            StreamState::Running as u8,
+            StreamState::Draining as u8,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+    }
+
+    #[cfg(test)]
+    pub fn state(&self) -> StreamState {
+        self.shared.state()
+    }
+
+    pub fn stats(&self) -> StreamStats {
+        StreamStats {
+            underruns: self.shared.underruns.load(Ordering::Relaxed),
+            input_overruns: self.shared.input_overruns.load(Ordering::Relaxed),
+            output_overruns: self.shared.output_overruns.load(Ordering::Relaxed),
+            processed_frames: self.shared.processed_frames.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Where applications (or a pipeline) write into the device
+pub struct VirtualDeviceInput {
+    ring: RingProducer,
+    format: AudioFormat,
+    shared: Arc<SharedState>,
+}
+
+impl VirtualDeviceInput {
+    #[cfg(test)]
+    // Queues as many whole frames as fit and returns the number of samples queued.
+    // Only a running device accepts input.
+    pub fn push(&mut self, samples: &[f32]) -> usize {
+        if self.shared.state() != StreamState::Running {
+            return 0;
+        }
+
+        let channels = self.format.channels as usize;
+        let fit = samples.len().min(self.ring.free());
+        let count = self.ring.push(&samples[..fit - fit % channels]);
+        if count < samples.len() {
+            self.shared.input_overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        count
+    }
+}
+
+impl RenderEndpoint for VirtualDeviceInput {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    // Waits for room like a device whose buffer is full; `push` is the non-blocking way
+    // in. Fails once the device no longer runs, since nothing would make room any more.
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        let channels = self.format.channels as usize;
+        let mut offset = 0;
+        while offset < samples.len() {
+            if self.shared.state() != StreamState::Running {
+                return Err("Virtual audio device is not running".to_string());
+            }
+            let free = self.ring.free() - self.ring.free() % channels;
+            if free == 0 {
+                thread::sleep(INPUT_WAIT);
+                continue;
+            }
+            let end = samples.len().min(offset + free);
+            offset += self.ring.push(&samples[offset..end]);
+        }
+        Ok(())
+    }
+}
+
+// Where the processed audio leaves the device, e.g. towards a render endpoint
+pub struct VirtualDeviceOutput {
+    ring: RingConsumer,
+    format: AudioFormat,
+}
+
+impl CaptureEndpoint for VirtualDeviceOutput {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize, String> {
+        let channels = self.format.channels as usize;
+        let count = buffer.len().min(self.ring.available());
+        Ok(self.ring.pop(&mut buffer[..count - count % channels]))
+    }
+}
+
+// Callback run on every period, in place on interleaved samples. It runs on the audio
+// thread, so it must not block or allocate.
+pub type ProcessCallback = Box<dyn FnMut(&mut [f32]) + Send>;
+
+struct Engine {
+    input: RingConsumer,
+    output: RingProducer,
+    scratch: Vec<f32>,
+    // Run the period count below belongs to
+    #[cfg(test)]
+    run: u64,
+    #[cfg(test)]
+    periods: u64,
+}
+
+// A software audio device: input ring -> processing callback -> output ring, one period
+// at a time. A host drives it by calling `process_period` whenever its audio device wants
+// a period; tests call `tick`, which follows the clock. PipelineRunner hosts one between
+// the capture and the render side of every boost pipeline.
+pub struct VirtualAudioDevice {
+    device_name: String,
+    config: VirtualDeviceConfig,
+    shared: Arc<SharedState>,
+    callback: ProcessCallback,
+    #[cfg(test)]
+    latency_frames: usize,
+    engine: Option<Engine>,
+    input: Option<VirtualDeviceInput>,
+    output: Option<VirtualDeviceOutput>,
+}
+
+impl VirtualAudioDevice {
+    #[cfg(test)]
+    pub fn new(name: &str) -> Self {
+        Self::with_config(name, VirtualDeviceConfig::default())
+    }
+
+    pub fn with_config(name: &str, config: VirtualDeviceConfig) -> Self {
+        Self::with_clock(name, config, Arc::new(SystemClock::new()))
+    }
+
+    pub fn with_clock(name: &str, config: VirtualDeviceConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            device_name: name.to_string(),
+            config,
+            shared: Arc::new(SharedState::new(clock)),
+            callback: Box::new(|_| {}),
+            #[cfg(test)]
+            latency_frames: 0,
+            engine: None,
+            input: None,
+            output: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn name(&self) -> &str {
+        &self.device_name
+    }
+
+    pub fn config(&self) -> VirtualDeviceConfig {
+        self.config
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        AudioFormat::new(self.config.sample_rate, self.config.channels)
+    }
+
+    #[cfg(test)]
+    pub fn period_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.config.period_frames as f64 / self.config.sample_rate as f64)
+    }
+
+    #[cfg(test)]
+    pub fn set_callback(&mut self, callback: impl FnMut(&mut [f32]) + Send + 'static) {
+        self.callback = Box::new(callback);
+        self.latency_frames = 0;
+    }
+
+    #[cfg(test)]
+    // Boosts everything passing through the device by the control's current factor, up to
+    // its true-peak ceiling. The limiter delays the output by `latency_frames`.
+    pub fn set_boost(&mut self, boost: BoostControl) {
+        let mut processor = AudioProcessor::for_control(self.format(), &boost);
+        let latency_frames = processor.latency_frames();
+        self.set_callback(move |samples| processor.process_with_control(samples, &boost));
+        self.latency_frames = latency_frames;
+    }
+
+    #[cfg(test)]
+    // Delay the processing callback adds
+    pub fn latency_frames(&self) -> usize {
+        self.latency_frames
+    }
+
+    // Allocates the rings and the period buffer; nothing allocates after this
+    pub fn initialize(&mut self) -> Result<(), String> {
+        let config = self.config;
+        if config.sample_rate == 0 || config.channels == 0 || config.period_frames == 0 {
+            return Err(format!("Invalid virtual audio device configuration: {:?}", config));
+        }
+        if config.buffer_frames < config.period_frames {
+            return Err(format!(
+                "Virtual audio device buffer of {} frames cannot hold a period of {} frames",
+                config.buffer_frames, config.period_frames
+            ));
+        }
+        if self.engine.is_some() {
+            return Err(format!("Virtual audio device {} is already initialized", self.device_name));
+        }
+
+        let channels = config.channels as usize;
+        let (input_producer, input_consumer) = ring_buffer(config.buffer_frames * channels);
+        let (output_producer, output_consumer) = ring_buffer(config.buffer_frames * channels);
+
+        self.input = Some(VirtualDeviceInput {
+            ring: input_producer,
+            format: self.format(),
+            shared: Arc::clone(&self.shared),
+        });
+        self.output = Some(VirtualDeviceOutput {
+            ring: output_consumer,
+            format: self.format(),
+        });
+        self.engine = Some(Engine {
+            input: input_consumer,
+            output: output_producer,
+            scratch: vec![0.0; config.period_frames * channels],
+            #[cfg(test)]
+            run: 0,
+            #[cfg(test)]
+            periods: 0,
+        });
+        Ok(())
+    }
+
+    // The write side of the device; can be taken once
+    pub fn take_input(&mut self) -> Result<VirtualDeviceInput, String> {
+        self.input.take().ok_or_else(|| self.endpoint_unavailable())
+    }
+
+    // The read side of the device; can be taken once
+    pub fn take_output(&mut self) -> Result<VirtualDeviceOutput, String> {
+        self.output.take().ok_or_else(|| self.endpoint_unavailable())
+    }
+
+    fn endpoint_unavailable(&self) -> String {
+        if self.engine.is_none() {
+            format!("Virtual audio device {} is not initialized", self.device_name)
+        } else {
+            format!("Virtual audio device {} endpoint was already taken", self.device_name)
+        }
+    }
+
+    pub fn handle(&self) -> VirtualDeviceHandle {
+        VirtualDeviceHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn start(&self) {
+        self.handle().start();
+    }
+
+    pub fn stop(&self) {
+        self.handle().stop();
+    }
+
+    #[cfg(test)]
+    pub fn drain(&self) {
+        self.handle().drain();
+    }
+
+    pub fn state(&self) -> StreamState {
+        self.shared.state()
+    }
+
+    #[cfg(test)]
+    pub fn stats(&self) -> StreamStats {
+        self.handle().stats()
+    }
+
+    // Frames written to the device and not processed yet
+    pub fn queued_frames(&self) -> usize {
+        self.engine.as_ref().map_or(0, |engine| engine.input.available() / self.config.channels as usize)
+    }
+
+    // Processes one period. Realtime-safe: no locks, no allocation.
+    pub fn process_period(&mut self) -> Result<(), String> {
+        let engine = self.engine.as_mut()
+            .ok_or_else(|| format!("Virtual audio device {} is not initialized", self.device_name))?;
+        run_period(engine, &self.shared, &mut self.callback, self.config.channels as usize);
+        Ok(())
+    }
+
+    // Processes every period that became due on the clock since the stream started and
+    // returns how many that was
+    #[cfg(test)]
+    pub fn tick(&mut self) -> Result<usize, String> {
+        let config = self.config;
+        let shared = &self.shared;
+        let engine = self.engine.as_mut()
+            .ok_or_else(|| format!("Virtual audio device {} is not initialized", self.device_name))?;
+
+        if shared.state() == StreamState::Stopped {
+            run_period(engine, shared, &mut self.callback, config.channels as usize);
+            return Ok(0);
+        }
+
+        let run = shared.run.load(Ordering::SeqCst);
+        if run != engine.run {
+            engine.run = run;
+            engine.periods = 0;
+        }
+        let started_at = Duration::from_nanos(shared.started_at_nanos.load(Ordering::SeqCst));
+        let elapsed = shared.clock.now().saturating_sub(started_at);
+        let due = (elapsed.as_nanos() * config.sample_rate as u128 / (config.period_frames as u128 * 1_000_000_000)) as u64;
+
+        let mut processed = 0;
+        while engine.periods < due && shared.state() != StreamState::Stopped {
+            run_period(engine, shared, &mut self.callback, config.channels as usize);
+            engine.periods += 1;
+            processed += 1;
+        }
+        Ok(processed)
+    }
+}
+
+fn run_period(engine: &mut Engine, shared: &SharedState, callback: &mut ProcessCallback, channels: usize) {
+    let state = shared.state();
+    if state == StreamState::Stopped {
+        engine.input.clear();
+        return;
+    }
+
+    let count = engine.input.pop(&mut engine.scratch);
+    let samples = if state == StreamState::Draining {
+        // The tail of a drained stream is not padded
+        if count == 0 {
+            shared.set_state(StreamState::Stopped);
+            return;
+        }
+        &mut engine.scratch[..count]
+    } else {
+        if count < engine.scratch.len() {
+            shared.underruns.fetch_add(1, Ordering::Relaxed);
+            engine.scratch[count..].fill(0.0);
+        }
+        &mut engine.scratch[..]
+    };
+
+    callback(samples);
+
+    if engine.output.push(samples) < samples.len() {
+        shared.output_overruns.fetch_add(1, Ordering::Relaxed);
+    }
+    shared.processed_frames.fetch_add((samples.len() / channels) as u64, Ordering::Relaxed);
+
+    if state == StreamState::Draining && engine.input.available() == 0 {
+        shared.set_state(StreamState::Stopped);
+    }
+}
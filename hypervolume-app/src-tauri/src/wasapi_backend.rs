@@ -0,0 +1,677 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use windows::core::{ComInterface, HSTRING, PWSTR};
+use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+use windows::Win32::Foundation::{CloseHandle, RPC_E_CHANGED_MODE};
+use windows::Win32::Media::Audio::{
+    eAll,
+    eCapture,
+    eCommunications,
+    eConsole,
+    eMultimedia,
+    eRender,
+    AudioSessionStateActive,
+    AudioSessionStateExpired,
+    EDataFlow,
+    ERole,
+    IAudioClient,
+    IChannelAudioVolume,
+    IMMDevice,
+    IMMDeviceEnumerator,
+    IMMEndpoint,
+    MMDeviceEnumerator,
+    IAudioSessionControl,
+    IAudioSessionControl2,
+    IAudioSessionManager2,
+    ISimpleAudioVolume,
+    DEVICE_STATEMASK_ALL,
+    DEVICE_STATE_ACTIVE,
+    DEVICE_STATE_DISABLED,
+    DEVICE_STATE_NOTPRESENT,
+    WAVEFORMATEXTENSIBLE,
+};
+use windows::Win32::Media::Audio::Endpoints::{IAudioEndpointVolume, IAudioMeterInformation};
+use windows::Win32::Storage::FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW};
+use windows::Win32::System::Com::StructuredStorage::{PropVariantClear, PropVariantToStringAlloc};
+use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CLSCTX_ALL, COINIT_APARTMENTTHREADED, STGM_READ};
+use windows::Win32::System::Threading::{
+    OpenProcess,
+    QueryFullProcessImageNameW,
+    PROCESS_NAME_WIN32,
+    PROCESS_QUERY_LIMITED_INFORMATION,
+};
+
+use crate::audio_endpoints::{CaptureEndpoint, RenderEndpoint};
+use crate::audio_manager::{
+    assign_session_ids,
+    channel_layout_from_mask,
+    default_channel_layout,
+    diff_sessions,
+    session_not_found,
+    validate_channel_volumes,
+    AudioBackend,
+    AudioDevice,
+    AudioSession,
+    ChannelPosition,
+    DeviceFlow,
+    DeviceRole,
+    DeviceState,
+    PeakLevels,
+    SessionEvent,
+    SessionIdentity,
+    SessionState,
+    SessionSubscribers,
+    VolumeRange,
+};
+use crate::wasapi_stream::{WasapiCapture, WasapiRender};
+
+// wFormatTag of a WAVEFORMATEXTENSIBLE, see mmreg.h
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+// WASAPI has no cheap "anything changed" query, so subscriptions are served by polling
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct WasapiBackend {
+    subscribers: Arc<SessionSubscribers>,
+    watcher_running: Arc<AtomicBool>,
+}
+
+impl WasapiBackend {
+    pub fn new() -> Self {
+        Self {
+            subscribers: Arc::new(SessionSubscribers::new()),
+            watcher_running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn start_watcher(&self) {
+        if self.watcher_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let subscribers = Arc::clone(&self.subscribers);
+        let watcher_running = Arc::clone(&self.watcher_running);
+        thread::spawn(move || {
+            let mut previous = enumerate_sessions().unwrap_or_default();
+            while subscribers.has_subscribers() {
+                thread::sleep(WATCH_INTERVAL);
+                // A failed enumeration (e.g. device unplugged) is retried on the next tick
+                if let Ok(current) = enumerate_sessions() {
+                    for event in diff_sessions(&previous, &current) {
+                        subscribers.publish(event);
+                    }
+                    previous = current;
+                }
+            }
+            watcher_running.store(false, Ordering::SeqCst);
+        });
+    }
+}
+
+impl Default for WasapiBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioBackend for WasapiBackend {
+    fn name(&self) -> &'static str {
+        "wasapi"
+    }
+
+    fn enumerate_devices(&self) -> Result<Vec<AudioDevice>, String> {
+        enumerate_devices()
+    }
+
+    fn enumerate_sessions(&self) -> Result<Vec<AudioSession>, String> {
+        enumerate_sessions()
+    }
+
+    fn get_device_volume(&self, device_id: &str) -> Result<f32, String> {
+        let endpoint_volume = find_endpoint_volume(device_id)?;
+        unsafe {
+            endpoint_volume.GetMasterVolumeLevelScalar()
+                .map_err(|e| format!("Failed to get endpoint volume: {:?}", e))
+        }
+    }
+
+    fn set_device_volume(&self, device_id: &str, volume: f32) -> Result<(), String> {
+        let endpoint_volume = find_endpoint_volume(device_id)?;
+        unsafe {
+            endpoint_volume.SetMasterVolumeLevelScalar(volume, std::ptr::null())
+                .map_err(|e| format!("Failed to set endpoint volume: {:?}", e))
+        }
+    }
+
+    fn get_device_mute(&self, device_id: &str) -> Result<bool, String> {
+        let endpoint_volume = find_endpoint_volume(device_id)?;
+        unsafe {
+            endpoint_volume.GetMute()
+                .map(|muted| muted.as_bool())
+                .map_err(|e| format!("Failed to get endpoint mute state: {:?}", e))
+        }
+    }
+
+    fn set_device_mute(&self, device_id: &str, muted: bool) -> Result<(), String> {
+        let endpoint_volume = find_endpoint_volume(device_id)?;
+        unsafe {
+            endpoint_volume.SetMute(muted, std::ptr::null())
+                .map_err(|e| format!("Failed to set endpoint mute state: {:?}", e))
+        }
+    }
+
+    fn get_device_volume_range(&self, device_id: &str) -> Result<VolumeRange, String> {
+        let endpoint_volume = find_endpoint_volume(device_id)?;
+        let mut range = VolumeRange {
+            min_db: 0.0,
+            max_db: 0.0,
+            step_db: 0.0,
+        };
+        unsafe {
+            endpoint_volume.GetVolumeRange(&mut range.min_db, &mut range.max_db, &mut range.step_db)
+                .map_err(|e| format!("Failed to get endpoint volume range: {:?}", e))?;
+        }
+        Ok(range)
+    }
+
+    fn get_session_volume(&self, session_id: &str) -> Result<f32, String> {
+        let simple_audio_volume = find_session_volume(session_id)?;
+        unsafe {
+            simple_audio_volume.GetMasterVolume()
+                .map_err(|e| format!("Failed to get master volume: {:?}", e))
+        }
+    }
+
+    fn set_session_volume(&self, session_id: &str, volume: f32) -> Result<(), String> {
+        let simple_audio_volume = find_session_volume(session_id)?;
+        unsafe {
+            simple_audio_volume.SetMasterVolume(volume, std::ptr::null())
+                .map_err(|e| format!("Failed to set master volume: {:?}", e))
+        }
+    }
+
+    fn get_session_channel_volumes(&self, session_id: &str) -> Result<Vec<f32>, String> {
+        let channel_audio_volume = find_channel_volume(session_id)?;
+        unsafe {
+            let count = channel_audio_volume.GetChannelCount()
+                .map_err(|e| format!("Failed to get channel count: {:?}", e))?;
+            let mut volumes = vec![0.0; count as usize];
+            channel_audio_volume.GetAllVolumes(&mut volumes)
+                .map_err(|e| format!("Failed to get channel volumes: {:?}", e))?;
+            Ok(volumes)
+        }
+    }
+
+    fn set_session_channel_volumes(&self, session_id: &str, volumes: &[f32]) -> Result<(), String> {
+        let channel_audio_volume = find_channel_volume(session_id)?;
+        unsafe {
+            let count = channel_audio_volume.GetChannelCount()
+                .map_err(|e| format!("Failed to get channel count: {:?}", e))?;
+            validate_channel_volumes(volumes, count as usize)?;
+            channel_audio_volume.SetAllVolumes(volumes, std::ptr::null())
+                .map_err(|e| format!("Failed to set channel volumes: {:?}", e))
+        }
+    }
+
+    fn get_session_mute(&self, session_id: &str) -> Result<bool, String> {
+        let simple_audio_volume = find_session_volume(session_id)?;
+        unsafe {
+            simple_audio_volume.GetMute()
+                .map(|muted| muted.as_bool())
+                .map_err(|e| format!("Failed to get mute state: {:?}", e))
+        }
+    }
+
+    fn set_session_mute(&self, session_id: &str, muted: bool) -> Result<(), String> {
+        let simple_audio_volume = find_session_volume(session_id)?;
+        unsafe {
+            simple_audio_volume.SetMute(muted, std::ptr::null())
+                .map_err(|e| format!("Failed to set mute state: {:?}", e))
+        }
+    }
+
+    fn get_peak_levels(&self) -> Result<PeakLevels, String> {
+        get_peak_levels()
+    }
+
+    fn describe_executable(&self, executable_path: &str) -> Option<String> {
+        file_description(executable_path)
+    }
+
+    fn subscribe(&self) -> Result<Receiver<SessionEvent>, String> {
+        let receiver = self.subscribers.subscribe()?;
+        self.start_watcher();
+        Ok(receiver)
+    }
+
+    fn open_capture(&self, device_id: &str) -> Result<Box<dyn CaptureEndpoint>, String> {
+        Ok(Box::new(WasapiCapture::open(device_id)?))
+    }
+
+    fn open_render(&self, device_id: &str) -> Result<Box<dyn RenderEndpoint>, String> {
+        Ok(Box::new(WasapiRender::open(device_id)?))
+    }
+}
+
+fn initialize_com() -> Result<(), String> {
+    unsafe {
+        match CoInitializeEx(None, COINIT_APARTMENTTHREADED) {
+            // The thread already joined a multithreaded apartment, which works for us too
+            Err(e) if e.code() == RPC_E_CHANGED_MODE => Ok(()),
+            result => result.map_err(|e| format!("Failed to initialize COM: {:?}", e)),
+        }
+    }
+}
+
+fn device_enumerator() -> Result<IMMDeviceEnumerator, String> {
+    initialize_com()?;
+
+    unsafe {
+        CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+            .map_err(|e| format!("Failed to create device enumerator: {:?}", e))
+    }
+}
+
+fn devices(enumerator: &IMMDeviceEnumerator, state_mask: u32) -> Result<Vec<IMMDevice>, String> {
+    unsafe {
+        let collection = enumerator.EnumAudioEndpoints(eAll, state_mask)
+            .map_err(|e| format!("Failed to enumerate audio endpoints: {:?}", e))?;
+
+        let count = collection.GetCount()
+            .map_err(|e| format!("Failed to get endpoint count: {:?}", e))?;
+
+        (0..count)
+            .map(|i| {
+                collection.Item(i)
+                    .map_err(|e| format!("Failed to get audio endpoint: {:?}", e))
+            })
+            .collect()
+    }
+}
+
+fn device_id(device: &IMMDevice) -> Result<String, String> {
+    unsafe {
+        device.GetId()
+            .map(take_pwstr)
+            .map_err(|e| format!("Failed to get device ID: {:?}", e))
+    }
+}
+
+fn default_device_id(enumerator: &IMMDeviceEnumerator, flow: EDataFlow, role: ERole) -> Option<String> {
+    // Fails with E_NOTFOUND when no endpoint of this flow exists, which is not an error for us
+    unsafe { enumerator.GetDefaultAudioEndpoint(flow, role).ok() }
+        .and_then(|device| device_id(&device).ok())
+}
+
+fn friendly_name(device: &IMMDevice) -> Result<String, String> {
+    unsafe {
+        let store = device.OpenPropertyStore(STGM_READ)
+            .map_err(|e| format!("Failed to open device property store: {:?}", e))?;
+
+        let mut value = store.GetValue(&PKEY_Device_FriendlyName)
+            .map_err(|e| format!("Failed to get device friendly name: {:?}", e))?;
+
+        let name = PropVariantToStringAlloc(&value).map(take_pwstr).unwrap_or_default();
+        let _ = PropVariantClear(&mut value);
+        Ok(name)
+    }
+}
+
+fn enumerate_devices() -> Result<Vec<AudioDevice>, String> {
+    let enumerator = device_enumerator()?;
+
+    let roles = [
+        (DeviceRole::Console, eConsole),
+        (DeviceRole::Multimedia, eMultimedia),
+        (DeviceRole::Communications, eCommunications),
+    ];
+    let mut defaults = Vec::new();
+    for data_flow in [eRender, eCapture] {
+        for (role, erole) in roles {
+            if let Some(id) = default_device_id(&enumerator, data_flow, erole) {
+                defaults.push((id, role));
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    for device in devices(&enumerator, DEVICE_STATEMASK_ALL)? {
+        unsafe {
+            let id = device_id(&device)?;
+            let flow = device_flow(&device)?;
+
+            let state = match device.GetState()
+                .map_err(|e| format!("Failed to get device state: {:?}", e))?
+            {
+                DEVICE_STATE_ACTIVE => DeviceState::Active,
+                DEVICE_STATE_DISABLED => DeviceState::Disabled,
+                DEVICE_STATE_NOTPRESENT => DeviceState::NotPresent,
+                _ => DeviceState::Unplugged,
+            };
+
+            // Disconnected endpoints may have no property store left
+            let name = friendly_name(&device).unwrap_or_default();
+
+            let default_roles = defaults.iter()
+                .filter(|(default_id, _)| *default_id == id)
+                .map(|(_, role)| *role)
+                .collect();
+
+            result.push(AudioDevice {
+                id,
+                name,
+                flow,
+                state,
+                default_roles,
+            });
+        }
+    }
+    Ok(result)
+}
+
+// Speaker mask of the device mix format, which shared-mode sessions are mixed into.
+// None when the format carries no mask (plain WAVEFORMATEX) or cannot be read.
+fn device_channel_mask(device: &IMMDevice) -> Option<u32> {
+    unsafe {
+        let audio_client: IAudioClient = device.Activate(CLSCTX_ALL, None).ok()?;
+        let format = audio_client.GetMixFormat().ok()?;
+
+        let channel_mask = if (*format).wFormatTag == WAVE_FORMAT_EXTENSIBLE {
+            let extensible = format as *const WAVEFORMATEXTENSIBLE;
+            // The struct is packed, so the field must be read unaligned
+            Some(std::ptr::addr_of!((*extensible).dwChannelMask).read_unaligned())
+        } else {
+            None
+        };
+
+        CoTaskMemFree(Some(format as *const _));
+        channel_mask
+    }
+}
+
+fn channel_layout(channel_mask: Option<u32>, channel_count: u32) -> Vec<ChannelPosition> {
+    match channel_mask {
+        Some(channel_mask) if channel_mask.count_ones() == channel_count => {
+            channel_layout_from_mask(channel_mask, channel_count as usize)
+        }
+        _ => default_channel_layout(channel_count as usize),
+    }
+}
+
+// Session controls of every active render and capture device, tagged with the device ID
+// and the speaker mask of the device mix format
+fn session_controls() -> Result<Vec<(String, Option<u32>, IAudioSessionControl)>, String> {
+    let enumerator = device_enumerator()?;
+    let mut controls = Vec::new();
+
+    for device in devices(&enumerator, DEVICE_STATE_ACTIVE)? {
+        let device_id = device_id(&device)?;
+        let channel_mask = device_channel_mask(&device);
+
+        unsafe {
+            let session_manager2: IAudioSessionManager2 = device.Activate(CLSCTX_ALL, None)
+                .map_err(|e| format!("Failed to activate audio session manager: {:?}", e))?;
+
+            let session_enumerator = session_manager2.GetSessionEnumerator()
+                .map_err(|e| format!("Failed to get session enumerator: {:?}", e))?;
+
+            let count = session_enumerator.GetCount()
+                .map_err(|e| format!("Failed to get session count: {:?}", e))?;
+
+            for i in 0..count {
+                let session_control = session_enumerator.GetSession(i)
+                    .map_err(|e| format!("Failed to get session control: {:?}", e))?;
+                controls.push((device_id.clone(), channel_mask, session_control));
+            }
+        }
+    }
+    Ok(controls)
+}
+
+pub fn find_device(device_id: &str) -> Result<IMMDevice, String> {
+    let enumerator = device_enumerator()?;
+
+    unsafe {
+        enumerator.GetDevice(&HSTRING::from(device_id))
+            .map_err(|e| format!("Failed to get device {}: {:?}", device_id, e))
+    }
+}
+
+pub fn device_flow(device: &IMMDevice) -> Result<DeviceFlow, String> {
+    unsafe {
+        let endpoint: IMMEndpoint = device.cast()
+            .map_err(|e| format!("Failed to cast to IMMEndpoint: {:?}", e))?;
+        match endpoint.GetDataFlow().map_err(|e| format!("Failed to get data flow: {:?}", e))? {
+            data_flow if data_flow == eCapture => Ok(DeviceFlow::Capture),
+            _ => Ok(DeviceFlow::Render),
+        }
+    }
+}
+
+fn find_endpoint_volume(device_id: &str) -> Result<IAudioEndpointVolume, String> {
+    let device = find_device(device_id)?;
+
+    unsafe {
+        device.Activate(CLSCTX_ALL, None)
+            .map_err(|e| format!("Failed to activate endpoint volume: {:?}", e))
+    }
+}
+
+fn find_session_volume(session_id: &str) -> Result<ISimpleAudioVolume, String> {
+    let (_, control) = session_entries()?
+        .into_iter()
+        .find(|(session, _)| session.id == session_id)
+        .ok_or_else(|| session_not_found(session_id))?;
+
+    control.cast()
+        .map_err(|e| format!("Failed to cast to ISimpleAudioVolume: {:?}", e))
+}
+
+fn find_channel_volume(session_id: &str) -> Result<IChannelAudioVolume, String> {
+    let (_, control) = session_entries()?
+        .into_iter()
+        .find(|(session, _)| session.id == session_id)
+        .ok_or_else(|| session_not_found(session_id))?;
+
+    control.cast()
+        .map_err(|e| format!("Failed to cast to IChannelAudioVolume: {:?}", e))
+}
+
+fn get_peak_levels() -> Result<PeakLevels, String> {
+    let mut levels = PeakLevels::default();
+
+    for (session, control) in session_entries()? {
+        let meter: IAudioMeterInformation = control.cast()
+            .map_err(|e| format!("Failed to cast to IAudioMeterInformation: {:?}", e))?;
+        let peak = unsafe { meter.GetPeakValue() }
+            .map_err(|e| format!("Failed to get session peak: {:?}", e))?;
+        levels.sessions.insert(session.id, peak);
+    }
+
+    let enumerator = device_enumerator()?;
+    for device in devices(&enumerator, DEVICE_STATE_ACTIVE)? {
+        unsafe {
+            let meter: IAudioMeterInformation = device.Activate(CLSCTX_ALL, None)
+                .map_err(|e| format!("Failed to activate audio meter: {:?}", e))?;
+            let peak = meter.GetPeakValue()
+                .map_err(|e| format!("Failed to get device peak: {:?}", e))?;
+            levels.devices.insert(device_id(&device)?, peak);
+        }
+    }
+    Ok(levels)
+}
+
+fn enumerate_sessions() -> Result<Vec<AudioSession>, String> {
+    Ok(session_entries()?.into_iter().map(|(session, _)| session).collect())
+}
+
+// Describes every session on every active device, paired with its control for follow-up calls
+fn session_entries() -> Result<Vec<(AudioSession, IAudioSessionControl)>, String> {
+    let mut entries = Vec::new();
+    let mut identities = Vec::new();
+
+    for (device_id, channel_mask, session_control) in session_controls()? {
+        unsafe {
+            let session_control2: IAudioSessionControl2 = session_control.cast()
+                .map_err(|e| format!("Failed to cast to IAudioSessionControl2: {:?}", e))?;
+
+            let process_id = session_control2.GetProcessId()
+                .map_err(|e| format!("Failed to get process ID: {:?}", e))?;
+
+            let display_name_pwstr = session_control2.GetDisplayName()
+                .map_err(|e| format!("Failed to get display name: {:?}", e))?;
+            let display_name = take_pwstr(display_name_pwstr);
+
+            let icon_path = session_control.GetIconPath()
+                .map(take_pwstr)
+                .map_err(|e| format!("Failed to get icon path: {:?}", e))?;
+
+            let session_identifier = session_control2.GetSessionIdentifier()
+                .map_err(|e| format!("Failed to get session identifier: {:?}", e))?;
+            let instance_identifier = session_control2.GetSessionInstanceIdentifier()
+                .map_err(|e| format!("Failed to get session instance identifier: {:?}", e))?;
+
+            let simple_audio_volume: ISimpleAudioVolume = session_control.cast()
+                .map_err(|e| format!("Failed to cast to ISimpleAudioVolume: {:?}", e))?;
+
+            let volume = simple_audio_volume.GetMasterVolume()
+                .map_err(|e| format!("Failed to get master volume: {:?}", e))?;
+
+            let muted = simple_audio_volume.GetMute()
+                .map_err(|e| format!("Failed to get mute state: {:?}", e))?;
+
+            let channel_audio_volume: IChannelAudioVolume = session_control.cast()
+                .map_err(|e| format!("Failed to cast to IChannelAudioVolume: {:?}", e))?;
+
+            let channel_count = channel_audio_volume.GetChannelCount()
+                .map_err(|e| format!("Failed to get channel count: {:?}", e))?;
+
+            let state = match session_control.GetState()
+                .map_err(|e| format!("Failed to get session state: {:?}", e))?
+            {
+                AudioSessionStateActive => SessionState::Active,
+                AudioSessionStateExpired => SessionState::Expired,
+                _ => SessionState::Inactive,
+            };
+
+            let executable_path = process_image_path(process_id);
+
+            identities.push(SessionIdentity {
+                session_identifier: take_pwstr(session_identifier),
+                instance_identifier: take_pwstr(instance_identifier),
+                executable_path: executable_path.clone(),
+            });
+
+            entries.push((
+                AudioSession {
+                    id: String::new(),
+                    display_name,
+                    volume,
+                    muted: muted.as_bool(),
+                    process_id,
+                    executable_path,
+                    icon_path,
+                    state,
+                    device_id,
+                    channel_count,
+                    channel_layout: channel_layout(channel_mask, channel_count),
+                },
+                session_control,
+            ));
+        }
+    }
+
+    for ((session, _), id) in entries.iter_mut().zip(assign_session_ids(&identities)) {
+        session.id = id;
+    }
+    Ok(entries)
+}
+
+// Full Win32 path of the process executable, empty for the system sounds session (PID 0)
+// and for processes we are not allowed to query
+fn process_image_path(process_id: u32) -> String {
+    if process_id == 0 {
+        return String::new();
+    }
+
+    unsafe {
+        let Ok(process) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id) else {
+            return String::new();
+        };
+
+        let mut buffer = [0u16; 1024];
+        let mut size = buffer.len() as u32;
+        let result = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, PWSTR(buffer.as_mut_ptr()), &mut size);
+        let _ = CloseHandle(process);
+
+        match result {
+            Ok(()) => String::from_utf16_lossy(&buffer[..size as usize]),
+            Err(_) => String::new(),
+        }
+    }
+}
+
+// FileDescription from the executable's version resource, in its first listed language
+fn file_description(executable_path: &str) -> Option<String> {
+    let path = HSTRING::from(executable_path);
+
+    unsafe {
+        let size = GetFileVersionInfoSizeW(&path, None);
+        if size == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; size as usize];
+        GetFileVersionInfoW(&path, 0, size, data.as_mut_ptr() as *mut _).ok()?;
+
+        // Each translation is a (language, code page) pair of u16
+        let translation = query_version_value(&data, "\\VarFileInfo\\Translation")?;
+        if translation.len() < 4 {
+            return None;
+        }
+        let language = u16::from_le_bytes([translation[0], translation[1]]);
+        let code_page = u16::from_le_bytes([translation[2], translation[3]]);
+
+        let key = format!("\\StringFileInfo\\{:04x}{:04x}\\FileDescription", language, code_page);
+        let value = query_version_value(&data, &key)?;
+        let wide: Vec<u16> = value.chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        let description = String::from_utf16_lossy(&wide).trim().to_string();
+        (!description.is_empty()).then_some(description)
+    }
+}
+
+// Raw bytes of one VerQueryValueW entry. The length is in characters for strings and in
+// bytes for binary values, so the slice is sized for UTF-16 and may run past a binary value.
+fn query_version_value<'a>(data: &'a [u8], key: &str) -> Option<&'a [u8]> {
+    let mut buffer = std::ptr::null_mut();
+    let mut length = 0u32;
+
+    unsafe {
+        if !VerQueryValueW(data.as_ptr() as *const _, &HSTRING::from(key), &mut buffer, &mut length).as_bool()
+            || buffer.is_null()
+        {
+            return None;
+        }
+        let start = buffer as usize - data.as_ptr() as usize;
+        let end = (start + length as usize * 2).min(data.len());
+        data.get(start..end)
+    }
+}
+
+// Converts a COM-allocated string to a Rust string and frees the COM allocation
+fn take_pwstr(pwstr: PWSTR) -> String {
+    if pwstr.is_null() {
+        return String::new();
+    }
+    unsafe {
+        let value = pwstr.to_string().unwrap_or_default();
+        CoTaskMemFree(Some(pwstr.0 as *const _));
+        value
+    }
+}
@@ -0,0 +1,254 @@
+use std::thread;
+use std::time::Duration;
+
+use windows::Win32::Media::Audio::{
+    IAudioCaptureClient,
+    IAudioClient,
+    IAudioRenderClient,
+    AUDCLNT_BUFFERFLAGS_SILENT,
+    AUDCLNT_SHAREMODE_SHARED,
+    AUDCLNT_STREAMFLAGS_LOOPBACK,
+    WAVEFORMATEX,
+};
+use windows::Win32::System::Com::{CoTaskMemFree, CLSCTX_ALL};
+
+use crate::audio_endpoints::{AudioFormat, CaptureEndpoint, RenderEndpoint};
+use crate::audio_manager::DeviceFlow;
+use crate::sample_format::{decode, parse_wave_format, Dither, Quantizer, SampleFormat};
+use crate::wasapi_backend::{device_flow, find_device};
+
+// Shared-mode buffer length, in 100 ns units
+const BUFFER_DURATION: i64 = 200 * 10_000;
+
+// How long a render write waits for the device to make room
+const RENDER_WAIT: Duration = Duration::from_millis(1);
+
+// Initializes a shared-mode client in the device's mix format, whatever its sample rate,
+// channel count and sample format; the endpoints convert between that and f32
+fn initialize_client(device_id: &str, stream_flags: u32) -> Result<(IAudioClient, AudioFormat, SampleFormat), String> {
+    let device = find_device(device_id)?;
+
+    unsafe {
+        let audio_client: IAudioClient = device.Activate(CLSCTX_ALL, None)
+            .map_err(|e| format!("Failed to activate audio client: {:?}", e))?;
+
+        let mix_format = audio_client.GetMixFormat()
+            .map_err(|e| format!("Failed to get mix format: {:?}", e))?;
+        let length = std::mem::size_of::<WAVEFORMATEX>() + (*mix_format).cbSize as usize;
+        // The mix format is always accepted in shared mode; it is freed whatever happens
+        let negotiated = parse_wave_format(std::slice::from_raw_parts(mix_format as *const u8, length))
+            .and_then(|negotiated| {
+                audio_client.Initialize(
+                    AUDCLNT_SHAREMODE_SHARED,
+                    stream_flags,
+                    BUFFER_DURATION,
+                    0,
+                    mix_format,
+                    None,
+                ).map_err(|e| format!("Failed to initialize audio client: {:?}", e))?;
+                Ok(negotiated)
+            });
+        CoTaskMemFree(Some(mix_format as *const _));
+
+        let (format, sample_format) = negotiated?;
+        Ok((audio_client, format, sample_format))
+    }
+}
+
+// Captures from a capture device, or from a render device in loopback
+pub struct WasapiCapture {
+    audio_client: IAudioClient,
+    capture_client: IAudioCaptureClient,
+    format: AudioFormat,
+    sample_format: SampleFormat,
+    // Rest of a packet that did not fit into the last read, from `pending_start` on. Sized
+    // for the device buffer, which no packet exceeds, so reads do not allocate.
+    pending: Vec<f32>,
+    pending_start: usize,
+}
+
+impl WasapiCapture {
+    pub fn open(device_id: &str) -> Result<Self, String> {
+        let stream_flags = match device_flow(&find_device(device_id)?)? {
+            DeviceFlow::Render => AUDCLNT_STREAMFLAGS_LOOPBACK,
+            DeviceFlow::Capture => 0,
+        };
+        let (audio_client, format, sample_format) = initialize_client(device_id, stream_flags)?;
+
+        unsafe {
+            let buffer_frames = audio_client.GetBufferSize()
+                .map_err(|e| format!("Failed to get capture buffer size: {:?}", e))?;
+            let capture_client: IAudioCaptureClient = audio_client.GetService()
+                .map_err(|e| format!("Failed to get capture client: {:?}", e))?;
+            audio_client.Start().map_err(|e| format!("Failed to start capture: {:?}", e))?;
+
+            Ok(Self {
+                audio_client,
+                capture_client,
+                format,
+                sample_format,
+                pending: Vec::with_capacity(buffer_frames as usize * format.channels as usize),
+                pending_start: 0,
+            })
+        }
+    }
+
+    fn drain_pending(&mut self, buffer: &mut [f32]) -> usize {
+        let count = (self.pending.len() - self.pending_start).min(buffer.len());
+        buffer[..count].copy_from_slice(&self.pending[self.pending_start..self.pending_start + count]);
+        self.pending_start += count;
+        count
+    }
+}
+
+// Converts one packet of `sample_format` samples to f32
+unsafe fn decode_packet(sample_format: SampleFormat, data: *const u8, flags: u32, target: &mut [f32]) {
+    if flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32 != 0 {
+        target.fill(0.0);
+    } else {
+        let bytes = std::slice::from_raw_parts(data, target.len() * sample_format.bytes_per_sample());
+        decode(sample_format, bytes, target);
+    }
+}
+
+impl CaptureEndpoint for WasapiCapture {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    // Packets are released as soon as they are read; the part of one that does not fit
+    // into `buffer` is kept for the next read, whatever the device's packet size
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize, String> {
+        let channels = self.format.channels as usize;
+        let mut written = self.drain_pending(buffer);
+        if self.pending_start < self.pending.len() {
+            return Ok(written);
+        }
+
+        unsafe {
+            while written < buffer.len() {
+                let packet_frames = self.capture_client.GetNextPacketSize()
+                    .map_err(|e| format!("Failed to get capture packet size: {:?}", e))?;
+                if packet_frames == 0 {
+                    break;
+                }
+
+                let mut data = std::ptr::null_mut();
+                let mut frames = 0;
+                let mut flags = 0;
+                self.capture_client.GetBuffer(&mut data, &mut frames, &mut flags, None, None)
+                    .map_err(|e| format!("Failed to get capture buffer: {:?}", e))?;
+
+                let count = frames as usize * channels;
+                let fits = written + count <= buffer.len();
+                if fits {
+                    decode_packet(self.sample_format, data, flags, &mut buffer[written..written + count]);
+                } else {
+                    self.pending.resize(count, 0.0);
+                    self.pending_start = 0;
+                    decode_packet(self.sample_format, data, flags, &mut self.pending);
+                }
+
+                self.capture_client.ReleaseBuffer(frames)
+                    .map_err(|e| format!("Failed to release capture buffer: {:?}", e))?;
+
+                if fits {
+                    written += count;
+                } else {
+                    written += self.drain_pending(&mut buffer[written..]);
+                }
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+impl Drop for WasapiCapture {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.audio_client.Stop();
+        }
+    }
+}
+
+pub struct WasapiRender {
+    audio_client: IAudioClient,
+    render_client: IAudioRenderClient,
+    format: AudioFormat,
+    quantizer: Quantizer,
+    buffer_frames: u32,
+}
+
+impl WasapiRender {
+    pub fn open(device_id: &str) -> Result<Self, String> {
+        if device_flow(&find_device(device_id)?)? != DeviceFlow::Render {
+            return Err(format!("Failed to open {} for rendering: it is a capture device", device_id));
+        }
+        let (audio_client, format, sample_format) = initialize_client(device_id, 0)?;
+
+        unsafe {
+            let buffer_frames = audio_client.GetBufferSize()
+                .map_err(|e| format!("Failed to get render buffer size: {:?}", e))?;
+            let render_client: IAudioRenderClient = audio_client.GetService()
+                .map_err(|e| format!("Failed to get render client: {:?}", e))?;
+            audio_client.Start().map_err(|e| format!("Failed to start render: {:?}", e))?;
+
+            Ok(Self {
+                audio_client,
+                render_client,
+                format,
+                quantizer: Quantizer::new(sample_format, format.channels as usize, Dither::default()),
+                buffer_frames,
+            })
+        }
+    }
+}
+
+impl RenderEndpoint for WasapiRender {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        let channels = self.format.channels as usize;
+        let mut offset = 0;
+
+        unsafe {
+            while offset < samples.len() {
+                let padding = self.audio_client.GetCurrentPadding()
+                    .map_err(|e| format!("Failed to get render padding: {:?}", e))?;
+                let frames = ((self.buffer_frames - padding) as usize).min((samples.len() - offset) / channels);
+                if frames == 0 {
+                    thread::sleep(RENDER_WAIT);
+                    continue;
+                }
+
+                let data = self.render_client.GetBuffer(frames as u32)
+                    .map_err(|e| format!("Failed to get render buffer: {:?}", e))?;
+                let count = frames * channels;
+                let bytes = std::slice::from_raw_parts_mut(data, count * self.quantizer.sample_format().bytes_per_sample());
+                self.quantizer.encode(&samples[offset..offset + count], bytes);
+                self.render_client.ReleaseBuffer(frames as u32, 0)
+                    .map_err(|e| format!("Failed to release render buffer: {:?}", e))?;
+                offset += count;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn buffered_frames(&self) -> Result<Option<usize>, String> {
+        let padding = unsafe { self.audio_client.GetCurrentPadding() }
+            .map_err(|e| format!("Failed to get render padding: {:?}", e))?;
+        Ok(Some(padding as usize))
+    }
+}
+
+impl Drop for WasapiRender {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.audio_client.Stop();
+        }
+    }
+}
@@ -1,4 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 
 interface AudioSession {
   id: string;
@@ -6,6 +7,40 @@ interface AudioSession {
   volume: number;
   muted: boolean;
   process_id: number;
+  executable_path: string;
+  state: "active" | "inactive" | "expired";
+  device_id: string;
+  channel_count: number;
+  channel_layout: string[];
+}
+
+interface AudioDevice {
+  id: string;
+  name: string;
+  flow: "render" | "capture";
+  state: "active" | "disabled" | "not_present" | "unplugged";
+  default_roles: ("console" | "multimedia" | "communications")[];
+}
+
+// Payloads of the session-* events emitted by the backend session watcher
+type SessionEvent =
+  | ({ type: "added" } & AudioSession)
+  | { type: "removed"; id: string }
+  | { type: "volume_changed"; id: string; volume: number }
+  | { type: "mute_changed"; id: string; muted: boolean }
+  | { type: "state_changed"; id: string; state: AudioSession["state"] };
+
+interface MeterReading {
+  peak: number;
+  rms: number | null;
+  peak_hold: number;
+  clipping: boolean;
+}
+
+// Payload of the throttled "audio-levels" event
+interface LevelSnapshot {
+  sessions: Record<string, MeterReading>;
+  devices: Record<string, MeterReading>;
 }
 
 interface UpdateInfo {
@@ -23,35 +58,227 @@ interface UpdateConfig {
 }
 
 let audioSessions: AudioSession[] = [];
+let audioDevices: AudioDevice[] = [];
+// Empty means sessions of every device are shown
+let selectedDeviceId = "";
+let masterMuted = false;
+// Last balance set per session; the backend only reports the resulting channel volumes
+const sessionBalances = new Map<string, number>();
+// Devices of the boost pipelines started per session; boosts above 100% are only applied
+// to the samples while one runs
+const sessionPipelines = new Map<string, { captureDeviceId: string; renderDeviceId: string }>();
 let updateCheckInterval: number | null = null;
 
+async function loadAudioDevices() {
+  try {
+    audioDevices = await invoke("get_audio_devices");
+    renderDeviceSelect();
+    loadMasterVolume();
+  } catch (error) {
+    console.error("Failed to load audio devices:", error);
+  }
+}
+
 async function loadAudioSessions() {
   try {
-    audioSessions = await invoke("get_audio_sessions");
+    audioSessions = await invoke("get_audio_sessions", { deviceId: selectedDeviceId || null });
     renderAudioSessions();
   } catch (error) {
     console.error("Failed to load audio sessions:", error);
   }
 }
 
-async function setVolume(processId: number, volume: number) {
+// The master control follows the selected device, or the default playback device for "All devices"
+function masterDeviceId(): string | undefined {
+  if (selectedDeviceId) return selectedDeviceId;
+  return audioDevices.find((device) => device.flow === "render" && device.default_roles.includes("console"))?.id;
+}
+
+async function loadMasterVolume() {
+  const deviceId = masterDeviceId();
+  if (!deviceId) return;
+  try {
+    const volume: number = await invoke("get_device_volume", { deviceId });
+    masterMuted = await invoke("get_device_mute", { deviceId });
+    renderMasterControl(volume);
+  } catch (error) {
+    console.error("Failed to load master volume:", error);
+  }
+}
+
+async function setMasterVolume(volume: number) {
+  const deviceId = masterDeviceId();
+  if (!deviceId) return;
+  try {
+    await invoke("set_device_volume", { deviceId, volume });
+    renderMasterControl(volume);
+  } catch (error) {
+    console.error("Failed to set master volume:", error);
+  }
+}
+
+async function toggleMasterMute() {
+  const deviceId = masterDeviceId();
+  if (!deviceId) return;
   try {
-    await invoke("set_app_volume", { processId, volume });
-    await loadAudioSessions(); // Refresh the list
+    await invoke("set_device_mute", { deviceId, muted: !masterMuted });
+    masterMuted = !masterMuted;
+    renderMasterControl();
+  } catch (error) {
+    console.error("Failed to toggle master mute:", error);
+  }
+}
+
+function renderMasterControl(volume?: number) {
+  const slider = document.querySelector("#master-slider") as HTMLInputElement | null;
+  const label = document.querySelector("#master-volume");
+  const muteButton = document.querySelector("#master-mute-btn");
+  if (!slider || !label || !muteButton) return;
+
+  if (volume !== undefined) {
+    slider.value = String(volume);
+    label.textContent = `${Math.round(volume * 100)}%`;
+  }
+  muteButton.textContent = masterMuted ? "🔇" : "🔊";
+  muteButton.classList.toggle("muted", masterMuted);
+}
+
+function renderDeviceSelect() {
+  const select = document.querySelector("#device-select") as HTMLSelectElement | null;
+  if (!select) return;
+
+  const options = audioDevices
+    .filter((device) => device.state === "active")
+    .map((device) => {
+      const label = device.default_roles.includes("console") ? `${device.name} (default)` : device.name;
+      return `<option value="${device.id}">${device.flow === "capture" ? "🎤" : "🔊"} ${label}</option>`;
+    });
+  select.innerHTML = `<option value="">All devices</option>` + options.join("");
+  select.value = selectedDeviceId;
+}
+
+async function setVolume(sessionId: string, volume: number) {
+  try {
+    await invoke("set_app_volume", { sessionId, volume });
   } catch (error) {
     console.error("Failed to set volume:", error);
   }
 }
 
-async function toggleMute(processId: number) {
+async function setBalance(sessionId: string, balance: number) {
+  try {
+    await invoke("set_app_balance", { sessionId, balance });
+    sessionBalances.set(sessionId, balance);
+  } catch (error) {
+    console.error("Failed to set balance:", error);
+  }
+}
+
+async function toggleMute(sessionId: string) {
   try {
-    await invoke("toggle_app_mute", { processId });
-    await loadAudioSessions(); // Refresh the list
+    await invoke("toggle_app_mute", { sessionId });
   } catch (error) {
     console.error("Failed to toggle mute:", error);
   }
 }
 
+async function toggleBoostPipeline(sessionId: string, captureDeviceId: string, renderDeviceId: string) {
+  try {
+    const running: boolean = await invoke("get_boost_pipeline_running", { sessionId });
+    if (running) {
+      await invoke("stop_boost_pipeline", { sessionId });
+      sessionPipelines.delete(sessionId);
+    } else {
+      await invoke("start_boost_pipeline", { sessionId, captureDeviceId, renderDeviceId });
+      sessionPipelines.set(sessionId, { captureDeviceId, renderDeviceId });
+    }
+  } catch (error) {
+    console.error("Failed to toggle boost pipeline:", error);
+    sessionPipelines.delete(sessionId);
+  }
+  renderAudioSessions();
+}
+
+function deviceOptions(devices: AudioDevice[], selectedId: string | undefined): string {
+  return devices
+    .map((device) => {
+      const icon = device.flow === "capture" ? "🎤" : "🔊";
+      const selected = device.id === selectedId ? " selected" : "";
+      return `<option value="${device.id}"${selected}>${icon} ${device.name}</option>`;
+    })
+    .join("");
+}
+
+function renderBoostPipeline(session: AudioSession): string {
+  const activeDevices = audioDevices.filter((device) => device.state === "active");
+  const pipeline = sessionPipelines.get(session.id);
+  // Capturing a render device records its loopback, so any active device can be the source
+  const captureDeviceId = pipeline?.captureDeviceId ?? activeDevices.find((device) => device.flow === "capture")?.id;
+  const renderDeviceId = pipeline?.renderDeviceId ?? session.device_id;
+  return `
+      <div class="boost-pipeline" data-session-id="${session.id}">
+        <select class="pipeline-capture" ${pipeline ? "disabled" : ""}>
+          ${deviceOptions(activeDevices, captureDeviceId)}
+        </select>
+        <span>→</span>
+        <select class="pipeline-render" ${pipeline ? "disabled" : ""}>
+          ${deviceOptions(activeDevices.filter((device) => device.flow === "render"), renderDeviceId)}
+        </select>
+        <button class="pipeline-btn ${pipeline ? "running" : ""}">${pipeline ? "Stop boost" : "Start boost"}</button>
+      </div>`;
+}
+
+function applySessionEvent(event: SessionEvent) {
+  if (event.type === "added") {
+    const { type, ...session } = event;
+    if (selectedDeviceId && session.device_id !== selectedDeviceId) return;
+    audioSessions = audioSessions.filter((s) => s.id !== session.id).concat(session);
+  } else if (event.type === "removed") {
+    audioSessions = audioSessions.filter((s) => s.id !== event.id);
+    sessionPipelines.delete(event.id);
+  } else {
+    const session = audioSessions.find((s) => s.id === event.id);
+    if (!session) return;
+    if (event.type === "volume_changed") session.volume = event.volume;
+    if (event.type === "mute_changed") session.muted = event.muted;
+    if (event.type === "state_changed") session.state = event.state;
+  }
+  renderAudioSessions();
+}
+
+// Meters are updated in place so level events do not rebuild the session list
+function applyLevelSnapshot(snapshot: LevelSnapshot) {
+  for (const [sessionId, reading] of Object.entries(snapshot.sessions)) {
+    const meter = document.querySelector(`.level-meter[data-session-id="${sessionId}"]`);
+    if (!meter) continue;
+    (meter.querySelector(".level-fill") as HTMLElement).style.width = `${Math.min(reading.peak, 1) * 100}%`;
+    (meter.querySelector(".level-hold") as HTMLElement).style.left = `${Math.min(reading.peak_hold, 1) * 100}%`;
+    meter.classList.toggle("clipping", reading.clipping);
+  }
+}
+
+async function startLevelMetering() {
+  await listen<LevelSnapshot>("audio-levels", (event) => applyLevelSnapshot(event.payload));
+  try {
+    await invoke("start_level_metering", { rateHz: 30 });
+  } catch (error) {
+    console.error("Failed to start level metering:", error);
+  }
+}
+
+async function subscribeToSessionEvents() {
+  const eventNames = [
+    "session-added",
+    "session-removed",
+    "session-volume-changed",
+    "session-mute-changed",
+    "session-state-changed",
+  ];
+  for (const name of eventNames) {
+    await listen<SessionEvent>(name, (event) => applySessionEvent(event.payload));
+  }
+}
+
 async function checkForUpdates() {
   try {
     const updateInfo: UpdateInfo | null = await invoke("check_for_updates");
@@ -167,10 +394,14 @@ function renderAudioSessions() {
           <span class="session-name">${session.display_name || `Process ${session.process_id}`}</span>
           <span class="session-volume">${extendedVolumePercent}%</span>
         </div>
-        <button class="mute-btn ${session.muted ? 'muted' : ''}" data-process-id="${session.process_id}">
+        <button class="mute-btn ${session.muted ? 'muted' : ''}" data-session-id="${session.id}">
           ${session.muted ? '🔇' : '🔊'}
         </button>
       </div>
+      <div class="level-meter" data-session-id="${session.id}">
+        <div class="level-fill"></div>
+        <div class="level-hold"></div>
+      </div>
       <div class="volume-control">
         <input 
           type="range" 
@@ -179,13 +410,28 @@ function renderAudioSessions() {
           max="7.77" 
           step="0.01" 
           value="${session.volume}" 
-          data-process-id="${session.process_id}"
+          data-session-id="${session.id}"
         />
         <div class="volume-markers">
           <span class="marker" style="left: 12.87%">100%</span>
           <span class="marker" style="left: 100%">777%</span>
         </div>
       </div>
+      ${session.channel_count >= 2 ? `
+      <div class="balance-control">
+        <span>L</span>
+        <input
+          type="range"
+          class="balance-slider"
+          min="-1"
+          max="1"
+          step="0.05"
+          value="${sessionBalances.get(session.id) ?? 0}"
+          data-session-id="${session.id}"
+        />
+        <span>R</span>
+      </div>` : ""}
+      ${renderBoostPipeline(session)}
     `;
 
     container.appendChild(sessionElement);
@@ -195,30 +441,62 @@ function renderAudioSessions() {
   document.querySelectorAll(".volume-slider").forEach((slider) => {
     slider.addEventListener("input", (e) => {
       const target = e.target as HTMLInputElement;
-      const processId = parseInt(target.dataset.processId!);
+      const sessionId = target.dataset.sessionId!;
       const volume = parseFloat(target.value);
-      setVolume(processId, volume);
+      setVolume(sessionId, volume);
+    });
+  });
+
+  document.querySelectorAll(".balance-slider").forEach((slider) => {
+    slider.addEventListener("input", (e) => {
+      const target = e.target as HTMLInputElement;
+      setBalance(target.dataset.sessionId!, parseFloat(target.value));
+    });
+  });
+
+  document.querySelectorAll(".boost-pipeline").forEach((row) => {
+    const element = row as HTMLElement;
+    element.querySelector(".pipeline-btn")?.addEventListener("click", () => {
+      const capture = element.querySelector(".pipeline-capture") as HTMLSelectElement;
+      const render = element.querySelector(".pipeline-render") as HTMLSelectElement;
+      toggleBoostPipeline(element.dataset.sessionId!, capture.value, render.value);
     });
   });
 
   document.querySelectorAll(".mute-btn").forEach((btn) => {
     btn.addEventListener("click", (e) => {
       const target = e.target as HTMLButtonElement;
-      const processId = parseInt(target.dataset.processId!);
-      toggleMute(processId);
+      const sessionId = target.dataset.sessionId!;
+      toggleMute(sessionId);
     });
   });
 }
 
-window.addEventListener("DOMContentLoaded", () => {
+window.addEventListener("DOMContentLoaded", async () => {
+  // Subscribe before the initial load so no change falls between the two
+  await subscribeToSessionEvents();
+  startLevelMetering();
+  loadAudioDevices();
   loadAudioSessions();
   setupAutoUpdater();
   
-  // Refresh sessions every 2 seconds
-  setInterval(loadAudioSessions, 2000);
-  
   // Add refresh button listener
-  document.querySelector("#refresh-btn")?.addEventListener("click", loadAudioSessions);
+  document.querySelector("#refresh-btn")?.addEventListener("click", () => {
+    loadAudioDevices();
+    loadAudioSessions();
+  });
+
+  document.querySelector("#device-select")?.addEventListener("change", (event) => {
+    selectedDeviceId = (event.target as HTMLSelectElement).value;
+    loadAudioSessions();
+    loadMasterVolume();
+  });
+
+  document.querySelector("#master-slider")?.addEventListener("input", (event) => {
+    setMasterVolume(parseFloat((event.target as HTMLInputElement).value));
+  });
+
+  document.querySelector("#master-mute-btn")?.addEventListener("click", toggleMasterMute);
   
   // Add manual update check button listener
   document.querySelector("#check-updates-btn")?.addEventListener("click", checkForUpdates);